@@ -0,0 +1,163 @@
+//! Misbehaving receiver for `nft_transfer_call`.
+//!
+//! The NFT contract must get its token back whatever the receiver does. The message picks
+//! how this receiver misbehaves:
+//!
+//! * `"panic"` fails in `nft_on_transfer`.
+//! * `"burn-gas"` runs out of gas in `nft_on_transfer`.
+//! * `"garbage"` answers with something that is not a `bool`.
+//! * `"approve:<account_id>"` approves `<account_id>` on the token before asking for it back.
+//!
+//! Any other message asks for the token back right away.
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U64;
+use near_sdk::{env, ext_contract, log, near_bindgen, AccountId, Balance, Gas, PromiseOrValue};
+
+near_sdk::setup_alloc!();
+
+pub type TokenId = String;
+
+pub const PANIC_MSG: &str = "panic";
+pub const BURN_GAS_MSG: &str = "burn-gas";
+pub const GARBAGE_MSG: &str = "garbage";
+/// Prefix of the message asking to approve the account after it before giving the token back.
+pub const APPROVE_MSG_PREFIX: &str = "approve:";
+
+const GAS_FOR_NFT_APPROVE: Gas = 20_000_000_000_000;
+const GAS_FOR_CALLBACK: Gas = 5_000_000_000_000;
+/// Covers the storage of the approval on the NFT contract.
+const APPROVE_DEPOSIT: Balance = 10_000_000_000_000_000_000_000;
+
+#[ext_contract(ext_nft)]
+trait NonFungibleToken {
+    fn nft_approve(
+        &mut self,
+        token_id: TokenId,
+        account_id: AccountId,
+        expires_at: Option<U64>,
+        msg: Option<String>,
+    );
+}
+
+#[ext_contract(ext_self)]
+trait MaliciousResolver {
+    fn give_back(&self) -> bool;
+    fn garbage(&self) -> String;
+}
+
+#[near_bindgen]
+#[derive(Default, BorshDeserialize, BorshSerialize)]
+pub struct MaliciousReceiver {}
+
+#[near_bindgen]
+impl MaliciousReceiver {
+    pub fn nft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_id: AccountId,
+        token_id: TokenId,
+        msg: String,
+    ) -> PromiseOrValue<bool> {
+        log!(
+            "Received {} from @{} sent by @{} with {}",
+            token_id,
+            previous_owner_id,
+            sender_id,
+            msg
+        );
+        if msg == PANIC_MSG {
+            env::panic(b"Refusing to answer");
+        }
+        if msg == BURN_GAS_MSG {
+            loop {
+                env::sha256(token_id.as_bytes());
+            }
+        }
+        if msg == GARBAGE_MSG {
+            return ext_self::garbage(&env::current_account_id(), 0, GAS_FOR_CALLBACK).into();
+        }
+        if let Some(account_id) = msg.strip_prefix(APPROVE_MSG_PREFIX) {
+            return ext_nft::nft_approve(
+                token_id,
+                account_id.to_string(),
+                None,
+                None,
+                &env::predecessor_account_id(),
+                APPROVE_DEPOSIT,
+                GAS_FOR_NFT_APPROVE,
+            )
+            .then(ext_self::give_back(
+                &env::current_account_id(),
+                0,
+                GAS_FOR_CALLBACK,
+            ))
+            .into();
+        }
+        PromiseOrValue::Value(true)
+    }
+
+    #[private]
+    pub fn give_back(&self) -> bool {
+        true
+    }
+
+    #[private]
+    pub fn garbage(&self) -> String {
+        "not a bool".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::MockedBlockchain;
+    use near_sdk::{testing_env, VMContext};
+
+    fn nft() -> AccountId {
+        String::from("nft.near")
+    }
+
+    fn alice() -> AccountId {
+        String::from("alice.near")
+    }
+
+    fn get_context(predecessor_account_id: AccountId) -> VMContext {
+        VMContext {
+            current_account_id: String::from("malicious.near"),
+            signer_account_id: alice(),
+            signer_account_pk: vec![0, 1, 2],
+            predecessor_account_id,
+            input: vec![],
+            block_index: 0,
+            block_timestamp: 0,
+            account_balance: 1000 * 10u128.pow(24),
+            account_locked_balance: 0,
+            storage_usage: 10u64.pow(6),
+            attached_deposit: 0,
+            prepaid_gas: 2 * 10u64.pow(14),
+            random_seed: vec![0, 1, 2],
+            is_view: false,
+            output_data_receivers: vec![],
+            epoch_height: 19,
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Refusing to answer")]
+    fn panics_when_asked() {
+        testing_env!(get_context(nft()));
+        let mut contract = MaliciousReceiver::default();
+        contract.nft_on_transfer(alice(), alice(), "0".to_string(), PANIC_MSG.to_string());
+    }
+
+    #[test]
+    fn asks_for_the_token_back() {
+        testing_env!(get_context(nft()));
+        let mut contract = MaliciousReceiver::default();
+        match contract.nft_on_transfer(alice(), alice(), "0".to_string(), String::new()) {
+            PromiseOrValue::Value(return_token) => assert!(return_token),
+            PromiseOrValue::Promise(_) => panic!("Expected a value"),
+        }
+    }
+}
@@ -0,0 +1,216 @@
+//! Example receiver for `nft_transfer_call`.
+//!
+//! The vault keeps the tokens sent to it on behalf of their previous owner, who can take
+//! them back with `withdraw`. Sending a token with the message `"return"` makes the vault
+//! refuse it, so the NFT contract gives it back right away.
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::LookupMap;
+use near_sdk::{
+    env, ext_contract, log, near_bindgen, AccountId, Gas, Promise, PromiseOrValue, PromiseResult,
+};
+
+near_sdk::setup_alloc!();
+
+pub type TokenId = String;
+
+/// Message asking the vault to give the token back instead of keeping it.
+pub const RETURN_MSG: &str = "return";
+
+const GAS_FOR_NFT_TRANSFER: Gas = 20_000_000_000_000;
+const GAS_FOR_ON_WITHDRAW: Gas = 10_000_000_000_000;
+
+/// Interface NFT contracts call on the receiver of `nft_transfer_call`, following NEP-171.
+pub trait NonFungibleTokenReceiver {
+    /// Returns `true` when the token should be returned to `previous_owner_id`.
+    fn nft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_id: AccountId,
+        token_id: TokenId,
+        msg: String,
+    ) -> PromiseOrValue<bool>;
+}
+
+#[ext_contract(ext_nft)]
+trait NonFungibleToken {
+    fn nft_transfer(&mut self, receiver_id: AccountId, token_id: TokenId, approval_id: Option<u64>);
+}
+
+#[ext_contract(ext_self)]
+trait VaultResolver {
+    fn on_withdraw(&mut self, nft_contract_id: AccountId, token_id: TokenId, owner_id: AccountId);
+}
+
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Vault {
+    /// Account each token was deposited by, keyed by NFT contract and token.
+    deposits: LookupMap<(AccountId, TokenId), AccountId>,
+}
+
+impl Default for Vault {
+    fn default() -> Self {
+        Self {
+            deposits: LookupMap::new(b"d".to_vec()),
+        }
+    }
+}
+
+#[near_bindgen]
+impl NonFungibleTokenReceiver for Vault {
+    fn nft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_id: AccountId,
+        token_id: TokenId,
+        msg: String,
+    ) -> PromiseOrValue<bool> {
+        let nft_contract_id = env::predecessor_account_id();
+        if msg == RETURN_MSG {
+            log!("Return {} to @{}", token_id, previous_owner_id);
+            return PromiseOrValue::Value(true);
+        }
+
+        log!(
+            "Keep {} from @{} sent by @{}",
+            token_id,
+            previous_owner_id,
+            sender_id
+        );
+        self.deposits
+            .insert(&(nft_contract_id, token_id), &previous_owner_id);
+        PromiseOrValue::Value(false)
+    }
+}
+
+#[near_bindgen]
+impl Vault {
+    /// Sends `token_id` back to the account that deposited it. The deposit is forwarded to
+    /// `nft_transfer` to cover its storage.
+    #[payable]
+    pub fn withdraw(&mut self, nft_contract_id: AccountId, token_id: TokenId) -> Promise {
+        let key = (nft_contract_id, token_id);
+        let owner_id = self.deposits.get(&key).expect("Token not in the vault");
+        if env::predecessor_account_id() != owner_id {
+            env::panic(b"Unauthorized");
+        }
+        self.deposits.remove(&key);
+
+        let (nft_contract_id, token_id) = key;
+        ext_nft::nft_transfer(
+            owner_id.clone(),
+            token_id.clone(),
+            None,
+            &nft_contract_id,
+            env::attached_deposit(),
+            GAS_FOR_NFT_TRANSFER,
+        )
+        .then(ext_self::on_withdraw(
+            nft_contract_id,
+            token_id,
+            owner_id,
+            &env::current_account_id(),
+            0,
+            GAS_FOR_ON_WITHDRAW,
+        ))
+    }
+
+    /// Puts the token back in the vault when the transfer failed.
+    #[private]
+    pub fn on_withdraw(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        owner_id: AccountId,
+    ) {
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            return;
+        }
+        log!("Withdrawal of {} failed", token_id);
+        self.deposits
+            .insert(&(nft_contract_id, token_id), &owner_id);
+    }
+
+    /// Account that deposited `token_id` of `nft_contract_id`, if the vault holds it.
+    pub fn deposit_of(&self, nft_contract_id: AccountId, token_id: TokenId) -> Option<AccountId> {
+        self.deposits.get(&(nft_contract_id, token_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::MockedBlockchain;
+    use near_sdk::{testing_env, VMContext};
+
+    fn vault() -> AccountId {
+        String::from("vault.near")
+    }
+
+    fn nft() -> AccountId {
+        String::from("nft.near")
+    }
+
+    fn alice() -> AccountId {
+        String::from("alice.near")
+    }
+
+    fn get_context(predecessor_account_id: AccountId, attached_deposit: u128) -> VMContext {
+        VMContext {
+            current_account_id: vault(),
+            signer_account_id: alice(),
+            signer_account_pk: vec![0, 1, 2],
+            predecessor_account_id,
+            input: vec![],
+            block_index: 0,
+            block_timestamp: 0,
+            account_balance: 1000 * 10u128.pow(24),
+            account_locked_balance: 0,
+            storage_usage: 10u64.pow(6),
+            attached_deposit,
+            prepaid_gas: 2 * 10u64.pow(14),
+            random_seed: vec![0, 1, 2],
+            is_view: false,
+            output_data_receivers: vec![],
+            epoch_height: 19,
+        }
+    }
+
+    fn returns_token(result: PromiseOrValue<bool>) -> bool {
+        match result {
+            PromiseOrValue::Value(return_token) => return_token,
+            PromiseOrValue::Promise(_) => panic!("Expected a value"),
+        }
+    }
+
+    #[test]
+    fn keeps_token_for_previous_owner() {
+        testing_env!(get_context(nft(), 0));
+        let mut contract = Vault::default();
+        let result = contract.nft_on_transfer(alice(), alice(), "0".to_string(), String::new());
+        assert!(!returns_token(result));
+        assert_eq!(contract.deposit_of(nft(), "0".to_string()), Some(alice()));
+    }
+
+    #[test]
+    fn returns_token_when_asked() {
+        testing_env!(get_context(nft(), 0));
+        let mut contract = Vault::default();
+        let result =
+            contract.nft_on_transfer(alice(), alice(), "0".to_string(), RETURN_MSG.to_string());
+        assert!(returns_token(result));
+        assert_eq!(contract.deposit_of(nft(), "0".to_string()), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unauthorized")]
+    fn only_depositor_can_withdraw() {
+        testing_env!(get_context(nft(), 0));
+        let mut contract = Vault::default();
+        contract.nft_on_transfer(alice(), alice(), "0".to_string(), String::new());
+
+        testing_env!(get_context("bob.near".to_string(), 1));
+        contract.withdraw(nft(), "0".to_string());
+    }
+}
@@ -0,0 +1,160 @@
+//! Gas regression checks for the most common calls.
+//!
+//! Each operation runs against the release build in a local sandbox node. The gas it burns
+//! is compared with a budget in TGas, and the storage it adds on chain is printed next to
+//! it. Run `make gas`, which builds the contract first, to print the measured costs. When a
+//! change makes an operation more expensive on purpose, raise its budget in the same commit
+//! and say what the extra cost pays for. Storage budgets are checked by `tests/costs.rs` in
+//! the contract crate.
+
+use near_workspaces::operations::CallTransaction;
+use near_workspaces::types::{Gas, NearToken};
+use near_workspaces::{Account, Contract};
+use serde_json::json;
+
+const WASM_PATH: &str = "../target/wasm32-unknown-unknown/release/nft_demo.wasm";
+
+const MINT_BUDGET: Gas = Gas::from_tgas(15);
+const TRANSFER_BUDGET: Gas = Gas::from_tgas(10);
+const TRANSFER_TO_HOLDER_BUDGET: Gas = Gas::from_tgas(8);
+const APPROVE_BUDGET: Gas = Gas::from_tgas(10);
+const BUY_BUDGET: Gas = Gas::from_tgas(15);
+const ENUMERATION_BUDGET: Gas = Gas::from_tgas(30);
+
+/// Number of tokens minted before reading them back with `nft_tokens`.
+const ENUMERATED_TOKENS: usize = 20;
+
+/// Gas burnt by a call and the bytes it added to the contract storage.
+struct Cost {
+    gas: Gas,
+    storage: i64,
+}
+
+/// Runs `call` and returns what it cost.
+async fn measure(contract: &Contract, call: CallTransaction) -> Cost {
+    let storage_before = contract.view_account().await.unwrap().storage_usage;
+    let gas = call
+        .max_gas()
+        .transact()
+        .await
+        .unwrap()
+        .into_result()
+        .unwrap()
+        .total_gas_burnt;
+    let storage_after = contract.view_account().await.unwrap().storage_usage;
+    Cost {
+        gas,
+        storage: storage_after as i64 - storage_before as i64,
+    }
+}
+
+fn assert_within_budget(operation: &str, cost: &Cost, budget: Gas) {
+    println!(
+        "{:<28} {:>6} Ggas (budget {} Tgas) {:>6} bytes",
+        operation,
+        cost.gas.as_ggas(),
+        budget.as_tgas(),
+        cost.storage
+    );
+    assert!(
+        cost.gas <= budget,
+        "{} burns {} Ggas, over its budget of {} Tgas",
+        operation,
+        cost.gas.as_ggas(),
+        budget.as_tgas()
+    );
+}
+
+/// Transfer of `token_id` from `account` to `receiver`.
+fn transfer(
+    contract: &Contract,
+    account: &Account,
+    receiver: &Account,
+    token_id: &str,
+) -> CallTransaction {
+    account
+        .call(contract.id(), "nft_transfer")
+        .args_json(json!({ "receiver_id": receiver.id(), "token_id": token_id }))
+        .deposit(NearToken::from_millinear(100))
+}
+
+/// Mint of `token_id` by `account`.
+fn mint(contract: &Contract, account: &Account, token_id: &str) -> CallTransaction {
+    account
+        .call(contract.id(), "nft_mint")
+        .args_json(json!({
+            "token_id": token_id,
+            "metadata": {
+                "title": "Mochi Rising",
+                "description": "Limited edition canvas",
+            },
+        }))
+        .deposit(NearToken::from_millinear(100))
+}
+
+#[tokio::test]
+async fn gas_within_budget() {
+    let worker = near_workspaces::sandbox().await.unwrap();
+    let wasm = std::fs::read(WASM_PATH).expect("Run `make build` first");
+    let contract = worker.dev_deploy(&wasm).await.unwrap();
+    let alice = worker.dev_create_account().await.unwrap();
+    let bob = worker.dev_create_account().await.unwrap();
+
+    let cost = measure(&contract, mint(&contract, &alice, "0")).await;
+    assert_within_budget("nft_mint", &cost, MINT_BUDGET);
+
+    let to_new_receiver = measure(&contract, transfer(&contract, &alice, &bob, "0")).await;
+    assert_within_budget(
+        "nft_transfer (new receiver)",
+        &to_new_receiver,
+        TRANSFER_BUDGET,
+    );
+
+    // Bob already holds a token, so no token set is created and no account id is hashed.
+    measure(&contract, mint(&contract, &alice, "holder")).await;
+    let to_holder = measure(&contract, transfer(&contract, &alice, &bob, "holder")).await;
+    assert_within_budget(
+        "nft_transfer (holder)",
+        &to_holder,
+        TRANSFER_TO_HOLDER_BUDGET,
+    );
+    println!(
+        "{:<28} {:>6} Ggas",
+        "new receiver overhead",
+        to_new_receiver
+            .gas
+            .as_ggas()
+            .saturating_sub(to_holder.gas.as_ggas())
+    );
+
+    let approve = bob
+        .call(contract.id(), "nft_approve")
+        .args_json(json!({ "token_id": "0", "account_id": alice.id() }))
+        .deposit(NearToken::from_millinear(100));
+    let cost = measure(&contract, approve).await;
+    assert_within_budget("nft_approve", &cost, APPROVE_BUDGET);
+
+    let list = bob
+        .call(contract.id(), "nft_list")
+        .args_json(
+            json!({ "token_id": "0", "price": NearToken::from_near(1).as_yoctonear().to_string() }),
+        )
+        .deposit(NearToken::from_millinear(10));
+    measure(&contract, list).await;
+    let buy = alice
+        .call(contract.id(), "nft_buy")
+        .args_json(json!({ "token_id": "0" }))
+        .deposit(NearToken::from_millinear(1100));
+    let cost = measure(&contract, buy).await;
+    assert_within_budget("nft_buy", &cost, BUY_BUDGET);
+
+    for i in 1..ENUMERATED_TOKENS {
+        measure(&contract, mint(&contract, &alice, &i.to_string())).await;
+    }
+    // Views don't report gas, so the page is read in a transaction instead.
+    let page = alice
+        .call(contract.id(), "nft_tokens")
+        .args_json(json!({ "limit": ENUMERATED_TOKENS }));
+    let cost = measure(&contract, page).await;
+    assert_within_budget("nft_tokens (20 tokens)", &cost, ENUMERATION_BUDGET);
+}
@@ -0,0 +1,175 @@
+//! Full `nft_transfer_call` round trips against the example vault in `examples/vault` and
+//! the misbehaving receiver in `examples/malicious-receiver`.
+//!
+//! The contracts run from their release builds in a local sandbox node. Run
+//! `make transfer-call`, which builds them first.
+
+use near_workspaces::types::NearToken;
+use near_workspaces::{Account, Contract};
+use serde_json::{json, Value};
+
+const NFT_WASM_PATH: &str = "../target/wasm32-unknown-unknown/release/nft_demo.wasm";
+const VAULT_WASM_PATH: &str = "../target/wasm32-unknown-unknown/release/nft_vault.wasm";
+const MALICIOUS_WASM_PATH: &str =
+    "../target/wasm32-unknown-unknown/release/nft_malicious_receiver.wasm";
+
+async fn owner_of(contract: &Contract, token_id: &str) -> String {
+    let token: Value = contract
+        .view("nft_token")
+        .args_json(json!({ "token_id": token_id }))
+        .await
+        .unwrap()
+        .json()
+        .unwrap();
+    token["owner_id"].as_str().unwrap().to_string()
+}
+
+/// Sends `token_id` from `account` to `receiver_id` with `msg`, returning whether the
+/// receiver kept it.
+async fn transfer_call(
+    contract: &Contract,
+    account: &Account,
+    receiver_id: &near_workspaces::AccountId,
+    token_id: &str,
+    msg: &str,
+) -> bool {
+    account
+        .call(contract.id(), "nft_transfer_call")
+        .args_json(json!({ "receiver_id": receiver_id, "token_id": token_id, "msg": msg }))
+        .deposit(NearToken::from_millinear(100))
+        .max_gas()
+        .transact()
+        .await
+        .unwrap()
+        .into_result()
+        .unwrap()
+        .json()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn transfer_call_round_trip() {
+    let worker = near_workspaces::sandbox().await.unwrap();
+    let nft_wasm = std::fs::read(NFT_WASM_PATH).expect("Run `make build` first");
+    let vault_wasm = std::fs::read(VAULT_WASM_PATH).expect("Run `make build` first");
+    let nft = worker.dev_deploy(&nft_wasm).await.unwrap();
+    let vault = worker.dev_deploy(&vault_wasm).await.unwrap();
+    let alice = worker.dev_create_account().await.unwrap();
+    let bob = worker.dev_create_account().await.unwrap();
+
+    alice
+        .call(nft.id(), "nft_mint")
+        .args_json(json!({ "token_id": "0", "metadata": { "title": "Mochi Rising" } }))
+        .deposit(NearToken::from_millinear(100))
+        .max_gas()
+        .transact()
+        .await
+        .unwrap()
+        .into_result()
+        .unwrap();
+
+    // The vault refuses the token, so it goes back to Alice.
+    assert!(!transfer_call(&nft, &alice, vault.id(), "0", "return").await);
+    assert_eq!(owner_of(&nft, "0").await, alice.id().to_string());
+
+    // A plain account has no `nft_on_transfer`, the failed call also returns the token.
+    assert!(!transfer_call(&nft, &alice, bob.id(), "0", "").await);
+    assert_eq!(owner_of(&nft, "0").await, alice.id().to_string());
+
+    // The vault keeps the token for Alice.
+    assert!(transfer_call(&nft, &alice, vault.id(), "0", "").await);
+    assert_eq!(owner_of(&nft, "0").await, vault.id().to_string());
+    let depositor: Option<String> = vault
+        .view("deposit_of")
+        .args_json(json!({ "nft_contract_id": nft.id(), "token_id": "0" }))
+        .await
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(depositor, Some(alice.id().to_string()));
+
+    // Only Alice can take it back out.
+    let result = bob
+        .call(vault.id(), "withdraw")
+        .args_json(json!({ "nft_contract_id": nft.id(), "token_id": "0" }))
+        .max_gas()
+        .transact()
+        .await
+        .unwrap();
+    assert!(result.is_failure());
+
+    alice
+        .call(vault.id(), "withdraw")
+        .args_json(json!({ "nft_contract_id": nft.id(), "token_id": "0" }))
+        .deposit(NearToken::from_millinear(100))
+        .max_gas()
+        .transact()
+        .await
+        .unwrap()
+        .into_result()
+        .unwrap();
+    assert_eq!(owner_of(&nft, "0").await, alice.id().to_string());
+}
+
+#[tokio::test]
+async fn malicious_receiver_gives_token_back() {
+    let worker = near_workspaces::sandbox().await.unwrap();
+    let nft_wasm = std::fs::read(NFT_WASM_PATH).expect("Run `make build` first");
+    let malicious_wasm = std::fs::read(MALICIOUS_WASM_PATH).expect("Run `make build` first");
+    let nft = worker.dev_deploy(&nft_wasm).await.unwrap();
+    let malicious = worker.dev_deploy(&malicious_wasm).await.unwrap();
+    let alice = worker.dev_create_account().await.unwrap();
+    let bob = worker.dev_create_account().await.unwrap();
+
+    alice
+        .call(nft.id(), "nft_mint")
+        .args_json(json!({ "token_id": "0", "metadata": { "title": "Mochi Rising" } }))
+        .deposit(NearToken::from_millinear(100))
+        .max_gas()
+        .transact()
+        .await
+        .unwrap()
+        .into_result()
+        .unwrap();
+
+    let approve_msg = format!("approve:{}", bob.id());
+    for msg in ["panic", "burn-gas", "garbage", approve_msg.as_str(), ""].iter() {
+        let result = alice
+            .call(nft.id(), "nft_transfer_call")
+            .args_json(json!({ "receiver_id": malicious.id(), "token_id": "0", "msg": msg }))
+            .deposit(NearToken::from_millinear(100))
+            .max_gas()
+            .transact()
+            .await
+            .unwrap()
+            .into_result()
+            .unwrap();
+        let kept: bool = result.json().unwrap();
+        assert!(!kept, "kept with {:?}", msg);
+        assert_eq!(owner_of(&nft, "0").await, alice.id().to_string());
+
+        // The return is announced like any other transfer.
+        let event = format!(
+            "EVENT_JSON:{{\"standard\":\"nep171\",\"version\":\"1.1.0\",\"event\":\"nft_transfer\",\"data\":[{{\"old_owner_id\":\"{}\",\"new_owner_id\":\"{}\",\"token_ids\":[\"0\"]}}]}}",
+            malicious.id(),
+            alice.id()
+        );
+        assert!(
+            result.logs().contains(&event.as_str()),
+            "no return event with {:?}",
+            msg
+        );
+    }
+
+    // The approval the receiver made before giving the token back is gone.
+    let token: Value = nft
+        .view("nft_token")
+        .args_json(json!({ "token_id": "0" }))
+        .await
+        .unwrap()
+        .json()
+        .unwrap();
+    assert!(token["approved_account_ids"]
+        .get(bob.id().as_str())
+        .is_none());
+}
@@ -0,0 +1,446 @@
+use crate::*;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::{ext_contract, serde_json, Gas, Promise};
+use std::collections::HashMap;
+
+const GAS_FOR_NFT_ON_APPROVE: Gas = 25_000_000_000_000;
+
+/// Sale terms marketplaces expect in the `msg` of `nft_approve`, logged when present.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SaleArgs {
+    pub price: U128,
+    pub ft_token_id: Option<AccountId>, // fungible token to pay with, NEAR when `None`
+}
+
+impl Token {
+    /// Whether `account_id` holds an unexpired approval, under `approval_id` if given.
+    pub(crate) fn is_approved(&self, account_id: &AccountId, approval_id: Option<u64>) -> bool {
+        let id = match self.approved_account_ids.get(account_id) {
+            Some(id) => *id,
+            None => return false,
+        };
+        let expired = matches!(
+            self.approvals_expire_at.get(account_id),
+            Some(expires_at) if env::block_timestamp() >= *expires_at
+        );
+        !expired && (approval_id.is_none() || approval_id == Some(id))
+    }
+
+    /// Drops the approvals that have expired.
+    pub(crate) fn remove_expired_approvals(&mut self) {
+        let now = env::block_timestamp();
+        let expired: Vec<AccountId> = self
+            .approvals_expire_at
+            .iter()
+            .filter(|(_, expires_at)| now >= **expires_at)
+            .map(|(account_id, _)| account_id.clone())
+            .collect();
+        for account_id in expired {
+            self.approved_account_ids.remove(&account_id);
+            self.approvals_expire_at.remove(&account_id);
+        }
+    }
+}
+
+#[ext_contract(ext_approval_receiver)]
+pub trait NonFungibleTokenApprovalReceiver {
+    fn nft_on_approve(
+        &mut self,
+        token_id: TokenId,
+        owner_id: AccountId,
+        approval_id: u64,
+        msg: String,
+    ) -> near_sdk::PromiseOrValue<String>;
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// Lets `account_id` transfer `token_id` for its owner, until `expires_at` if given.
+    /// With a `msg`, ex. `SaleArgs`, the approved marketplace is notified through
+    /// `nft_on_approve` so it can list the token right away. The deposit covers the storage
+    /// of the approval.
+    #[payable]
+    pub fn nft_approve(
+        &mut self,
+        token_id: TokenId,
+        account_id: ValidAccountId,
+        expires_at: Option<U64>,
+        msg: Option<String>,
+    ) -> Option<Promise> {
+        self.assert_not_paused();
+        let mut token = self.tokens_by_id.get(&token_id).expect("Token not found");
+
+        if env::predecessor_account_id() != token.owner_id {
+            env::panic(b"Unauthorized");
+        }
+        token.assert_transferable();
+
+        if let Some(expires_at) = expires_at {
+            assert!(
+                expires_at.0 > env::block_timestamp(),
+                "Approval must expire in the future"
+            );
+        }
+
+        let initial_storage_usage = env::storage_usage();
+        token.remove_expired_approvals();
+        let approval_id = token.next_approval_id;
+        token.next_approval_id += 1;
+        token
+            .approved_account_ids
+            .insert(account_id.clone().into(), approval_id);
+        match expires_at {
+            Some(expires_at) => token
+                .approvals_expire_at
+                .insert(account_id.clone().into(), expires_at.0),
+            None => token.approvals_expire_at.remove(account_id.as_ref()),
+        };
+        self.tokens_by_id.insert(&token_id, &token);
+        self.internal_charge_storage(initial_storage_usage);
+
+        let msg = msg?;
+        let gas = env::prepaid_gas()
+            .checked_sub(GAS_FOR_NFT_ON_APPROVE)
+            .expect("More gas is required");
+        // Other marketplaces define their own `msg`, it is passed on as is.
+        if let Ok(sale_args) = serde_json::from_str::<SaleArgs>(&msg) {
+            log!(
+                "Approve @{} to sell {} for {}",
+                account_id,
+                token_id,
+                sale_args.price.0
+            );
+        }
+        Some(ext_approval_receiver::nft_on_approve(
+            token_id,
+            token.owner_id,
+            approval_id,
+            msg,
+            account_id.as_ref(),
+            0,
+            gas,
+        ))
+    }
+
+    /// Revokes the approval of `account_id`, refunding its storage to the owner.
+    pub fn nft_revoke(&mut self, token_id: TokenId, account_id: ValidAccountId) {
+        self.assert_not_sunset();
+        let mut token = self.tokens_by_id.get(&token_id).expect("Token not found");
+
+        if env::predecessor_account_id() != token.owner_id {
+            env::panic(b"Unauthorized");
+        }
+
+        let initial_storage_usage = env::storage_usage();
+        token.approvals_expire_at.remove(account_id.as_ref());
+        if token
+            .approved_account_ids
+            .remove(account_id.as_ref())
+            .is_some()
+        {
+            self.tokens_by_id.insert(&token_id, &token);
+        }
+        self.internal_charge_storage(initial_storage_usage);
+    }
+
+    /// Revokes every approval of `token_id`, refunding their storage to the owner.
+    pub fn nft_revoke_all(&mut self, token_id: TokenId) {
+        self.assert_not_sunset();
+        let mut token = self.tokens_by_id.get(&token_id).expect("Token not found");
+
+        if env::predecessor_account_id() != token.owner_id {
+            env::panic(b"Unauthorized");
+        }
+
+        let initial_storage_usage = env::storage_usage();
+        if !token.approved_account_ids.is_empty() {
+            token.approved_account_ids = HashMap::new();
+            token.approvals_expire_at = HashMap::new();
+            self.tokens_by_id.insert(&token_id, &token);
+        }
+        self.internal_charge_storage(initial_storage_usage);
+    }
+
+    /// Lets `operator_id` transfer any token of the caller, including tokens received later,
+    /// until revoked with `nft_revoke_all_for`. The deposit covers the storage of the grant.
+    #[payable]
+    pub fn nft_approve_all(&mut self, operator_id: ValidAccountId) {
+        self.assert_not_paused();
+        let owner_id = env::predecessor_account_id();
+        assert_ne!(
+            operator_id.as_ref(),
+            &owner_id,
+            "Cannot approve yourself as operator"
+        );
+
+        let initial_storage_usage = env::storage_usage();
+        let mut operators = self.operators.get(&owner_id).unwrap_or_else(|| {
+            UnorderedSet::new(
+                StorageKey::OperatorsInner {
+                    account_id_hash: hash_account_id(&owner_id),
+                }
+                .try_to_vec()
+                .unwrap(),
+            )
+        });
+        operators.insert(operator_id.as_ref());
+        self.operators.insert(&owner_id, &operators);
+        self.internal_charge_storage(initial_storage_usage);
+    }
+
+    /// Revokes `operator_id`, refunding the storage of the grant to the caller.
+    pub fn nft_revoke_all_for(&mut self, operator_id: ValidAccountId) {
+        self.assert_not_sunset();
+        let owner_id = env::predecessor_account_id();
+        let mut operators = match self.operators.get(&owner_id) {
+            Some(operators) => operators,
+            None => return,
+        };
+        let initial_storage_usage = env::storage_usage();
+        operators.remove(operator_id.as_ref());
+        if operators.is_empty() {
+            self.operators.remove(&owner_id);
+        } else {
+            self.operators.insert(&owner_id, &operators);
+        }
+        self.internal_charge_storage(initial_storage_usage);
+    }
+
+    /// Page of the operators allowed to transfer every token of `account_id`.
+    pub fn nft_operators(
+        &self,
+        account_id: ValidAccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<AccountId> {
+        let operators = match self.operators.get(account_id.as_ref()) {
+            Some(operators) => operators,
+            None => return vec![],
+        };
+        let from_index = from_index.map_or(0, |index| index.0 as usize);
+        let limit = limit.map_or(usize::MAX, |limit| limit as usize);
+        operators.iter().skip(from_index).take(limit).collect()
+    }
+
+    pub(crate) fn is_operator(&self, owner_id: &AccountId, operator_id: &AccountId) -> bool {
+        matches!(
+            self.operators.get(owner_id),
+            Some(operators) if operators.contains(operator_id)
+        )
+    }
+
+    /// Whether `approved_account_id` may transfer `token_id`, under `approval_id` if given.
+    /// Expired approvals count as revoked. Operators of the owner may transfer any token.
+    pub fn nft_is_approved(
+        &self,
+        token_id: TokenId,
+        approved_account_id: ValidAccountId,
+        approval_id: Option<u64>,
+    ) -> bool {
+        let token = self.tokens_by_id.get(&token_id).expect("Token not found");
+        token.is_approved(approved_account_id.as_ref(), approval_id)
+            || self.is_operator(&token.owner_id, approved_account_id.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn approvals_refunded_to_owner_on_transfer() {
+        let (mut contract, _) = helper_mint();
+        contract.nft_mint(
+            "1".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+        testing_env!(get_context(nft(), 10u128.pow(23)));
+        let balance = contract.storage_deposit(None);
+        testing_env!(get_context(nft(), 0));
+        contract.nft_approve(
+            "0".to_string(),
+            ValidAccountId::try_from(bob()).unwrap(),
+            None,
+            None,
+        );
+
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        contract.nft_transfer(
+            ValidAccountId::try_from(owner()).unwrap(),
+            "0".to_string(),
+            Some(0),
+        );
+        let refunded = contract
+            .storage_balance_of(ValidAccountId::try_from(nft()).unwrap())
+            .unwrap();
+        assert_eq!(refunded.available, balance.available);
+    }
+
+    #[test]
+    fn approved_account_transfers() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(nft(), 10u128.pow(24)));
+        contract.nft_approve(
+            "0".to_string(),
+            ValidAccountId::try_from(bob()).unwrap(),
+            None,
+            None,
+        );
+        assert!(contract.nft_is_approved(
+            "0".to_string(),
+            ValidAccountId::try_from(bob()).unwrap(),
+            Some(0)
+        ));
+
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        contract.nft_transfer(
+            ValidAccountId::try_from(owner()).unwrap(),
+            "0".to_string(),
+            Some(0),
+        );
+        let token = contract.nft_token("0".to_string()).unwrap();
+        assert_eq!(token.owner_id, owner());
+        assert!(token.approved_account_ids.is_empty());
+        assert_eq!(
+            contract
+                .nft_incoming_transfer("0".to_string())
+                .unwrap()
+                .sender_id,
+            nft()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Unauthorized")]
+    fn transfer_with_expired_approval() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(nft(), 10u128.pow(24)));
+        contract.nft_approve(
+            "0".to_string(),
+            ValidAccountId::try_from(bob()).unwrap(),
+            Some(U64(100)),
+            None,
+        );
+        assert!(contract.nft_is_approved(
+            "0".to_string(),
+            ValidAccountId::try_from(bob()).unwrap(),
+            None
+        ));
+
+        let mut context = get_context(bob(), 10u128.pow(24));
+        context.block_timestamp = 100;
+        testing_env!(context);
+        assert!(!contract.nft_is_approved(
+            "0".to_string(),
+            ValidAccountId::try_from(bob()).unwrap(),
+            None
+        ));
+        assert!(contract
+            .nft_token("0".to_string())
+            .unwrap()
+            .approved_account_ids
+            .is_empty());
+        contract.nft_transfer(
+            ValidAccountId::try_from(owner()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+    }
+
+    #[test]
+    fn approve_with_other_marketplace_msg() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(nft(), 10u128.pow(24)));
+        let promise = contract.nft_approve(
+            "0".to_string(),
+            ValidAccountId::try_from("market.near").unwrap(),
+            None,
+            Some("{\"market_type\":\"sale\"}".to_string()),
+        );
+        assert!(promise.is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "Unauthorized")]
+    fn transfer_after_revoke() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(nft(), 10u128.pow(24)));
+        contract.nft_approve(
+            "0".to_string(),
+            ValidAccountId::try_from(bob()).unwrap(),
+            None,
+            None,
+        );
+        contract.nft_revoke("0".to_string(), ValidAccountId::try_from(bob()).unwrap());
+
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        contract.nft_transfer(
+            ValidAccountId::try_from(owner()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+    }
+
+    #[test]
+    fn operator_transfers_later_token() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(nft(), 10u128.pow(24)));
+        contract.nft_approve_all(ValidAccountId::try_from(bob()).unwrap());
+        contract.nft_mint(
+            "1".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+        assert_eq!(
+            contract.nft_operators(ValidAccountId::try_from(nft()).unwrap(), None, None),
+            vec![bob()]
+        );
+        assert!(contract.nft_is_approved(
+            "1".to_string(),
+            ValidAccountId::try_from(bob()).unwrap(),
+            None
+        ));
+
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        contract.nft_transfer(
+            ValidAccountId::try_from(owner()).unwrap(),
+            "1".to_string(),
+            None,
+        );
+        assert_eq!(
+            contract.nft_token("1".to_string()).unwrap().owner_id,
+            owner()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Unauthorized")]
+    fn revoked_operator_transfers() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(nft(), 10u128.pow(24)));
+        contract.nft_approve_all(ValidAccountId::try_from(bob()).unwrap());
+        contract.nft_revoke_all_for(ValidAccountId::try_from(bob()).unwrap());
+        assert!(contract
+            .nft_operators(ValidAccountId::try_from(nft()).unwrap(), None, None)
+            .is_empty());
+
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        contract.nft_transfer(
+            ValidAccountId::try_from(owner()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+    }
+}
@@ -0,0 +1,375 @@
+use crate::*;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::Promise;
+
+/// A bid in the last `AUCTION_EXTENSION` nanoseconds pushes the end back to that much time
+/// after the bid, so bidders can answer last-second bids.
+pub const AUCTION_EXTENSION: u64 = 10 * 60 * 1_000_000_000;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Auction {
+    pub token_id: TokenId,
+    pub owner_id: AccountId,
+    pub reserve_price: U128, // minimum accepted bid in yoctoNEAR
+    pub ends_at: U64,        // block timestamp in nanoseconds
+    pub highest_bid: Option<Bid>,
+}
+
+/// Bid held in escrow by the contract until it is outbid or the auction ends.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Bid {
+    pub bidder_id: AccountId,
+    pub amount: U128, // in yoctoNEAR
+}
+
+#[cfg(feature = "marketplace")]
+#[near_bindgen]
+impl NftContract {
+    /// Auctions `token_id` for `duration` nanoseconds. The deposit covers the storage of the
+    /// auction, which is refunded once it is settled or cancelled.
+    #[payable]
+    pub fn auction_create(&mut self, token_id: TokenId, reserve_price: U128, duration: U64) {
+        self.assert_not_paused();
+        let token = self.tokens_by_id.get(&token_id).expect("Token not found");
+
+        if env::predecessor_account_id() != token.owner_id {
+            env::panic(b"Unauthorized");
+        }
+
+        token.assert_transferable();
+        assert!(duration.0 > 0, "Duration must be greater than zero");
+        assert!(
+            self.auctions.get(&token_id).is_none(),
+            "Token is already in auction"
+        );
+        assert!(self.listings.get(&token_id).is_none(), "Token is listed");
+
+        let initial_storage_usage = env::storage_usage();
+        let auction = Auction {
+            token_id: token_id.clone(),
+            owner_id: token.owner_id,
+            reserve_price,
+            ends_at: math::add_u64(env::block_timestamp(), duration.0, "End").into(),
+            highest_bid: None,
+        };
+        self.auctions.insert(&token_id, &auction);
+        self.auctions_by_end
+            .insert(&(auction.ends_at.0, token_id.clone()), &());
+        self.internal_record_activity(&token_id, ActivityKind::AuctionCreate);
+        self.internal_emit_extension(NftEvent::AuctionCreate(vec![AuctionCreateLog {
+            token_id,
+            owner_id: auction.owner_id,
+            reserve_price,
+            ends_at: auction.ends_at,
+        }]));
+        self.internal_charge_storage(initial_storage_usage);
+    }
+
+    /// Cancels an auction that has no bids yet.
+    pub fn auction_cancel(&mut self, token_id: TokenId) {
+        self.assert_not_paused();
+        let auction = self
+            .auctions
+            .get(&token_id)
+            .expect("Token is not in auction");
+
+        if env::predecessor_account_id() != auction.owner_id {
+            env::panic(b"Unauthorized");
+        }
+        assert!(auction.highest_bid.is_none(), "Auction has bids");
+
+        self.internal_remove_auction(&token_id);
+        self.internal_record_activity(&token_id, ActivityKind::AuctionCancel);
+        self.internal_emit_extension(NftEvent::AuctionCancel(vec![AuctionCancelLog { token_id }]));
+    }
+
+    /// Bids the attached deposit. It must reach the reserve price and beat the highest bid,
+    /// which is refunded to its bidder.
+    #[payable]
+    pub fn auction_bid(&mut self, token_id: TokenId) {
+        self.assert_not_paused();
+        let bidder_id = env::predecessor_account_id();
+        self.assert_not_blacklisted(&bidder_id);
+        let mut auction = self
+            .auctions
+            .get(&token_id)
+            .expect("Token is not in auction");
+        let now = env::block_timestamp();
+        let amount = env::attached_deposit();
+
+        assert!(now < auction.ends_at.0, "Auction has ended");
+        assert_ne!(bidder_id, auction.owner_id, "Owner cannot bid");
+        assert!(
+            amount >= auction.reserve_price.0,
+            "Bid is below the reserve price"
+        );
+        if let Some(bid) = &auction.highest_bid {
+            assert!(
+                amount > bid.amount.0,
+                "Bid must be higher than {}",
+                bid.amount.0
+            );
+            Promise::new(bid.bidder_id.clone()).transfer(bid.amount.0);
+        }
+
+        if auction.ends_at.0 - now < AUCTION_EXTENSION {
+            self.auctions_by_end
+                .remove(&(auction.ends_at.0, token_id.clone()));
+            auction.ends_at = (now + AUCTION_EXTENSION).into();
+            self.auctions_by_end
+                .insert(&(auction.ends_at.0, token_id.clone()), &());
+        }
+        auction.highest_bid = Some(Bid {
+            bidder_id: bidder_id.clone(),
+            amount: amount.into(),
+        });
+        self.auctions.insert(&token_id, &auction);
+
+        self.internal_emit_extension(NftEvent::AuctionBid(vec![AuctionBidLog {
+            token_id,
+            bidder_id,
+            amount: amount.into(),
+            ends_at: auction.ends_at,
+        }]));
+    }
+
+    /// Closes an ended auction: the token goes to the highest bidder and the bid is split
+    /// between the owner and the royalty payees.
+    /// Without bids, or when the token can no longer go to the bidder (e.g. either account
+    /// was blacklisted), the token stays with its owner and the bid is refunded.
+    /// Anyone can call it.
+    pub fn auction_settle(&mut self, token_id: TokenId) {
+        self.assert_not_paused();
+        let auction = self
+            .auctions
+            .get(&token_id)
+            .expect("Token is not in auction");
+        assert!(
+            env::block_timestamp() >= auction.ends_at.0,
+            "Auction has not ended"
+        );
+
+        self.internal_remove_auction_records(&auction);
+
+        let mut winner = None;
+        if let Some(bid) = auction.highest_bid {
+            let token = self.tokens_by_id.get(&token_id).unwrap();
+            if self.internal_can_transfer(&token, &bid.bidder_id) {
+                self.internal_pay_out(&token, bid.amount.0);
+                self.internal_transfer(token, &bid.bidder_id, None, Some(bid.amount.0));
+                self.internal_record_activity(&token_id, ActivityKind::Sale);
+                winner = Some(bid);
+            } else {
+                log!(
+                    "Token {} cannot be transferred to @{}, refunding the bid",
+                    token_id,
+                    bid.bidder_id
+                );
+                Promise::new(bid.bidder_id).transfer(bid.amount.0);
+            }
+        }
+
+        self.internal_emit_extension(NftEvent::AuctionSettle(vec![AuctionSettleLog {
+            token_id,
+            owner_id: auction.owner_id,
+            winner_id: winner.as_ref().map(|bid| bid.bidder_id.clone()),
+            amount: winner.map(|bid| bid.amount),
+        }]));
+    }
+
+    pub fn nft_auction(&self, token_id: TokenId) -> Option<Auction> {
+        self.auctions.get(&token_id)
+    }
+
+    /// Auctions that have not ended yet, the closest to ending first.
+    pub fn auctions_ending_soon(&self, limit: Option<u64>) -> Vec<Auction> {
+        let limit = limit.map_or(usize::MAX, |limit| limit as usize);
+        self.auctions_by_end
+            .iter_from((env::block_timestamp(), TokenId::new()))
+            .take(limit)
+            .map(|((_, token_id), _)| self.auctions.get(&token_id).unwrap())
+            .collect()
+    }
+}
+
+impl NftContract {
+    /// Panics if `token_id` is in an auction with bids, whose bidder is owed the token.
+    pub(crate) fn assert_no_auction_bids(&self, token_id: &TokenId) {
+        assert!(
+            !matches!(self.auctions.get(token_id), Some(auction) if auction.highest_bid.is_some()),
+            "Token is in an auction with bids"
+        );
+    }
+
+    /// Removes the auction on `token_id`, refunding the highest bid if any.
+    pub(crate) fn internal_remove_auction(&mut self, token_id: &TokenId) -> Option<Auction> {
+        let auction = self.auctions.get(token_id)?;
+        self.internal_remove_auction_records(&auction);
+        if let Some(bid) = &auction.highest_bid {
+            Promise::new(bid.bidder_id.clone()).transfer(bid.amount.0);
+        }
+        Some(auction)
+    }
+
+    /// Removes the records of `auction` and refunds their storage to the owner, who paid for
+    /// it. The contract paid for the bid, so its bytes stay with it.
+    fn internal_remove_auction_records(&mut self, auction: &Auction) {
+        let initial_storage_usage = env::storage_usage();
+        self.auctions.remove(&auction.token_id);
+        self.auctions_by_end
+            .remove(&(auction.ends_at.0, auction.token_id.clone()));
+        let freed = initial_storage_usage.saturating_sub(env::storage_usage());
+        let bid = auction
+            .highest_bid
+            .as_ref()
+            .map_or(0, |bid| bid.try_to_vec().unwrap().len() as u64);
+        self.internal_refund_storage(&auction.owner_id, freed.saturating_sub(bid));
+    }
+}
+
+#[cfg(all(test, feature = "marketplace"))]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U128;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn auctions_sorted_by_end() {
+        let (mut contract, mut context) = helper_mint();
+        contract.nft_mint(
+            "1".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.nft_mint(
+            "2".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.auction_create("0".to_string(), U128(1), U64(300));
+        contract.auction_create("1".to_string(), U128(1), U64(100));
+        contract.auction_create("2".to_string(), U128(1), U64(200));
+
+        let ids = |auctions: Vec<Auction>| {
+            auctions
+                .into_iter()
+                .map(|auction| auction.token_id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            ids(contract.auctions_ending_soon(None)),
+            vec!["1", "2", "0"]
+        );
+
+        context.block_timestamp = 150;
+        testing_env!(context);
+        contract.auction_cancel("0".to_string());
+        assert_eq!(ids(contract.auctions_ending_soon(Some(5))), vec!["2"]);
+    }
+
+    #[test]
+    fn auction_bid_and_settle() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(context);
+        contract.auction_create("0".to_string(), U128(10), U64(2 * AUCTION_EXTENSION));
+
+        testing_env!(get_context(bob(), 10));
+        contract.auction_bid("0".to_string());
+        testing_env!(get_context(owner(), 20));
+        contract.auction_bid("0".to_string());
+        let bid = contract.nft_auction("0".to_string()).unwrap().highest_bid;
+        assert_eq!(bid.unwrap().bidder_id, owner());
+
+        let mut context = get_context(bob(), 0);
+        context.block_timestamp = 2 * AUCTION_EXTENSION;
+        testing_env!(context);
+        contract.auction_settle("0".to_string());
+        assert_eq!(
+            contract.nft_token("0".to_string()).unwrap().owner_id,
+            owner()
+        );
+        assert!(contract.nft_auction("0".to_string()).is_none());
+        let history = contract.nft_history("0".to_string(), None, None);
+        assert_eq!(history[0].price, Some(U128(20)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Token is in an auction with bids")]
+    fn transfer_token_with_auction_bids() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(context.clone());
+        contract.auction_create("0".to_string(), U128(10), U64(100));
+        testing_env!(get_context(bob(), 10));
+        contract.auction_bid("0".to_string());
+
+        testing_env!(get_context(nft(), 1));
+        contract.nft_transfer(
+            ValidAccountId::try_from(owner()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+    }
+
+    #[test]
+    fn settle_refunds_blacklisted_bidder() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(context);
+        contract.auction_create("0".to_string(), U128(10), U64(100));
+        testing_env!(get_context(bob(), 10));
+        contract.auction_bid("0".to_string());
+        testing_env!(get_context(owner(), 0));
+        contract.blacklist_add(ValidAccountId::try_from(bob()).unwrap());
+
+        let mut context = get_context(owner(), 0);
+        context.block_timestamp = AUCTION_EXTENSION + 100;
+        testing_env!(context);
+        contract.auction_settle("0".to_string());
+        assert_eq!(contract.nft_token("0".to_string()).unwrap().owner_id, nft());
+        assert!(contract.nft_auction("0".to_string()).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "End overflow")]
+    fn auction_end_overflow() {
+        let (mut contract, mut context) = helper_mint();
+        context.block_timestamp = 1;
+        testing_env!(context);
+        contract.auction_create("0".to_string(), U128(10), U64(u64::MAX));
+    }
+
+    #[test]
+    fn late_bid_extends_auction() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(context);
+        contract.auction_create("0".to_string(), U128(10), U64(100));
+
+        let mut context = get_context(bob(), 10);
+        context.block_timestamp = 50;
+        testing_env!(context);
+        contract.auction_bid("0".to_string());
+        assert_eq!(
+            contract.nft_auction("0".to_string()).unwrap().ends_at,
+            U64(50 + AUCTION_EXTENSION)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Auction has not ended")]
+    fn settle_running_auction() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(context);
+        contract.auction_create("0".to_string(), U128(10), U64(100));
+        contract.auction_settle("0".to_string());
+    }
+}
@@ -0,0 +1,96 @@
+use crate::*;
+
+/// Number of tokens `nft_transfer_batch` can move in one call.
+pub const MAX_BATCH_TRANSFER: usize = 100;
+
+#[near_bindgen]
+impl NftContract {
+    /// Transfers every `(token_id, receiver_id)` pair, all of them or none, as the owner
+    /// of the tokens or an approved account. The deposit covers the storage of the new owners.
+    #[payable]
+    pub fn nft_transfer_batch(&mut self, transfers: Vec<(TokenId, ValidAccountId)>) {
+        assert!(!transfers.is_empty(), "Nothing to transfer");
+        assert!(
+            transfers.len() <= MAX_BATCH_TRANSFER,
+            "Cannot transfer more than {} tokens at once",
+            MAX_BATCH_TRANSFER
+        );
+
+        let initial_storage_usage = env::storage_usage();
+        self.transfer_logs = Some(vec![]);
+        for (token_id, receiver_id) in transfers {
+            self.internal_transfer_by_owner(&token_id, receiver_id.as_ref(), None);
+        }
+        let logs = self.transfer_logs.take().unwrap();
+        self.internal_emit(NftEvent::NftTransfer(logs));
+        self.internal_charge_storage(initial_storage_usage);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::test_utils::get_logs;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn transfer_batch_with_one_event() {
+        let (mut contract, context) = helper_mint();
+        contract.nft_mint(
+            "1".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.nft_mint(
+            "2".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        testing_env!(context);
+        contract.nft_transfer_batch(vec![
+            ("0".to_string(), ValidAccountId::try_from(bob()).unwrap()),
+            ("1".to_string(), ValidAccountId::try_from(owner()).unwrap()),
+            ("2".to_string(), ValidAccountId::try_from(bob()).unwrap()),
+        ]);
+        assert_eq!(contract.nft_token("2".to_string()).unwrap().owner_id, bob());
+        let events: Vec<String> = get_logs()
+            .into_iter()
+            .filter(|log| log.starts_with("EVENT_JSON:"))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                "EVENT_JSON:{\"standard\":\"nep171\",\"version\":\"1.1.0\",\"event\":\"nft_transfer\",\"data\":[{\"old_owner_id\":\"nft.near\",\"new_owner_id\":\"bob.near\",\"token_ids\":[\"0\",\"2\"]},{\"old_owner_id\":\"nft.near\",\"new_owner_id\":\"alice_near\",\"token_ids\":[\"1\"]}]}"
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Unauthorized")]
+    fn transfer_batch_is_all_or_nothing() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        contract.nft_mint(
+            "1".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.nft_transfer_batch(vec![
+            ("1".to_string(), ValidAccountId::try_from(owner()).unwrap()),
+            ("0".to_string(), ValidAccountId::try_from(owner()).unwrap()),
+        ]);
+    }
+}
@@ -0,0 +1,91 @@
+use crate::*;
+use near_sdk::json_types::U128;
+
+#[near_bindgen]
+impl NftContract {
+    /// Prevents `account_id` from minting, sending or receiving tokens.
+    pub fn blacklist_add(&mut self, account_id: ValidAccountId) {
+        self.assert_admin();
+        assert!(
+            self.blacklist.insert(account_id.as_ref()),
+            "Account is already blacklisted"
+        );
+        self.internal_emit_extension(NftEvent::BlacklistAdd(vec![BlacklistLog {
+            account_id: account_id.into(),
+        }]));
+    }
+
+    pub fn blacklist_remove(&mut self, account_id: ValidAccountId) {
+        self.assert_admin();
+        assert!(
+            self.blacklist.remove(account_id.as_ref()),
+            "Account is not blacklisted"
+        );
+        self.internal_emit_extension(NftEvent::BlacklistRemove(vec![BlacklistLog {
+            account_id: account_id.into(),
+        }]));
+    }
+
+    pub fn is_blacklisted(&self, account_id: ValidAccountId) -> bool {
+        self.blacklist.contains(account_id.as_ref())
+    }
+
+    pub fn blacklisted_accounts(
+        &self,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<AccountId> {
+        let from_index = from_index.map_or(0, |index| index.0 as usize);
+        let limit = limit.map_or(usize::MAX, |limit| limit as usize);
+        self.blacklist.iter().skip(from_index).take(limit).collect()
+    }
+
+    pub(crate) fn assert_not_blacklisted(&self, account_id: &AccountId) {
+        if self.blacklist.contains(account_id) {
+            env::panic(format!("Account {} is blacklisted", account_id).as_bytes());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::serde::export::TryFrom;
+    #[cfg(feature = "verbose-events")]
+    use near_sdk::test_utils::get_logs;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[cfg(feature = "verbose-events")]
+    #[test]
+    fn blacklist_emits_events() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(owner(), 0));
+        contract.blacklist_add(ValidAccountId::try_from(bob()).unwrap());
+        assert!(contract.is_blacklisted(ValidAccountId::try_from(bob()).unwrap()));
+        contract.blacklist_remove(ValidAccountId::try_from(bob()).unwrap());
+        assert!(!contract.is_blacklisted(ValidAccountId::try_from(bob()).unwrap()));
+        assert_eq!(
+            get_logs(),
+            vec![
+                r#"EVENT_JSON:{"standard":"nft-demo","version":"1.0.0","event":"blacklist_add","data":[{"account_id":"bob.near"}]}"#,
+                r#"EVENT_JSON:{"standard":"nft-demo","version":"1.0.0","event":"blacklist_remove","data":[{"account_id":"bob.near"}]}"#,
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Account bob.near is blacklisted")]
+    fn transfer_to_blacklisted() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(get_context(owner(), 0));
+        contract.blacklist_add(ValidAccountId::try_from(bob()).unwrap());
+        testing_env!(context);
+        contract.nft_transfer(
+            ValidAccountId::try_from(bob()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+    }
+}
@@ -0,0 +1,203 @@
+use crate::*;
+use near_sdk::json_types::U64;
+
+/// Longest destination chain name or address `bridge_lock` accepts.
+pub const MAX_BRIDGE_FIELD_LEN: usize = 128;
+
+/// Token held by the contract while it is mirrored on another chain.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BridgeLock {
+    pub token_id: TokenId,
+    pub owner_id: AccountId, // owner before the lock
+    pub destination_chain: String,
+    pub destination_address: String,
+    pub locked_at: U64,
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// Lets `bridge_id`, e.g. the relayer account, unlock tokens along with the admin.
+    pub fn set_bridge_account(&mut self, bridge_id: Option<ValidAccountId>) {
+        self.assert_admin();
+        self.bridge_id = bridge_id.map(|bridge_id| bridge_id.into());
+    }
+
+    pub fn bridge_account(&self) -> Option<AccountId> {
+        self.bridge_id.clone()
+    }
+
+    /// Escrows `token_id` in the contract so a relayer can mint its mirror on
+    /// `destination_chain` for `destination_address`, as announced by the `nft_bridge_lock`
+    /// event. The deposit covers the storage of the lock.
+    #[payable]
+    pub fn bridge_lock(
+        &mut self,
+        token_id: TokenId,
+        destination_chain: String,
+        destination_address: String,
+    ) {
+        self.assert_not_paused();
+        let token = self.tokens_by_id.get(&token_id).expect("Token not found");
+
+        if env::predecessor_account_id() != token.owner_id {
+            env::panic(b"Unauthorized");
+        }
+
+        for (name, value) in [
+            ("destination_chain", &destination_chain),
+            ("destination_address", &destination_address),
+        ]
+        .iter()
+        {
+            assert!(
+                !value.is_empty() && value.len() <= MAX_BRIDGE_FIELD_LEN,
+                "{} must have between 1 and {} bytes",
+                name,
+                MAX_BRIDGE_FIELD_LEN
+            );
+        }
+        token.assert_transferable();
+        self.assert_not_blacklisted(&token.owner_id);
+        assert!(!self.internal_is_locked(&token_id), "Token is locked");
+
+        // The contract only holds the token in escrow, so the transfer checks, ex. the
+        // compliance allowlist, don't apply to it.
+        let initial_storage_usage = env::storage_usage();
+        let owner_id = token.owner_id.clone();
+        let contract_id = env::current_account_id();
+        self.internal_move_token(
+            token,
+            &contract_id,
+            Some(env::predecessor_account_id()),
+            None,
+        );
+        self.internal_record_activity(&token_id, ActivityKind::Transfer);
+        let lock = BridgeLock {
+            token_id: token_id.clone(),
+            owner_id: owner_id.clone(),
+            destination_chain,
+            destination_address,
+            locked_at: env::block_timestamp().into(),
+        };
+        self.bridge_locks.insert(&token_id, &lock);
+        self.internal_emit(NftEvent::NftBridgeLock(vec![lock]));
+        self.internal_charge_storage(initial_storage_usage);
+    }
+
+    /// Releases a locked token to `receiver_id` once its mirror is burned on the other chain.
+    /// Only the admin or the bridge account can unlock.
+    pub fn bridge_unlock(&mut self, token_id: TokenId, receiver_id: ValidAccountId) {
+        self.assert_not_paused();
+        if !self.is_admin() && self.bridge_id.as_ref() != Some(&env::predecessor_account_id()) {
+            env::panic(b"Unauthorized");
+        }
+
+        let lock = self
+            .bridge_locks
+            .remove(&token_id)
+            .expect("Token is not locked");
+        let token = self.tokens_by_id.get(&token_id).unwrap();
+        let receiver_id: AccountId = receiver_id.into();
+        self.assert_not_blacklisted(&receiver_id);
+        self.internal_move_token(
+            token,
+            &receiver_id,
+            Some(env::predecessor_account_id()),
+            None,
+        );
+        self.internal_record_activity(&token_id, ActivityKind::Transfer);
+        self.internal_emit(NftEvent::NftBridgeUnlock(vec![BridgeUnlockLog {
+            token_id,
+            receiver_id,
+            destination_chain: lock.destination_chain,
+        }]));
+    }
+
+    pub fn nft_bridge_lock(&self, token_id: TokenId) -> Option<BridgeLock> {
+        self.bridge_locks.get(&token_id)
+    }
+
+    /// Locked tokens only leave the contract through `bridge_unlock`.
+    pub(crate) fn assert_not_bridged(&self, token_id: &TokenId) {
+        assert!(
+            self.bridge_locks.get(token_id).is_none(),
+            "Token is locked in the bridge"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::test_utils::get_logs;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn bridge_lock_and_unlock() {
+        let (mut contract, context) = helper_mint();
+        contract.bridge_lock(
+            "0".to_string(),
+            "aurora".to_string(),
+            "0x5a0b54d5dc17e0aadc383d2db43b0a0d3e029c4c".to_string(),
+        );
+        assert_eq!(
+            contract.nft_token("0".to_string()).unwrap().owner_id,
+            owner()
+        );
+        assert_eq!(
+            contract.nft_bridge_lock("0".to_string()).unwrap().owner_id,
+            nft()
+        );
+        assert!(get_logs()
+            .iter()
+            .any(|log| log.contains(r#""event":"nft_bridge_lock""#)));
+
+        testing_env!(get_context(owner(), 0));
+        contract.set_bridge_account(Some(ValidAccountId::try_from(bob()).unwrap()));
+        testing_env!(get_context(bob(), 0));
+        contract.bridge_unlock("0".to_string(), ValidAccountId::try_from(nft()).unwrap());
+        assert_eq!(contract.nft_token("0".to_string()).unwrap().owner_id, nft());
+        assert!(contract.nft_bridge_lock("0".to_string()).is_none());
+
+        testing_env!(context);
+        contract.nft_transfer(
+            ValidAccountId::try_from(bob()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Unauthorized")]
+    fn only_bridge_can_unlock() {
+        let (mut contract, _) = helper_mint();
+        contract.bridge_lock("0".to_string(), "aurora".to_string(), "0x00".to_string());
+
+        testing_env!(get_context(bob(), 0));
+        contract.bridge_unlock("0".to_string(), ValidAccountId::try_from(bob()).unwrap());
+    }
+
+    #[test]
+    fn bridge_lock_in_compliance_mode() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = NftContract::new(true);
+        contract.compliance_allow(ValidAccountId::try_from(nft()).unwrap());
+        testing_env!(get_context(nft(), 10u128.pow(24)));
+        contract.nft_mint(
+            "0".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.bridge_lock("0".to_string(), "aurora".to_string(), "0x00".to_string());
+        testing_env!(get_context(owner(), 0));
+        contract.bridge_unlock("0".to_string(), ValidAccountId::try_from(nft()).unwrap());
+        assert_eq!(contract.nft_token("0".to_string()).unwrap().owner_id, nft());
+    }
+}
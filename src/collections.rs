@@ -0,0 +1,570 @@
+use crate::*;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::Promise;
+
+/// Longest collection id `create_collection` accepts.
+pub const MAX_COLLECTION_ID_LEN: usize = 64;
+
+/// Number of accounts a collection owner can let mint.
+pub const MAX_COLLECTION_MINTERS: usize = 50;
+
+pub type CollectionId = String;
+
+/// Metadata describing a collection, as `NFTMetadata` does for the whole contract.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CollectionMetadata {
+    pub name: String,   // ex. "Mosaics"
+    pub symbol: String, // ex. "MOSAIC"
+    pub description: Option<String>,
+    pub icon: Option<String>,      // Data URL
+    pub base_uri: Option<String>,  // overrides the contract `base_uri` for relative paths
+    pub reference: Option<String>, // URL to a JSON file with more info
+}
+
+/// Drop hosted by the contract. Its tokens are minted as `<collection_id>/<token_id>`.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Collection {
+    pub collection_id: CollectionId,
+    pub owner_id: AccountId, // manages the collection and can always mint
+    pub metadata: CollectionMetadata,
+    pub minters: Vec<AccountId>, // accounts minting along with the owner
+    pub max_supply: Option<U64>, // `None` for an uncapped collection
+    pub minted: U64,             // tokens ever minted, burned ones included
+    pub royalty: u32, // share of every sale of its tokens in basis points, paid to the owner
+    pub pending_transfer: Option<PendingCollectionTransfer>,
+}
+
+/// Ownership transfer proposed by the collection owner, waiting for the new owner to accept.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PendingCollectionTransfer {
+    pub new_owner_id: AccountId,
+    pub price: U128, // paid to the current owner on acceptance
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// Creates `collection_id`, owned by the caller, who receives `royalty` basis points of
+    /// every sale of its tokens. The deposit covers its storage.
+    #[payable]
+    pub fn create_collection(
+        &mut self,
+        collection_id: CollectionId,
+        metadata: CollectionMetadata,
+        minters: Option<Vec<ValidAccountId>>,
+        max_supply: Option<U64>,
+        royalty: Option<u32>,
+    ) {
+        self.assert_not_paused();
+        assert!(
+            !collection_id.is_empty()
+                && collection_id.len() <= MAX_COLLECTION_ID_LEN
+                && collection_id.bytes().all(|c| c.is_ascii_lowercase()
+                    || c.is_ascii_digit()
+                    || c == b'-'
+                    || c == b'_'),
+            "Collection id must have between 1 and {} lowercase letters, digits, - or _",
+            MAX_COLLECTION_ID_LEN
+        );
+        assert!(
+            self.collections.get(&collection_id).is_none(),
+            "Collection already exists"
+        );
+
+        let initial_storage_usage = env::storage_usage();
+        let collection = Collection {
+            collection_id: collection_id.clone(),
+            owner_id: env::predecessor_account_id(),
+            metadata,
+            minters: vec![],
+            max_supply: None,
+            minted: U64(0),
+            royalty: 0,
+            pending_transfer: None,
+        };
+        self.internal_save_collection(collection, minters, max_supply, royalty);
+        log!("Create collection {}", collection_id);
+        self.internal_charge_storage(initial_storage_usage);
+    }
+
+    /// Replaces the metadata, minters, supply cap and royalty of `collection_id`. The supply
+    /// cap cannot go below the tokens already minted. Only its owner can update it.
+    #[payable]
+    pub fn update_collection(
+        &mut self,
+        collection_id: CollectionId,
+        metadata: CollectionMetadata,
+        minters: Option<Vec<ValidAccountId>>,
+        max_supply: Option<U64>,
+        royalty: Option<u32>,
+    ) {
+        let mut collection = self.internal_owned_collection(&collection_id);
+        let initial_storage_usage = env::storage_usage();
+        let base_uri_changed = collection.metadata.base_uri != metadata.base_uri;
+        collection.metadata = metadata;
+        self.internal_save_collection(collection, minters, max_supply, royalty);
+        if base_uri_changed {
+            // Shown by every token of the collection, too many to list.
+            self.internal_emit_contract_metadata_update(Some(format!(
+                "base uri of collection {}",
+                collection_id
+            )));
+        }
+        self.internal_charge_storage(initial_storage_usage);
+    }
+
+    /// Deletes `collection_id` once none of its tokens exist. Only its owner can delete it.
+    pub fn delete_collection(&mut self, collection_id: CollectionId) {
+        self.internal_owned_collection(&collection_id);
+        assert!(
+            self.tokens_by_collection.get(&collection_id).is_none(),
+            "Collection still has tokens"
+        );
+        self.collections.remove(&collection_id);
+        log!("Delete collection {}", collection_id);
+    }
+
+    /// Offers the ownership of `collection_id`, with its mint rights, metadata control and
+    /// royalty, to `new_owner_id` for `price`, free by default. Replaces any previous offer.
+    /// The deposit covers the storage of the offer.
+    #[payable]
+    pub fn propose_collection_transfer(
+        &mut self,
+        collection_id: CollectionId,
+        new_owner_id: ValidAccountId,
+        price: Option<U128>,
+    ) {
+        let mut collection = self.internal_owned_collection(&collection_id);
+        let initial_storage_usage = env::storage_usage();
+        let transfer = PendingCollectionTransfer {
+            new_owner_id: new_owner_id.into(),
+            price: price.unwrap_or(U128(0)),
+        };
+        collection.pending_transfer = Some(transfer.clone());
+        self.collections.insert(&collection_id, &collection);
+        self.internal_emit_extension(NftEvent::CollectionTransferPropose(vec![
+            CollectionTransferProposeLog {
+                collection_id,
+                owner_id: collection.owner_id,
+                new_owner_id: transfer.new_owner_id,
+                price: transfer.price,
+            },
+        ]));
+        self.internal_charge_storage(initial_storage_usage);
+    }
+
+    pub fn cancel_collection_transfer(&mut self, collection_id: CollectionId) {
+        let mut collection = self.internal_owned_collection(&collection_id);
+        assert!(
+            collection.pending_transfer.take().is_some(),
+            "No collection transfer proposed"
+        );
+        self.collections.insert(&collection_id, &collection);
+    }
+
+    /// Takes over `collection_id` as proposed by its owner, who receives the attached price.
+    /// From then on the collection royalty of every sale goes to the new owner. The rest of
+    /// the deposit is refunded.
+    #[payable]
+    pub fn accept_collection_transfer(&mut self, collection_id: CollectionId) {
+        self.assert_not_paused();
+        let mut collection = self
+            .collections
+            .get(&collection_id)
+            .expect("Collection not found");
+        let transfer = collection
+            .pending_transfer
+            .take()
+            .expect("No collection transfer proposed");
+        let new_owner_id = env::predecessor_account_id();
+        if new_owner_id != transfer.new_owner_id {
+            env::panic(b"Unauthorized");
+        }
+
+        let attached = env::attached_deposit();
+        assert!(
+            attached >= transfer.price.0,
+            "Must attach {} yoctoNEAR to buy the collection",
+            transfer.price.0
+        );
+        if transfer.price.0 > 0 {
+            Promise::new(collection.owner_id.clone()).transfer(transfer.price.0);
+        }
+        if attached > transfer.price.0 {
+            Promise::new(new_owner_id.clone()).transfer(attached - transfer.price.0);
+        }
+
+        let old_owner_id = std::mem::replace(&mut collection.owner_id, new_owner_id.clone());
+        self.collections.insert(&collection_id, &collection);
+        self.internal_emit_extension(NftEvent::CollectionTransfer(vec![CollectionTransferLog {
+            collection_id,
+            old_owner_id,
+            new_owner_id,
+            price: transfer.price,
+        }]));
+    }
+
+    /// Mints `<collection_id>/<token_id>` to `receiver_id`, the caller by default. Only the
+    /// collection owner and minters can mint, paying the mint price and storage as in
+    /// `nft_mint`. Sales
+    /// of the token pay the collection royalty to whoever owns the collection.
+    #[payable]
+    pub fn nft_mint_collection(
+        &mut self,
+        collection_id: CollectionId,
+        token_id: TokenId,
+        metadata: TokenMetadata,
+        receiver_id: Option<ValidAccountId>,
+    ) -> MintReceipt {
+        self.assert_not_paused();
+        self.assert_mint_not_ft_gated();
+        self.assert_valid_token_id(&token_id);
+        let mut collection = self
+            .collections
+            .get(&collection_id)
+            .expect("Collection not found");
+        let minter_id = env::predecessor_account_id();
+        if minter_id != collection.owner_id && !collection.minters.contains(&minter_id) {
+            env::panic(b"Unauthorized");
+        }
+        let minted = math::add_u64(collection.minted.0, 1, "Collection supply");
+        if let Some(max_supply) = collection.max_supply {
+            assert!(minted <= max_supply.0, "Collection is sold out");
+        }
+        let initial_storage_usage = env::storage_usage();
+        collection.minted = minted.into();
+        self.collections.insert(&collection_id, &collection);
+
+        let price = self.internal_take_mint_price(&minter_id);
+        let owner_id = receiver_id.map_or_else(|| minter_id.clone(), Into::into);
+        if owner_id != minter_id {
+            self.assert_can_receive(&minter_id, &owner_id);
+        }
+        let token = Token {
+            token_id: format!("{}/{}", collection_id, token_id),
+            owner_id,
+            frozen: false,
+            revealed: self.placeholder_metadata.is_none(),
+            creator_id: minter_id,
+            license: None,
+            reissued_from: None,
+            royalty: Royalty::new(),
+            approved_account_ids: HashMap::new(),
+            approvals_expire_at: HashMap::new(),
+            next_approval_id: 0,
+            transferable: true,
+            mint_sequence: self.minted_count,
+            series_id: None,
+            collection_id: Some(collection_id),
+        };
+        let receipt = self.internal_mint_token(token, metadata, price);
+        self.internal_charge_mint_deposit(initial_storage_usage, price);
+        receipt
+    }
+
+    pub fn nft_collection(&self, collection_id: CollectionId) -> Option<Collection> {
+        self.collections.get(&collection_id)
+    }
+
+    /// Page of the collections in creation order.
+    pub fn nft_collections(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<Collection> {
+        let from_index = from_index.map_or(0, |index| index.0 as usize);
+        let limit = limit.map_or(usize::MAX, |limit| limit as usize);
+        self.collections
+            .values()
+            .skip(from_index)
+            .take(limit)
+            .collect()
+    }
+
+    /// Page of the existing tokens of `collection_id`.
+    pub fn nft_tokens_for_collection(
+        &self,
+        collection_id: CollectionId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<JsonToken> {
+        let token_ids = match self.tokens_by_collection.get(&collection_id) {
+            Some(token_ids) => token_ids,
+            None => return vec![],
+        };
+        let from_index = from_index.map_or(0, |index| index.0 as usize);
+        let limit = limit.map_or(usize::MAX, |limit| limit as usize);
+        token_ids
+            .iter()
+            .skip(from_index)
+            .take(limit)
+            .map(|token_id| self.nft_token(token_id).unwrap())
+            .collect()
+    }
+
+    pub fn nft_collection_supply(&self, collection_id: CollectionId) -> U128 {
+        U128(
+            self.tokens_by_collection
+                .get(&collection_id)
+                .map_or(0, |token_ids| token_ids.len() as u128),
+        )
+    }
+}
+
+impl NftContract {
+    /// `collection_id`, panicking unless the caller owns it.
+    pub(crate) fn internal_owned_collection(&self, collection_id: &CollectionId) -> Collection {
+        self.assert_not_sunset();
+        let collection = self
+            .collections
+            .get(collection_id)
+            .expect("Collection not found");
+        if env::predecessor_account_id() != collection.owner_id {
+            env::panic(b"Unauthorized");
+        }
+        collection
+    }
+
+    fn internal_save_collection(
+        &mut self,
+        mut collection: Collection,
+        minters: Option<Vec<ValidAccountId>>,
+        max_supply: Option<U64>,
+        royalty: Option<u32>,
+    ) {
+        let minters = minters.unwrap_or_default();
+        assert!(
+            minters.len() <= MAX_COLLECTION_MINTERS,
+            "Cannot allow more than {} minters",
+            MAX_COLLECTION_MINTERS
+        );
+        if let Some(max_supply) = max_supply {
+            assert!(
+                max_supply.0 > 0 && max_supply.0 >= collection.minted.0,
+                "Max supply must be positive and cover the minted tokens"
+            );
+        }
+        let royalty = royalty.unwrap_or(0);
+        assert!(
+            royalty <= self.max_royalty,
+            "Royalty of {} exceeds the maximum of {}",
+            royalty,
+            self.max_royalty
+        );
+        collection.minters = minters.into_iter().map(Into::into).collect();
+        collection.max_supply = max_supply;
+        collection.royalty = royalty;
+        self.collections
+            .insert(&collection.collection_id.clone(), &collection);
+    }
+
+    /// Royalty shares of `token`, with the royalty of its collection going to whoever owns
+    /// the collection at the time of the sale.
+    pub(crate) fn internal_token_royalty(&self, token: &Token) -> Royalty {
+        let mut royalty = token.royalty.clone();
+        let collection = token
+            .collection_id
+            .as_ref()
+            .and_then(|collection_id| self.collections.get(collection_id));
+        if let Some(collection) = collection {
+            if collection.royalty > 0 {
+                *royalty.entry(collection.owner_id).or_insert(0) += collection.royalty;
+            }
+        }
+        royalty
+    }
+
+    pub(crate) fn internal_add_token_to_collection(&mut self, token: &Token) {
+        let collection_id = match &token.collection_id {
+            Some(collection_id) => collection_id,
+            None => return,
+        };
+        let mut token_ids = self
+            .tokens_by_collection
+            .get(collection_id)
+            .unwrap_or_else(|| {
+                let mut collection_hash = CryptoHash::default();
+                collection_hash.copy_from_slice(&env::sha256(collection_id.as_bytes()));
+                UnorderedSet::new(
+                    StorageKey::TokensByCollectionInner { collection_hash }
+                        .try_to_vec()
+                        .unwrap(),
+                )
+            });
+        token_ids.insert(&token.token_id);
+        self.tokens_by_collection.insert(collection_id, &token_ids);
+    }
+
+    pub(crate) fn internal_remove_token_from_collection(&mut self, token: &Token) {
+        let collection_id = match &token.collection_id {
+            Some(collection_id) => collection_id,
+            None => return,
+        };
+        if let Some(mut token_ids) = self.tokens_by_collection.get(collection_id) {
+            token_ids.remove(&token.token_id);
+            if token_ids.is_empty() {
+                self.tokens_by_collection.remove(collection_id);
+            } else {
+                self.tokens_by_collection.insert(collection_id, &token_ids);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U128;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    fn helper_collection_metadata() -> CollectionMetadata {
+        CollectionMetadata {
+            name: "Mochi Drop".to_string(),
+            symbol: "MOCHI".to_string(),
+            description: None,
+            icon: None,
+            base_uri: None,
+            reference: None,
+        }
+    }
+
+    #[test]
+    fn mint_in_collection() {
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        let mut contract = NftContract::default();
+        contract.create_collection(
+            "mochi".to_string(),
+            helper_collection_metadata(),
+            Some(vec![ValidAccountId::try_from(nft()).unwrap()]),
+            Some(U64(1)),
+            None,
+        );
+
+        testing_env!(get_context(nft(), 10u128.pow(24)));
+        let receipt = contract.nft_mint_collection(
+            "mochi".to_string(),
+            "0".to_string(),
+            helper_token_metadata(),
+            Some(ValidAccountId::try_from(owner()).unwrap()),
+        );
+        assert_eq!(receipt.token_id, "mochi/0");
+        let token = contract.nft_token("mochi/0".to_string()).unwrap();
+        assert_eq!(token.owner_id, owner());
+        assert_eq!(token.collection_id, Some("mochi".to_string()));
+        assert_eq!(contract.nft_collection_supply("mochi".to_string()), U128(1));
+        assert_eq!(contract.nft_collections(None, None)[0].owner_id, bob());
+        assert_eq!(
+            contract
+                .nft_tokens_for_collection("mochi".to_string(), None, None)
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Unauthorized")]
+    fn only_owner_updates_collection() {
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        let mut contract = NftContract::default();
+        contract.create_collection(
+            "mochi".to_string(),
+            helper_collection_metadata(),
+            None,
+            None,
+            None,
+        );
+
+        testing_env!(get_context(nft(), 10u128.pow(24)));
+        contract.update_collection(
+            "mochi".to_string(),
+            helper_collection_metadata(),
+            None,
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    fn sell_collection_ownership() {
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        let mut contract = NftContract::default();
+        contract.create_collection(
+            "mochi".to_string(),
+            helper_collection_metadata(),
+            None,
+            None,
+            Some(500),
+        );
+        contract.nft_mint_collection(
+            "mochi".to_string(),
+            "0".to_string(),
+            helper_token_metadata(),
+            Some(ValidAccountId::try_from(owner()).unwrap()),
+        );
+        let royalty = contract.nft_token("mochi/0".to_string()).unwrap().royalty;
+        assert_eq!(royalty.get(&bob()), Some(&500));
+        contract.propose_collection_transfer(
+            "mochi".to_string(),
+            ValidAccountId::try_from(nft()).unwrap(),
+            Some(U128(100)),
+        );
+        assert_eq!(
+            contract
+                .nft_collection("mochi".to_string())
+                .unwrap()
+                .owner_id,
+            bob()
+        );
+
+        testing_env!(get_context(nft(), 100));
+        contract.accept_collection_transfer("mochi".to_string());
+        let collection = contract.nft_collection("mochi".to_string()).unwrap();
+        assert_eq!(collection.owner_id, nft());
+        assert_eq!(collection.pending_transfer, None);
+        let royalty = contract.nft_token("mochi/0".to_string()).unwrap().royalty;
+        assert_eq!(royalty.get(&bob()), None);
+        assert_eq!(royalty.get(&nft()), Some(&500));
+        testing_env!(get_context(nft(), 10u128.pow(24)));
+        contract.update_collection(
+            "mochi".to_string(),
+            helper_collection_metadata(),
+            None,
+            Some(U64(10)),
+            None,
+        );
+    }
+
+    #[test]
+    fn collection_and_series_base_uri() {
+        testing_env!(get_context(owner(), 10u128.pow(24)));
+        let mut contract = NftContract::default();
+        let mut contract_metadata = contract.nft_metadata();
+        contract_metadata.base_uri = Some("https://ipfs.io/ipfs".to_string());
+        contract.set_contract_metadata(contract_metadata);
+        let mut metadata = helper_token_metadata();
+        metadata.media = Some("bafy/1.png".to_string());
+        metadata.media_hash = Some(Base64VecU8(vec![0; 32]));
+
+        let mut collection_metadata = helper_collection_metadata();
+        collection_metadata.base_uri = Some("https://nftstorage.link/ipfs".to_string());
+        contract.create_collection("mochi".to_string(), collection_metadata, None, None, None);
+        contract.nft_mint_collection("mochi".to_string(), "1".to_string(), metadata.clone(), None);
+        let series_id = contract.create_series(metadata, Some(U128(0)), None, None, None);
+        contract.nft_mint_series(series_id.clone(), None);
+
+        assert_eq!(
+            contract.nft_media_urls("mochi/1".to_string()),
+            vec!["https://nftstorage.link/ipfs/bafy/1.png"]
+        );
+        assert_eq!(
+            contract.nft_base_uri("1:1".to_string()),
+            Some("https://ipfs.io/ipfs".to_string())
+        );
+        contract.set_series_base_uri(series_id, Some("https://w3s.link/ipfs".to_string()));
+        assert_eq!(
+            contract.nft_media_urls("1:1".to_string()),
+            vec!["https://w3s.link/ipfs/bafy/1.png"]
+        );
+    }
+}
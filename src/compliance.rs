@@ -0,0 +1,118 @@
+use crate::*;
+use near_sdk::json_types::U128;
+
+#[near_bindgen]
+impl NftContract {
+    /// Adds `account_id` to the accounts allowed to send and receive tokens in compliance mode.
+    pub fn compliance_allow(&mut self, account_id: ValidAccountId) {
+        self.assert_admin();
+        self.compliance_allowlist.insert(account_id.as_ref());
+    }
+
+    pub fn compliance_disallow(&mut self, account_id: ValidAccountId) {
+        self.assert_admin();
+        self.compliance_allowlist.remove(account_id.as_ref());
+    }
+
+    pub fn is_compliance_mode(&self) -> bool {
+        self.compliance_mode
+    }
+
+    pub fn is_compliance_allowed(&self, account_id: ValidAccountId) -> bool {
+        self.compliance_allowlist.contains(account_id.as_ref())
+    }
+
+    pub fn compliance_allowlist(
+        &self,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<AccountId> {
+        let from_index = from_index.map_or(0, |index| index.0 as usize);
+        let limit = limit.map_or(usize::MAX, |limit| limit as usize);
+        self.compliance_allowlist
+            .iter()
+            .skip(from_index)
+            .take(limit)
+            .collect()
+    }
+
+    /// Whether `assert_compliant_transfer` lets `sender_id` send a token to `receiver_id`.
+    #[cfg(feature = "marketplace")]
+    pub(crate) fn is_compliant_transfer(
+        &self,
+        sender_id: &AccountId,
+        receiver_id: &AccountId,
+    ) -> bool {
+        !self.compliance_mode
+            || (self.compliance_allowlist.contains(sender_id)
+                && self.compliance_allowlist.contains(receiver_id))
+    }
+
+    /// In compliance mode, panics unless both accounts are in the allowlist.
+    pub(crate) fn assert_compliant_transfer(&self, sender_id: &AccountId, receiver_id: &AccountId) {
+        if !self.compliance_mode {
+            return;
+        }
+        for account_id in [sender_id, receiver_id].iter() {
+            if !self.compliance_allowlist.contains(account_id) {
+                env::panic(
+                    format!("Account {} is not in the compliance allowlist", account_id).as_bytes(),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn compliance_mode_transfer() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = NftContract::new(true);
+        contract.compliance_allow(ValidAccountId::try_from(nft()).unwrap());
+        contract.compliance_allow(ValidAccountId::try_from(bob()).unwrap());
+        testing_env!(get_context(nft(), 10u128.pow(24)));
+        contract.nft_mint(
+            "0".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.nft_transfer(
+            ValidAccountId::try_from(bob()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+        assert_eq!(contract.nft_token("0".to_string()).unwrap().owner_id, bob());
+    }
+
+    #[test]
+    #[should_panic(expected = "Account bob.near is not in the compliance allowlist")]
+    fn compliance_mode_transfer_to_unlisted() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = NftContract::new(true);
+        contract.compliance_allow(ValidAccountId::try_from(nft()).unwrap());
+        testing_env!(get_context(nft(), 10u128.pow(24)));
+        contract.nft_mint(
+            "0".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.nft_transfer(
+            ValidAccountId::try_from(bob()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+    }
+}
@@ -0,0 +1,68 @@
+use crate::*;
+use near_sdk::json_types::U128;
+
+#[near_bindgen]
+impl NftContract {
+    pub fn verify_creator(&mut self, account_id: ValidAccountId) {
+        self.assert_admin();
+        self.verified_creators.insert(account_id.as_ref());
+    }
+
+    pub fn unverify_creator(&mut self, account_id: ValidAccountId) {
+        self.assert_admin();
+        self.verified_creators.remove(account_id.as_ref());
+    }
+
+    pub fn is_creator_verified(&self, account_id: ValidAccountId) -> bool {
+        self.verified_creators.contains(account_id.as_ref())
+    }
+
+    pub fn verified_creators(
+        &self,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<AccountId> {
+        let from_index = from_index.map_or(0, |index| index.0 as usize);
+        let limit = limit.map_or(usize::MAX, |limit| limit as usize);
+        self.verified_creators
+            .iter()
+            .skip(from_index)
+            .take(limit)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn verified_creator_badge() {
+        let (mut contract, _) = helper_mint();
+        assert!(
+            !contract
+                .nft_token("0".to_string())
+                .unwrap()
+                .creator_verified
+        );
+
+        testing_env!(get_context(owner(), 0));
+        contract.verify_creator(ValidAccountId::try_from(nft()).unwrap());
+        let token = contract.nft_token("0".to_string()).unwrap();
+        assert_eq!(token.creator_id, nft());
+        assert!(token.creator_verified);
+        assert_eq!(contract.verified_creators(None, None), vec![nft()]);
+
+        contract.unverify_creator(ValidAccountId::try_from(nft()).unwrap());
+        assert!(
+            !contract
+                .nft_token("0".to_string())
+                .unwrap()
+                .creator_verified
+        );
+    }
+}
@@ -0,0 +1,125 @@
+use crate::*;
+
+/// Time a receiver has to decline a token sent to them, in nanoseconds (7 days).
+pub const DECLINE_GRACE_PERIOD: u64 = 7 * 24 * 60 * 60 * 1_000_000_000;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct IncomingTransfer {
+    pub sender_id: AccountId,
+    pub received_at: U64, // block timestamp in nanoseconds
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// Sends back a token received through `nft_transfer` to its sender. The deposit covers
+    /// the storage the sender takes back, while the caller's token set, when emptied, is
+    /// refunded to the sender who paid for it.
+    #[payable]
+    pub fn decline_token(&mut self, token_id: TokenId) {
+        self.assert_not_paused();
+        let token = self.tokens_by_id.get(&token_id).expect("Token not found");
+
+        if env::predecessor_account_id() != token.owner_id {
+            env::panic(b"Unauthorized");
+        }
+
+        let incoming = self
+            .incoming_transfers
+            .get(&token_id)
+            .expect("Token was not received by a transfer");
+        assert!(
+            env::block_timestamp()
+                <= math::add_u64(incoming.received_at.0, DECLINE_GRACE_PERIOD, "Deadline"),
+            "Grace period to decline the token is over"
+        );
+
+        let initial_storage_usage = env::storage_usage();
+        self.internal_transfer(
+            token,
+            &incoming.sender_id,
+            Some(env::predecessor_account_id()),
+            None,
+        );
+        self.internal_record_activity(&token_id, ActivityKind::Decline);
+        self.internal_charge_storage(initial_storage_usage);
+    }
+
+    pub fn nft_incoming_transfer(&self, token_id: TokenId) -> Option<IncomingTransfer> {
+        self.incoming_transfers.get(&token_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn decline_unsolicited_token() {
+        let (mut contract, _) = helper_mint();
+        contract.nft_transfer(
+            ValidAccountId::try_from(bob()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+        testing_env!(get_context(bob(), 10u128.pow(22)));
+        contract.decline_token("0".to_string());
+        assert_eq!(contract.nft_token("0".to_string()).unwrap().owner_id, nft());
+        assert!(contract.nft_incoming_transfer("0".to_string()).is_none());
+    }
+
+    #[test]
+    fn declined_token_set_refunded_to_sender() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(nft(), 10u128.pow(23)));
+        contract.storage_deposit(None);
+        testing_env!(get_context(nft(), 0));
+        contract.nft_transfer(
+            ValidAccountId::try_from(bob()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+        let paid = contract
+            .storage_balance_of(ValidAccountId::try_from(nft()).unwrap())
+            .unwrap();
+
+        testing_env!(get_context(bob(), 10u128.pow(22)));
+        contract.decline_token("0".to_string());
+        let refunded = contract
+            .storage_balance_of(ValidAccountId::try_from(nft()).unwrap())
+            .unwrap();
+        assert!(refunded.available.0 > paid.available.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "to cover storage")]
+    fn decline_without_storage_deposit() {
+        let (mut contract, _) = helper_mint();
+        contract.nft_transfer(
+            ValidAccountId::try_from(bob()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+        testing_env!(get_context(bob(), 0));
+        contract.decline_token("0".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Grace period to decline the token is over")]
+    fn decline_after_grace_period() {
+        let (mut contract, _) = helper_mint();
+        contract.nft_transfer(
+            ValidAccountId::try_from(bob()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+        let mut context = get_context(bob(), 0);
+        context.block_timestamp = DECLINE_GRACE_PERIOD + 1;
+        testing_env!(context);
+        contract.decline_token("0".to_string());
+    }
+}
@@ -0,0 +1,172 @@
+use crate::*;
+use near_sdk::json_types::Base58PublicKey;
+use near_sdk::Promise;
+
+/// Allowance of the access key added by `add_drop`, paying for the gas of its `claim` call.
+pub const DROP_KEY_ALLOWANCE: Balance = 10_000_000_000_000_000_000_000; // 0.01 NEAR
+
+/// Token that anyone holding the private half of `public_key` can claim.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenDrop {
+    pub token_id: TokenId,
+    pub owner_id: AccountId,
+    pub public_key: Base58PublicKey,
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// Lets whoever holds the private half of `public_key` claim `token_id`, e.g. from a QR
+    /// code, with no NEAR account of their own. The key is added to this contract limited to
+    /// `claim`, and the deposit pays for its allowance and the storage of the drop. The
+    /// allowance pays for gas the key burns, so it is never refunded. The token cannot move
+    /// until it is claimed or the drop is cancelled.
+    #[payable]
+    pub fn add_drop(&mut self, token_id: TokenId, public_key: Base58PublicKey) {
+        self.assert_not_paused();
+        let token = self.tokens_by_id.get(&token_id).expect("Token not found");
+
+        if env::predecessor_account_id() != token.owner_id {
+            env::panic(b"Unauthorized");
+        }
+
+        token.assert_transferable();
+        self.assert_not_leased(&token_id);
+        self.assert_not_sent(&token_id);
+        self.assert_not_staked(&token_id);
+        self.assert_not_swapped(&token_id);
+        self.assert_no_auction_bids(&token_id);
+        assert!(
+            self.drops.get(&token_id).is_none(),
+            "Token is already dropped"
+        );
+        assert!(
+            self.drop_keys.get(&public_key.0).is_none(),
+            "Key is already used by another drop"
+        );
+        assert!(
+            env::attached_deposit() >= DROP_KEY_ALLOWANCE,
+            "Must attach at least {} yoctoNEAR for the key allowance",
+            DROP_KEY_ALLOWANCE
+        );
+
+        let initial_storage_usage = env::storage_usage();
+        self.drop_keys.insert(&public_key.0, &token_id);
+        self.drops.insert(
+            &token_id,
+            &TokenDrop {
+                token_id: token_id.clone(),
+                owner_id: token.owner_id,
+                public_key: public_key.clone(),
+            },
+        );
+        let storage_used = env::storage_usage().saturating_sub(initial_storage_usage);
+        let required = DROP_KEY_ALLOWANCE + Balance::from(storage_used) * env::storage_byte_cost();
+        assert!(
+            env::attached_deposit() >= required,
+            "Must attach {} yoctoNEAR to cover the key allowance and storage",
+            required
+        );
+        let refund = env::attached_deposit() - required;
+        if refund > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+
+        Promise::new(env::current_account_id()).add_access_key(
+            public_key.into(),
+            DROP_KEY_ALLOWANCE,
+            env::current_account_id(),
+            b"claim".to_vec(),
+        );
+    }
+
+    /// Transfers a dropped token to `new_account_id`. Must be signed with the drop key,
+    /// which is deleted afterwards. What the claim left of the key allowance is kept, and the
+    /// storage of the drop goes back to its owner.
+    pub fn claim(&mut self, token_id: TokenId, new_account_id: ValidAccountId) {
+        self.assert_not_paused();
+        if env::predecessor_account_id() != env::current_account_id() {
+            env::panic(b"Unauthorized");
+        }
+        let drop = self.drops.get(&token_id).expect("Token is not dropped");
+        if env::signer_account_pk() != drop.public_key.0 {
+            env::panic(b"Unauthorized");
+        }
+
+        self.internal_remove_drop(&token_id);
+        let token = self.tokens_by_id.get(&token_id).unwrap();
+        let receiver_id: AccountId = new_account_id.into();
+        // Claimed with the drop key on the contract account, on behalf of no one else.
+        self.internal_transfer(token, &receiver_id, None, None);
+        self.internal_record_activity(&token_id, ActivityKind::Transfer);
+    }
+
+    /// Takes a dropped token back and deletes its key. The storage of the drop is refunded,
+    /// but the key allowance is not: what the key has spent on failed claims is unknown here.
+    pub fn cancel_drop(&mut self, token_id: TokenId) {
+        let drop = self.drops.get(&token_id).expect("Token is not dropped");
+
+        if env::predecessor_account_id() != drop.owner_id {
+            env::panic(b"Unauthorized");
+        }
+
+        self.internal_remove_drop(&token_id);
+    }
+
+    pub fn nft_drop(&self, token_id: TokenId) -> Option<TokenDrop> {
+        self.drops.get(&token_id)
+    }
+
+    pub(crate) fn assert_not_dropped(&self, token_id: &TokenId) {
+        assert!(
+            self.drops.get(token_id).is_none(),
+            "Token is waiting to be claimed"
+        );
+    }
+
+    /// Removes the drop of `token_id`, if any, along with its access key, and refunds its
+    /// storage to the owner.
+    pub(crate) fn internal_remove_drop(&mut self, token_id: &TokenId) -> Option<TokenDrop> {
+        let initial_storage_usage = env::storage_usage();
+        let drop = self.drops.remove(token_id)?;
+        self.drop_keys.remove(&drop.public_key.0);
+        Promise::new(env::current_account_id()).delete_key(drop.public_key.0.clone());
+        let freed = initial_storage_usage.saturating_sub(env::storage_usage());
+        self.internal_refund_storage(&drop.owner_id, freed);
+        Some(drop)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::Base58PublicKey;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn claim_drop_with_key() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(nft(), 10u128.pow(24)));
+        contract.add_drop("0".to_string(), Base58PublicKey(vec![0, 1, 2]));
+        assert_eq!(contract.nft_drop("0".to_string()).unwrap().owner_id, nft());
+
+        testing_env!(get_context(owner(), 0));
+        contract.claim("0".to_string(), ValidAccountId::try_from(bob()).unwrap());
+        assert_eq!(contract.nft_token("0".to_string()).unwrap().owner_id, bob());
+        assert!(contract.nft_drop("0".to_string()).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Unauthorized")]
+    fn claim_drop_with_other_key() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(nft(), 10u128.pow(24)));
+        contract.add_drop("0".to_string(), Base58PublicKey(vec![3, 4, 5]));
+
+        testing_env!(get_context(owner(), 0));
+        contract.claim("0".to_string(), ValidAccountId::try_from(bob()).unwrap());
+    }
+}
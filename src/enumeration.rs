@@ -0,0 +1,416 @@
+use crate::*;
+use near_sdk::json_types::{U128, U64};
+use std::ops::Bound;
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftStats {
+    pub minted: U64, // tokens ever minted, reissued replacements included
+    pub burned: U64, // tokens ever burned, reissued originals included
+    pub total_supply: U64,
+    pub holders: U64,
+}
+
+/// Tokens returned by one `nft_tokens_detailed` call at most, to stay under the view gas limit.
+pub const MAX_DETAILED_TOKENS: u64 = 50;
+
+/// Everything an indexer needs about a token, as returned by `nft_tokens_detailed`.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenDetails {
+    #[serde(flatten)]
+    pub token: JsonToken,
+    pub approvals_expire_at: HashMap<AccountId, U64>, // only the approvals that expire
+    pub listing: Option<Listing>,
+}
+
+/// Everything a token page shows, as returned by `nft_token_full`.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenFull {
+    #[serde(flatten)]
+    pub details: TokenDetails,
+    pub auction: Option<Auction>,
+    pub lease: Option<Lease>, // only while active
+    pub stake: Option<Stake>,
+    pub bridge_lock: Option<BridgeLock>,
+    pub dropped: bool, // waiting to be claimed with a drop key
+    pub sent: bool,    // gift waiting for its receiver
+    pub media: Option<ResolvedMedia>,
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// Number of live tokens, minted less burned.
+    pub fn nft_total_supply(&self) -> U128 {
+        U128((self.minted_count - self.burned_count) as u128)
+    }
+
+    pub fn nft_stats(&self) -> NftStats {
+        NftStats {
+            minted: self.minted_count.into(),
+            burned: self.burned_count.into(),
+            total_supply: (self.minted_count - self.burned_count).into(),
+            holders: self.supply_per_owner.len().into(),
+        }
+    }
+
+    pub fn nft_tokens(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<JsonToken> {
+        let from_index = from_index.map_or(0, |index| index.0 as usize);
+        let limit = limit.map_or(usize::MAX, |limit| limit as usize);
+        self.token_ids
+            .iter()
+            .skip(from_index)
+            .take(limit)
+            .map(|token_id| self.internal_json_token(self.tokens_by_id.get(&token_id).unwrap()))
+            .collect()
+    }
+
+    /// Page of tokens with their listing and approval expiry, for indexers backfilling the
+    /// collection. At most `MAX_DETAILED_TOKENS` are returned, also when `limit` is larger.
+    pub fn nft_tokens_detailed(
+        &self,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<TokenDetails> {
+        // Index the set directly, so skipped tokens are not read from storage.
+        let token_ids = self.token_ids.as_vector();
+        let from_index = from_index.map_or(0, |index| index.0 as u64);
+        let limit = limit.map_or(MAX_DETAILED_TOKENS, |limit| limit.min(MAX_DETAILED_TOKENS));
+        let end = token_ids.len().min(from_index.saturating_add(limit));
+        (from_index..end)
+            .map(|index| {
+                let token_id = token_ids.get(index).unwrap();
+                self.internal_token_details(self.tokens_by_id.get(&token_id).unwrap())
+            })
+            .collect()
+    }
+
+    /// The token with its approvals, royalty, sale, lease and lock status and resolved
+    /// media, so a token page needs a single view call.
+    pub fn nft_token_full(&self, token_id: TokenId) -> Option<TokenFull> {
+        let token = self.tokens_by_id.get(&token_id)?;
+        Some(TokenFull {
+            details: self.internal_token_details(token),
+            auction: self.auctions.get(&token_id),
+            lease: self.leases.get(&token_id).filter(Lease::is_active),
+            stake: self.stakes.get(&token_id),
+            bridge_lock: self.bridge_locks.get(&token_id),
+            dropped: self.drops.get(&token_id).is_some(),
+            sent: self.gifts.get(&token_id).is_some(),
+            media: self.nft_media(token_id),
+        })
+    }
+
+    /// Page of the tokens owned by `account_id` in mint order, so large holders can be read
+    /// in bounded calls. `nft_tokens_for_owner_from` pages without walking the skipped tokens.
+    pub fn nft_tokens_for_owner(
+        &self,
+        account_id: ValidAccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<JsonToken> {
+        let tokens = match self.tokens_per_owner.get(account_id.as_ref()) {
+            Some(tokens) => tokens,
+            None => return vec![],
+        };
+        let from_index = from_index.map_or(0, |index| index.0 as usize);
+        let limit = limit.map_or(usize::MAX, |limit| limit as usize);
+        tokens
+            .iter()
+            .skip(from_index)
+            .take(limit)
+            .map(|(_, token_id)| {
+                self.internal_json_token(self.tokens_by_id.get(&token_id).unwrap())
+            })
+            .collect()
+    }
+
+    /// Tokens owned by `account_id` minted at `from_sequence` or later, in mint order.
+    /// Pass the `mint_sequence` of the last token of a page plus one to read the next page.
+    pub fn nft_tokens_for_owner_from(
+        &self,
+        account_id: ValidAccountId,
+        from_sequence: U64,
+        limit: Option<u64>,
+    ) -> Vec<JsonToken> {
+        let tokens = match self.tokens_per_owner.get(account_id.as_ref()) {
+            Some(tokens) => tokens,
+            None => return vec![],
+        };
+        let limit = limit.map_or(usize::MAX, |limit| limit as usize);
+        tokens
+            .range((Bound::Included(from_sequence.0), Bound::Unbounded))
+            .take(limit)
+            .map(|(_, token_id)| {
+                self.internal_json_token(self.tokens_by_id.get(&token_id).unwrap())
+            })
+            .collect()
+    }
+
+    /// Number of tokens held by `account_id`, read from a counter instead of its token set.
+    pub fn nft_supply_for_owner(&self, account_id: ValidAccountId) -> U128 {
+        U128(self.supply_per_owner.get(account_id.as_ref()).unwrap_or(0) as u128)
+    }
+
+    /// Page of `(account_id, token count)` for every account holding tokens.
+    pub fn nft_holders(
+        &self,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<(AccountId, U128)> {
+        let holders = self.supply_per_owner.keys_as_vector();
+        let supplies = self.supply_per_owner.values_as_vector();
+        let from_index = from_index.map_or(0, |index| index.0 as u64);
+        let end = limit.map_or(holders.len(), |limit| {
+            holders.len().min(from_index.saturating_add(limit))
+        });
+        (from_index..end)
+            .map(|index| {
+                (
+                    holders.get(index).unwrap(),
+                    U128(supplies.get(index).unwrap() as u128),
+                )
+            })
+            .collect()
+    }
+
+    /// Adds tokens minted before the enumeration index existed to it.
+    /// `tokens_by_id` cannot be iterated, so their ids have to be provided, ex. from an indexer.
+    pub fn backfill_token_ids(&mut self, token_ids: Vec<TokenId>) {
+        self.assert_admin();
+        for token_id in token_ids.iter() {
+            assert!(
+                self.tokens_by_id.get(token_id).is_some(),
+                "Token {} not found",
+                token_id
+            );
+            self.token_ids.insert(token_id);
+        }
+    }
+
+    pub(crate) fn internal_increase_supply(&mut self, account_id: &AccountId) {
+        let supply = self.supply_per_owner.get(account_id).unwrap_or(0);
+        self.supply_per_owner.insert(account_id, &(supply + 1));
+    }
+
+    pub(crate) fn internal_decrease_supply(&mut self, account_id: &AccountId) {
+        let supply = self.supply_per_owner.get(account_id).unwrap_or(0);
+        if supply <= 1 {
+            self.supply_per_owner.remove(account_id);
+        } else {
+            self.supply_per_owner.insert(account_id, &(supply - 1));
+        }
+    }
+
+    fn internal_token_details(&self, token: Token) -> TokenDetails {
+        let approvals_expire_at = token
+            .approvals_expire_at
+            .iter()
+            .filter(|(account_id, _)| token.is_approved(account_id, None))
+            .map(|(account_id, expires_at)| (account_id.clone(), U64(*expires_at)))
+            .collect();
+        TokenDetails {
+            listing: self.listings.get(&token.token_id),
+            token: self.internal_json_token(token),
+            approvals_expire_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U128;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn enumerate_tokens() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(context);
+        contract.nft_mint(
+            "1".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+        assert_eq!(contract.nft_total_supply(), U128(2));
+        let tokens = contract.nft_tokens(Some(U128(1)), Some(10));
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].token_id, "1");
+    }
+
+    #[test]
+    fn tokens_for_owner_page() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(context);
+        contract.nft_mint(
+            "1".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.nft_mint(
+            "2".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+        let page = contract.nft_tokens_for_owner(
+            ValidAccountId::try_from(nft()).unwrap(),
+            Some(U128(1)),
+            Some(1),
+        );
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].token_id, "1");
+        assert!(contract
+            .nft_tokens_for_owner(ValidAccountId::try_from(bob()).unwrap(), None, None)
+            .is_empty());
+    }
+
+    #[test]
+    fn supply_for_owner_follows_transfers() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(context);
+        contract.nft_mint(
+            "1".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.nft_transfer(
+            ValidAccountId::try_from(bob()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+        assert_eq!(
+            contract.nft_supply_for_owner(ValidAccountId::try_from(nft()).unwrap()),
+            U128(1)
+        );
+        assert_eq!(
+            contract.nft_supply_for_owner(ValidAccountId::try_from(bob()).unwrap()),
+            U128(1)
+        );
+    }
+
+    #[test]
+    fn holders_with_counts() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(context);
+        contract.nft_mint(
+            "1".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.nft_transfer(
+            ValidAccountId::try_from(bob()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+        assert_eq!(
+            contract.nft_holders(None, None),
+            vec![(nft(), U128(1)), (bob(), U128(1))]
+        );
+        assert_eq!(
+            contract.nft_holders(Some(U128(1)), Some(1)),
+            vec![(bob(), U128(1))]
+        );
+    }
+
+    #[test]
+    fn stats_count_mints_and_burns() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(context);
+        contract.nft_mint(
+            "1".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.nft_burn("0".to_string());
+        let stats = contract.nft_stats();
+        assert_eq!(stats.minted, U64(2));
+        assert_eq!(stats.burned, U64(1));
+        assert_eq!(stats.holders, U64(1));
+        assert_eq!(contract.nft_total_supply(), U128(1));
+    }
+
+    #[cfg(feature = "marketplace")]
+    #[test]
+    fn tokens_detailed_page() {
+        let (mut contract, _) = helper_mint();
+        contract.nft_mint(
+            "1".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.nft_list("1".to_string(), U128(100), None);
+
+        let tokens = contract.nft_tokens_detailed(Some(U128(1)), Some(1000));
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].token.token_id, "1");
+        assert_eq!(tokens[0].listing.as_ref().unwrap().price, U128(100));
+        assert!(tokens[0].approvals_expire_at.is_empty());
+    }
+
+    #[test]
+    fn tokens_for_owner_in_mint_order() {
+        let (mut contract, _) = helper_mint();
+        for token_id in ["z", "a", "m"].iter() {
+            contract.nft_mint(
+                token_id.to_string(),
+                helper_token_metadata(),
+                None,
+                None,
+                None,
+                None,
+            );
+        }
+        let owner_id = ValidAccountId::try_from(nft()).unwrap();
+        let token_ids: Vec<TokenId> = contract
+            .nft_tokens_for_owner(owner_id.clone(), Some(U128(1)), None)
+            .into_iter()
+            .map(|token| token.token_id)
+            .collect();
+        assert_eq!(token_ids, vec!["z", "a", "m"]);
+
+        let tokens = contract.nft_tokens_for_owner_from(owner_id, U64(2), Some(1));
+        assert_eq!(tokens[0].token_id, "a");
+        assert_eq!(tokens[0].mint_sequence, U64(2));
+    }
+
+    #[test]
+    fn token_full_view() {
+        let (mut contract, _) = helper_mint();
+        contract.nft_approve(
+            "0".to_string(),
+            ValidAccountId::try_from(bob()).unwrap(),
+            None,
+            None,
+        );
+        let full = contract.nft_token_full("0".to_string()).unwrap();
+        assert_eq!(full.details.token.owner_id, nft());
+        assert!(full.details.token.approved_account_ids.contains_key(&bob()));
+        assert!(full.lease.is_none() && full.stake.is_none() && !full.dropped);
+        assert!(contract.nft_token_full("1".to_string()).is_none());
+    }
+}
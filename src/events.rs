@@ -0,0 +1,358 @@
+use crate::*;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde_json;
+use near_sdk::BlockHeight;
+
+/// Standard name and version reported in the NEP-297 logs of the NEP-171 events.
+pub const NFT_STANDARD_NAME: &str = "nep171";
+pub const NFT_STANDARD_VERSION: &str = "1.1.0";
+
+/// Standard name and version reported in the NEP-297 logs of the events of this contract,
+/// so that NEP-171 indexers skip them.
+pub const EXTENSION_STANDARD_NAME: &str = "nft-demo";
+pub const EXTENSION_STANDARD_VERSION: &str = "1.0.0";
+
+/// Number of recent events kept for `events_since`.
+pub const MAX_RECENT_EVENTS: u64 = 100;
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data")]
+#[serde(rename_all = "snake_case")]
+pub enum NftEvent {
+    NftMint(Vec<NftMintLog>),
+    NftBurn(Vec<NftBurnLog>),
+    NftTransfer(Vec<NftTransferLog>),
+    ContractMetadataUpdate(Vec<ContractMetadataUpdateLog>),
+    NftMetadataUpdate(Vec<NftMetadataUpdateLog>),
+    NftReveal(Vec<NftRevealLog>),
+    BlacklistAdd(Vec<BlacklistLog>),
+    BlacklistRemove(Vec<BlacklistLog>),
+    AuctionCreate(Vec<AuctionCreateLog>),
+    AuctionBid(Vec<AuctionBidLog>),
+    AuctionSettle(Vec<AuctionSettleLog>),
+    AuctionCancel(Vec<AuctionCancelLog>),
+    NftBridgeLock(Vec<BridgeLock>),
+    NftBridgeUnlock(Vec<BridgeUnlockLog>),
+    SeriesClose(Vec<SeriesCloseLog>),
+    CollectionTransferPropose(Vec<CollectionTransferProposeLog>),
+    CollectionTransfer(Vec<CollectionTransferLog>),
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftMintLog {
+    pub owner_id: AccountId,
+    pub token_ids: Vec<TokenId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftBurnLog {
+    pub owner_id: AccountId,
+    pub token_ids: Vec<TokenId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftTransferLog {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authorized_id: Option<AccountId>, // approved account that made the transfer
+    pub old_owner_id: AccountId,
+    pub new_owner_id: AccountId,
+    pub token_ids: Vec<TokenId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ContractMetadataUpdateLog {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftMetadataUpdateLog {
+    pub token_ids: Vec<TokenId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftRevealLog {
+    pub token_ids: Vec<TokenId>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BlacklistLog {
+    pub account_id: AccountId,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AuctionCreateLog {
+    pub token_id: TokenId,
+    pub owner_id: AccountId,
+    pub reserve_price: U128,
+    pub ends_at: U64,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AuctionBidLog {
+    pub token_id: TokenId,
+    pub bidder_id: AccountId,
+    pub amount: U128,
+    pub ends_at: U64, // after any anti-sniping extension
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AuctionSettleLog {
+    pub token_id: TokenId,
+    pub owner_id: AccountId,
+    pub winner_id: Option<AccountId>, // `None` when the auction ended without bids
+    pub amount: Option<U128>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AuctionCancelLog {
+    pub token_id: TokenId,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BridgeUnlockLog {
+    pub token_id: TokenId,
+    pub receiver_id: AccountId,
+    pub destination_chain: String, // chain the token was mirrored on
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SeriesCloseLog {
+    pub series_id: SeriesId,
+    pub final_supply: U64, // editions ever minted
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CollectionTransferProposeLog {
+    pub collection_id: CollectionId,
+    pub owner_id: AccountId,
+    pub new_owner_id: AccountId,
+    pub price: U128,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CollectionTransferLog {
+    pub collection_id: CollectionId,
+    pub old_owner_id: AccountId,
+    pub new_owner_id: AccountId,
+    pub price: U128,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct EventLog<'a> {
+    standard: &'static str,
+    version: &'static str,
+    #[serde(flatten)]
+    event: &'a NftEvent,
+}
+
+impl NftEvent {
+    /// Formats the event as NEP-297 JSON, without the `EVENT_JSON:` prefix.
+    fn to_json(&self) -> String {
+        let (standard, version) = if self.is_nep171() {
+            (NFT_STANDARD_NAME, NFT_STANDARD_VERSION)
+        } else {
+            (EXTENSION_STANDARD_NAME, EXTENSION_STANDARD_VERSION)
+        };
+        let log = EventLog {
+            standard,
+            version,
+            event: self,
+        };
+        serde_json::to_string(&log).expect("Event should serialize")
+    }
+
+    /// Whether the event is defined by NEP-171.
+    fn is_nep171(&self) -> bool {
+        matches!(
+            self,
+            NftEvent::NftMint(_)
+                | NftEvent::NftBurn(_)
+                | NftEvent::NftTransfer(_)
+                | NftEvent::ContractMetadataUpdate(_)
+                | NftEvent::NftMetadataUpdate(_)
+        )
+    }
+
+    /// Whether registered listeners are notified of the event.
+    fn is_token_movement(&self) -> bool {
+        matches!(
+            self,
+            NftEvent::NftMint(_) | NftEvent::NftBurn(_) | NftEvent::NftTransfer(_)
+        )
+    }
+}
+
+/// Event kept in the recent events buffer.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EventRecord {
+    pub event_id: U64,
+    pub block_height: BlockHeight,
+    pub log: String, // the `EVENT_JSON:{...}` line as logged
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// Id of the latest event, if any was emitted.
+    pub fn last_event_id(&self) -> Option<U64> {
+        self.next_event_id.checked_sub(1).map(U64)
+    }
+
+    /// Events after `event_id` still in the buffer, oldest first.
+    /// When the first returned id is not `event_id + 1`, the events in between were dropped.
+    pub fn events_since(&self, event_id: Option<U64>, limit: Option<u64>) -> Vec<EventRecord> {
+        let oldest = self.next_event_id.saturating_sub(MAX_RECENT_EVENTS);
+        let from = event_id.map_or(0, |event_id| event_id.0 + 1).max(oldest);
+        let limit = limit.unwrap_or(MAX_RECENT_EVENTS);
+        (from..self.next_event_id)
+            .take(limit as usize)
+            .map(|event_id| {
+                self.recent_events
+                    .get(&(event_id % MAX_RECENT_EVENTS))
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    /// Emits `nft_transfer` for the move in `log`, or adds it to the transfers of the batch
+    /// being run, grouped with the moves between the same accounts.
+    pub(crate) fn internal_emit_transfer(&mut self, log: NftTransferLog) {
+        let logs = match &mut self.transfer_logs {
+            Some(logs) => logs,
+            None => {
+                self.internal_emit(NftEvent::NftTransfer(vec![log]));
+                return;
+            }
+        };
+        match logs.iter_mut().find(|batched| {
+            batched.old_owner_id == log.old_owner_id
+                && batched.new_owner_id == log.new_owner_id
+                && batched.authorized_id == log.authorized_id
+        }) {
+            Some(batched) => batched.token_ids.extend(log.token_ids),
+            None => logs.push(log),
+        }
+    }
+
+    /// Emits `nft_metadata_update` so indexers and wallets refresh the cached JSON and media
+    /// of `token_ids`.
+    pub(crate) fn internal_emit_metadata_update(&mut self, token_ids: Vec<TokenId>, memo: &str) {
+        self.internal_emit(NftEvent::NftMetadataUpdate(vec![NftMetadataUpdateLog {
+            token_ids,
+            memo: Some(memo.to_string()),
+        }]));
+    }
+
+    /// Emits `contract_metadata_update` for a change affecting every token.
+    pub(crate) fn internal_emit_contract_metadata_update(&mut self, memo: Option<String>) {
+        self.internal_emit(NftEvent::ContractMetadataUpdate(vec![
+            ContractMetadataUpdateLog { memo },
+        ]));
+    }
+
+    /// Emits an event beyond the NEP-171 standard ones, left out of builds without the
+    /// `verbose-events` feature.
+    pub(crate) fn internal_emit_extension(&mut self, event: NftEvent) {
+        if cfg!(feature = "verbose-events") {
+            self.internal_emit(event);
+        }
+    }
+
+    /// Logs `event` and keeps it in the recent events buffer, returning its id.
+    /// Mints, burns and transfers are also sent to the registered listeners.
+    pub(crate) fn internal_emit(&mut self, event: NftEvent) -> u64 {
+        let json = event.to_json();
+        let log = format!("EVENT_JSON:{}", json);
+        env::log(log.as_bytes());
+
+        let event_id = self.next_event_id;
+        self.next_event_id += 1;
+        self.recent_events.insert(
+            &(event_id % MAX_RECENT_EVENTS),
+            &EventRecord {
+                event_id: event_id.into(),
+                block_height: env::block_index(),
+                log,
+            },
+        );
+        if event.is_token_movement() {
+            self.internal_notify_listeners(event_id, &json);
+        }
+        event_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::test_utils::get_logs;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn events_since_checkpoint() {
+        let (mut contract, context) = helper_mint();
+        assert_eq!(contract.last_event_id(), Some(U64(0)));
+        testing_env!(context);
+        contract.nft_mint(
+            "1".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+        let events = contract.events_since(Some(U64(0)), None);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_id, U64(1));
+        assert!(events[0].log.contains(r#""token_ids":["1"]"#));
+    }
+
+    #[test]
+    fn metadata_changes_emit_update_events() {
+        let (mut contract, _) = helper_mint();
+        contract.nft_set_license(
+            "0".to_string(),
+            Some(License {
+                identifier: "CC-BY-4.0".to_string(),
+                hash: None,
+            }),
+        );
+        assert!(get_logs().iter().any(|log| log.contains(
+            r#""event":"nft_metadata_update","data":[{"token_ids":["0"],"memo":"license"}]"#
+        )));
+
+        testing_env!(get_context(owner(), 0));
+        contract.set_fallback_gateways(vec!["https://ipfs.io/ipfs/".to_string()]);
+        assert!(get_logs()
+            .iter()
+            .any(|log| log.contains(r#""event":"contract_metadata_update""#)));
+    }
+}
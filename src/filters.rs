@@ -0,0 +1,167 @@
+use crate::*;
+use near_sdk::json_types::U128;
+
+/// Secondary index of tokens. Tokens are added to their creator and series filters when
+/// they are minted, and to their attribute filters once revealed.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum TokenFilter {
+    Creator { account_id: AccountId },
+    Series { series_id: SeriesId },
+    Attribute { trait_type: String, value: String },
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// Page of the tokens matching `filter`, in minting order unless tokens were burned.
+    pub fn nft_tokens_filtered(
+        &self,
+        filter: TokenFilter,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<JsonToken> {
+        let tokens = match self.internal_filter_tokens(&filter) {
+            Some(tokens) => tokens,
+            None => return vec![],
+        };
+        let token_ids = tokens.as_vector();
+        let from_index = from_index.map_or(0, |index| index.0 as u64);
+        let end = limit.map_or(token_ids.len(), |limit| {
+            token_ids.len().min(from_index.saturating_add(limit))
+        });
+        (from_index..end)
+            .map(|index| {
+                let token_id = token_ids.get(index).unwrap();
+                self.internal_json_token(self.tokens_by_id.get(&token_id).unwrap())
+            })
+            .collect()
+    }
+
+    pub fn nft_supply_filtered(&self, filter: TokenFilter) -> U128 {
+        U128(
+            self.internal_filter_tokens(&filter)
+                .map_or(0, |tokens| tokens.len() as u128),
+        )
+    }
+}
+
+impl NftContract {
+    pub(crate) fn internal_filter_tokens(
+        &self,
+        filter: &TokenFilter,
+    ) -> Option<UnorderedSet<TokenId>> {
+        self.tokens_by_filter.get(&filter.try_to_vec().unwrap())
+    }
+
+    /// Adds `token_id` to the index of `filter`. Returns whether it was not there yet.
+    pub(crate) fn internal_filter_add(&mut self, filter: &TokenFilter, token_id: &TokenId) -> bool {
+        let key = filter.try_to_vec().unwrap();
+        let mut tokens = self.tokens_by_filter.get(&key).unwrap_or_else(|| {
+            let mut filter_hash = CryptoHash::default();
+            filter_hash.copy_from_slice(&env::sha256(&key));
+            UnorderedSet::new(
+                StorageKey::TokensByFilterInner { filter_hash }
+                    .try_to_vec()
+                    .unwrap(),
+            )
+        });
+        if !tokens.insert(token_id) {
+            return false;
+        }
+        self.tokens_by_filter.insert(&key, &tokens);
+        true
+    }
+
+    /// Removes `token_id` from the index of `filter`. Returns whether it was there.
+    pub(crate) fn internal_filter_remove(
+        &mut self,
+        filter: &TokenFilter,
+        token_id: &TokenId,
+    ) -> bool {
+        let key = filter.try_to_vec().unwrap();
+        let mut tokens = match self.tokens_by_filter.get(&key) {
+            Some(tokens) => tokens,
+            None => return false,
+        };
+        if !tokens.remove(token_id) {
+            return false;
+        }
+        if tokens.is_empty() {
+            self.tokens_by_filter.remove(&key);
+        } else {
+            self.tokens_by_filter.insert(&key, &tokens);
+        }
+        true
+    }
+
+    /// Adds `token` to its creator and series filters.
+    pub(crate) fn internal_index_token(&mut self, token: &Token) {
+        for filter in token_filters(token) {
+            self.internal_filter_add(&filter, &token.token_id);
+        }
+    }
+
+    pub(crate) fn internal_unindex_token(&mut self, token: &Token) {
+        for filter in token_filters(token) {
+            self.internal_filter_remove(&filter, &token.token_id);
+        }
+    }
+}
+
+fn token_filters(token: &Token) -> Vec<TokenFilter> {
+    let mut filters = vec![TokenFilter::Creator {
+        account_id: token.creator_id.clone(),
+    }];
+    if let Some(series_id) = &token.series_id {
+        filters.push(TokenFilter::Series {
+            series_id: series_id.clone(),
+        });
+    }
+    filters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U128;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn tokens_filtered_by_creator() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        contract.nft_mint(
+            "1".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.nft_transfer(
+            ValidAccountId::try_from(nft()).unwrap(),
+            "1".to_string(),
+            None,
+        );
+
+        let filter = TokenFilter::Creator { account_id: bob() };
+        let tokens = contract.nft_tokens_filtered(filter.clone(), None, None);
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].token_id, "1");
+        assert_eq!(contract.nft_supply_filtered(filter), U128(1));
+    }
+
+    #[test]
+    fn backfill_pre_index_tokens() {
+        let (mut contract, _) = helper_mint();
+        contract.token_ids.clear();
+        assert!(contract.nft_tokens(None, None).is_empty());
+        testing_env!(get_context(owner(), 0));
+        contract.backfill_token_ids(vec!["0".to_string()]);
+        assert_eq!(contract.nft_tokens(None, None).len(), 1);
+    }
+}
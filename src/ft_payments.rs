@@ -0,0 +1,303 @@
+use crate::*;
+use near_sdk::json_types::U128;
+use near_sdk::{ext_contract, serde_json, PromiseOrValue};
+#[cfg(feature = "marketplace")]
+use near_sdk::{Gas, PromiseResult};
+
+#[cfg(feature = "marketplace")]
+const GAS_FOR_FT_TRANSFER: Gas = 10_000_000_000_000;
+#[cfg(feature = "marketplace")]
+const GAS_FOR_RESOLVE_FT_PAYOUT: Gas = 10_000_000_000_000;
+
+/// Mint price in a NEP-141 fungible token.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FtPrice {
+    pub ft_token_id: AccountId,
+    pub price: U128, // in the smallest unit of the token
+}
+
+/// What an `ft_transfer_call` to this contract pays for, given as its `msg`.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum FtPaymentAction {
+    Mint {
+        token_id: TokenId,
+        metadata: Box<TokenMetadata>,
+        royalty: Option<Royalty>,
+        transferable: Option<bool>,
+    },
+    #[cfg(feature = "marketplace")]
+    Buy { token_id: TokenId },
+}
+
+#[ext_contract(ext_ft)]
+trait FungibleToken {
+    fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>);
+}
+
+#[cfg(feature = "marketplace")]
+#[ext_contract(ext_ft_payout_resolver)]
+trait FtPayoutResolver {
+    fn on_ft_payout(&mut self, account_id: AccountId, ft_token_id: AccountId, amount: U128);
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// Lets mints be paid with `price` of a fungible token, or stops it with `None`.
+    pub fn set_ft_mint_price(&mut self, price: Option<FtPrice>) {
+        self.assert_admin();
+        self.ft_mint_price = price;
+    }
+
+    pub fn ft_mint_price(&self) -> Option<FtPrice> {
+        self.ft_mint_price.clone()
+    }
+
+    /// NEP-141 receiver: mints or buys a token for `sender_id`, as described by `msg`.
+    /// Returns the unused amount, which the fungible token contract refunds to the sender.
+    /// The storage of a minted token is paid out of the sender's prepaid storage.
+    pub fn ft_on_transfer(
+        &mut self,
+        sender_id: ValidAccountId,
+        amount: U128,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        self.assert_not_paused();
+        let ft_token_id = env::predecessor_account_id();
+        let action: FtPaymentAction = serde_json::from_str(&msg).expect("Invalid payment msg");
+        let price = match action {
+            FtPaymentAction::Mint {
+                token_id,
+                metadata,
+                royalty,
+                transferable,
+            } => {
+                self.assert_mint_not_ft_gated();
+                let price = match &self.ft_mint_price {
+                    Some(price) if price.ft_token_id == ft_token_id => price.price.0,
+                    _ => env::panic(b"Mints cannot be paid with this token"),
+                };
+                assert!(
+                    amount.0 >= price,
+                    "Must transfer {} to pay the mint price",
+                    price
+                );
+                let initial_storage_usage = env::storage_usage();
+                self.internal_mint(
+                    sender_id.as_ref(),
+                    token_id,
+                    *metadata,
+                    royalty,
+                    transferable.unwrap_or(true),
+                    price,
+                );
+                self.internal_charge_prepaid_storage(sender_id.as_ref(), initial_storage_usage);
+                price
+            }
+            #[cfg(feature = "marketplace")]
+            FtPaymentAction::Buy { token_id } => {
+                let listing = self.listings.get(&token_id).expect("Token is not listed");
+                assert_eq!(
+                    listing.ft_token_id.as_ref(),
+                    Some(&ft_token_id),
+                    "Listing is not priced in this token"
+                );
+                assert!(
+                    amount.0 >= listing.price.0,
+                    "Must transfer {} to pay the price",
+                    listing.price.0
+                );
+                let token = self.tokens_by_id.get(&token_id).unwrap();
+                assert_eq!(token.owner_id, listing.owner_id, "Listing is stale");
+                self.assert_can_receive(&token.owner_id, sender_id.as_ref());
+                pay_out_ft(self.internal_payout(&token, listing.price.0), &ft_token_id);
+                self.internal_transfer(token, sender_id.as_ref(), None, Some(listing.price.0));
+                self.internal_record_activity(&token_id, ActivityKind::Sale);
+                listing.price.0
+            }
+        };
+        PromiseOrValue::Value(U128(amount.0 - price))
+    }
+}
+
+#[cfg(feature = "marketplace")]
+#[near_bindgen]
+impl NftContract {
+    /// Keeps sale proceeds whose transfer failed, e.g. because the payee is not registered
+    /// with the fungible token, until the payee claims them with `ft_proceeds_claim`.
+    #[private]
+    pub fn on_ft_payout(&mut self, account_id: AccountId, ft_token_id: AccountId, amount: U128) {
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            return;
+        }
+        log!(
+            "Payout of {} of {} to @{} failed",
+            amount.0,
+            ft_token_id,
+            account_id
+        );
+        let key = (account_id, ft_token_id);
+        let unpaid = self.unpaid_ft_proceeds.get(&key).unwrap_or(0);
+        self.unpaid_ft_proceeds
+            .insert(&key, &math::add_u128(unpaid, amount.0, "Unpaid proceeds"));
+    }
+
+    /// Sends the caller its proceeds in `ft_token_id` whose transfer failed.
+    pub fn ft_proceeds_claim(&mut self, ft_token_id: ValidAccountId) -> U128 {
+        let account_id = env::predecessor_account_id();
+        let amount = self
+            .unpaid_ft_proceeds
+            .remove(&(account_id.clone(), ft_token_id.clone().into()))
+            .expect("No unpaid proceeds");
+        pay_ft(account_id, amount, ft_token_id.as_ref());
+        U128(amount)
+    }
+
+    pub fn unpaid_ft_proceeds(
+        &self,
+        account_id: ValidAccountId,
+        ft_token_id: ValidAccountId,
+    ) -> U128 {
+        U128(
+            self.unpaid_ft_proceeds
+                .get(&(account_id.into(), ft_token_id.into()))
+                .unwrap_or(0),
+        )
+    }
+}
+
+/// Sends the proceeds of a sale in `ft_token_id` to the owner and royalty payees in `payout`.
+/// Each transfer costs the contract one yoctoNEAR.
+#[cfg(feature = "marketplace")]
+fn pay_out_ft(payout: HashMap<AccountId, Balance>, ft_token_id: &AccountId) {
+    for (account_id, amount) in payout {
+        if amount > 0 {
+            pay_ft(account_id, amount, ft_token_id);
+        }
+    }
+}
+
+/// Sends `amount` of `ft_token_id` to `account_id`, keeping it for `ft_proceeds_claim` if
+/// the transfer fails.
+#[cfg(feature = "marketplace")]
+fn pay_ft(account_id: AccountId, amount: Balance, ft_token_id: &AccountId) {
+    ext_ft::ft_transfer(
+        account_id.clone(),
+        U128(amount),
+        None,
+        ft_token_id,
+        1,
+        GAS_FOR_FT_TRANSFER,
+    )
+    .then(ext_ft_payout_resolver::on_ft_payout(
+        account_id,
+        ft_token_id.clone(),
+        U128(amount),
+        &env::current_account_id(),
+        0,
+        GAS_FOR_RESOLVE_FT_PAYOUT,
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U128;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::MockedBlockchain;
+    use near_sdk::{testing_env, PromiseOrValue};
+
+    #[test]
+    fn mint_paid_with_ft() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = NftContract::default();
+        contract.set_ft_mint_price(Some(FtPrice {
+            ft_token_id: "usdc.near".to_string(),
+            price: U128(100),
+        }));
+        testing_env!(get_context(bob(), 10u128.pow(23)));
+        contract.storage_deposit(None);
+
+        testing_env!(get_context("usdc.near".to_string(), 0));
+        let msg = near_sdk::serde_json::json!({
+            "action": "mint",
+            "token_id": "0",
+            "metadata": helper_token_metadata(),
+        });
+        let unused = contract.ft_on_transfer(
+            ValidAccountId::try_from(bob()).unwrap(),
+            U128(150),
+            msg.to_string(),
+        );
+        assert!(matches!(unused, PromiseOrValue::Value(U128(50))));
+        assert_eq!(contract.nft_token("0".to_string()).unwrap().owner_id, bob());
+        let balance = contract
+            .storage_balance_of(ValidAccountId::try_from(bob()).unwrap())
+            .unwrap();
+        assert!(balance.available.0 < balance.total.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "must prepay")]
+    fn mint_paid_with_ft_without_storage() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = NftContract::default();
+        contract.set_ft_mint_price(Some(FtPrice {
+            ft_token_id: "usdc.near".to_string(),
+            price: U128(100),
+        }));
+
+        testing_env!(get_context("usdc.near".to_string(), 0));
+        let msg = near_sdk::serde_json::json!({
+            "action": "mint",
+            "token_id": "0",
+            "metadata": helper_token_metadata(),
+        });
+        contract.ft_on_transfer(
+            ValidAccountId::try_from(bob()).unwrap(),
+            U128(100),
+            msg.to_string(),
+        );
+    }
+
+    #[cfg(feature = "marketplace")]
+    #[test]
+    fn buy_listed_token_with_ft() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(context);
+        contract.nft_list(
+            "0".to_string(),
+            U128(100),
+            Some(ValidAccountId::try_from("usdc.near").unwrap()),
+        );
+
+        testing_env!(get_context("usdc.near".to_string(), 0));
+        let unused = contract.ft_on_transfer(
+            ValidAccountId::try_from(bob()).unwrap(),
+            U128(100),
+            "{\"action\":\"buy\",\"token_id\":\"0\"}".to_string(),
+        );
+        assert!(matches!(unused, PromiseOrValue::Value(U128(0))));
+        assert_eq!(contract.nft_token("0".to_string()).unwrap().owner_id, bob());
+    }
+
+    #[test]
+    #[should_panic(expected = "Mints cannot be paid with this token")]
+    fn mint_paid_with_unknown_ft() {
+        testing_env!(get_context("fake.near".to_string(), 0));
+        let mut contract = NftContract::default();
+        let msg = near_sdk::serde_json::json!({
+            "action": "mint",
+            "token_id": "0",
+            "metadata": helper_token_metadata(),
+        });
+        contract.ft_on_transfer(
+            ValidAccountId::try_from(bob()).unwrap(),
+            U128(100),
+            msg.to_string(),
+        );
+    }
+}
@@ -0,0 +1,420 @@
+use crate::*;
+use near_sdk::json_types::U128;
+use near_sdk::{ext_contract, Gas, Promise, PromiseOrValue, PromiseResult};
+
+/// Number of requirements a single `check_gate` call can fan out to.
+pub const MAX_GATE_REQUIREMENTS: usize = 10;
+
+const GAS_FOR_SUPPLY_FOR_OWNER: Gas = 10_000_000_000_000;
+const GAS_FOR_RESOLVE_GATE: Gas = 10_000_000_000_000;
+const GAS_FOR_FT_BALANCE_OF: Gas = 10_000_000_000_000;
+const GAS_FOR_GATED_MINT: Gas = 30_000_000_000_000;
+const GAS_FOR_RESOLVE_GATED_MINT_RESULT: Gas = 10_000_000_000_000;
+const GAS_FOR_RESOLVE_GATED_MINT: Gas =
+    GAS_FOR_GATED_MINT + GAS_FOR_RESOLVE_GATED_MINT_RESULT + 10_000_000_000_000;
+
+/// Minimum number of tokens `account_id` must hold in a partner collection.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GateRequirement {
+    pub contract_id: AccountId,
+    pub min_tokens: U128,
+}
+
+/// Minimum balance of a NEP-141 fungible token an account must hold to mint.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FtGate {
+    pub ft_token_id: AccountId,
+    pub min_balance: U128, // in the smallest unit of the token
+}
+
+#[ext_contract(ext_gate_ft)]
+trait GateFungibleToken {
+    fn ft_balance_of(&self, account_id: AccountId) -> U128;
+}
+
+#[ext_contract(ext_partner)]
+trait PartnerNftContract {
+    fn nft_supply_for_owner(&self, account_id: AccountId) -> U128;
+}
+
+#[ext_contract(ext_gate_resolver)]
+trait GateResolver {
+    fn resolve_gate(&self, requirements: Vec<GateRequirement>) -> bool;
+    fn resolve_gated_mint(
+        &mut self,
+        owner_id: AccountId,
+        token_id: TokenId,
+        metadata: TokenMetadata,
+        royalty: Option<Royalty>,
+        transferable: bool,
+        deposit: U128,
+    ) -> PromiseOrValue<Option<MintReceipt>>;
+    fn on_gated_mint(
+        &mut self,
+        owner_id: AccountId,
+        token_id: TokenId,
+        metadata: TokenMetadata,
+        royalty: Option<Royalty>,
+        transferable: bool,
+        deposit: U128,
+    ) -> MintReceipt;
+    fn resolve_gated_mint_result(
+        &mut self,
+        owner_id: AccountId,
+        token_id: TokenId,
+        deposit: U128,
+    ) -> Option<MintReceipt>;
+}
+
+#[near_bindgen]
+impl NftContract {
+    pub fn add_partner_contract(&mut self, contract_id: ValidAccountId) {
+        self.assert_admin();
+        self.partner_contracts.insert(contract_id.as_ref());
+    }
+
+    pub fn remove_partner_contract(&mut self, contract_id: ValidAccountId) {
+        self.assert_admin();
+        self.partner_contracts.remove(contract_id.as_ref());
+    }
+
+    pub fn partner_contracts(&self) -> Vec<AccountId> {
+        self.partner_contracts.to_vec()
+    }
+
+    /// Restricts mints to holders of at least `min_balance` of a fungible token, who mint with
+    /// `nft_mint_gated`. `None` lifts the requirement.
+    pub fn set_mint_ft_gate(&mut self, gate: Option<FtGate>) {
+        self.assert_admin();
+        self.mint_ft_gate = gate;
+    }
+
+    pub fn mint_ft_gate(&self) -> Option<FtGate> {
+        self.mint_ft_gate.clone()
+    }
+
+    /// `nft_mint` for token-gated drops: checks the caller's balance of the gate token, then
+    /// mints and resolves to the receipt. The deposit pays the mint price and the storage as
+    /// in `nft_mint`. When the balance is too low or the mint fails the deposit is refunded
+    /// and the call resolves to `None`.
+    #[payable]
+    pub fn nft_mint_gated(
+        &mut self,
+        token_id: TokenId,
+        metadata: TokenMetadata,
+        royalty: Option<Royalty>,
+        transferable: Option<bool>,
+    ) -> Promise {
+        self.assert_not_paused();
+        let gate = self.mint_ft_gate.clone().expect("Mints are not gated");
+        let owner_id = env::predecessor_account_id();
+        // Checked now as well, so that most bad mints fail before the balance is queried.
+        self.assert_valid_token_id(&token_id);
+        self.assert_not_blacklisted(&owner_id);
+        self.assert_valid_royalty(royalty.as_ref().unwrap_or(&Royalty::new()));
+        metadata.assert_valid();
+        self.assert_metadata_within_limits(&metadata);
+        assert!(
+            self.tokens_by_id.get(&token_id).is_none(),
+            "Token already exists"
+        );
+
+        ext_gate_ft::ft_balance_of(
+            owner_id.clone(),
+            &gate.ft_token_id,
+            0,
+            GAS_FOR_FT_BALANCE_OF,
+        )
+        .then(ext_gate_resolver::resolve_gated_mint(
+            owner_id,
+            token_id,
+            metadata,
+            royalty,
+            transferable.unwrap_or(true),
+            U128(env::attached_deposit()),
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_GATED_MINT,
+        ))
+    }
+
+    /// Mints in a receipt of its own when the balance is high enough, so that a failed mint
+    /// is rolled back on its own and the deposit refunded by `resolve_gated_mint_result`.
+    #[private]
+    pub fn resolve_gated_mint(
+        &mut self,
+        owner_id: AccountId,
+        token_id: TokenId,
+        metadata: TokenMetadata,
+        royalty: Option<Royalty>,
+        transferable: bool,
+        deposit: U128,
+    ) -> PromiseOrValue<Option<MintReceipt>> {
+        let balance = match env::promise_result(0) {
+            PromiseResult::Successful(value) => {
+                near_sdk::serde_json::from_slice::<U128>(&value).ok()
+            }
+            _ => None,
+        };
+        let min_balance = self
+            .mint_ft_gate
+            .as_ref()
+            .map_or(0, |gate| gate.min_balance.0);
+        if !matches!(balance, Some(balance) if balance.0 >= min_balance) {
+            log!(
+                "Gated mint of {} failed: balance of the gate token is too low",
+                token_id
+            );
+            if deposit.0 > 0 {
+                Promise::new(owner_id).transfer(deposit.0);
+            }
+            return PromiseOrValue::Value(None);
+        }
+
+        ext_gate_resolver::on_gated_mint(
+            owner_id.clone(),
+            token_id.clone(),
+            metadata,
+            royalty,
+            transferable,
+            deposit,
+            &env::current_account_id(),
+            0,
+            GAS_FOR_GATED_MINT,
+        )
+        .then(ext_gate_resolver::resolve_gated_mint_result(
+            owner_id,
+            token_id,
+            deposit,
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_GATED_MINT_RESULT,
+        ))
+        .into()
+    }
+
+    /// Mints `token_id` to `owner_id` out of their `deposit`, held by the contract since
+    /// `nft_mint_gated`, and refunds what the mint price and storage leave.
+    #[private]
+    pub fn on_gated_mint(
+        &mut self,
+        owner_id: AccountId,
+        token_id: TokenId,
+        metadata: TokenMetadata,
+        royalty: Option<Royalty>,
+        transferable: bool,
+        deposit: U128,
+    ) -> MintReceipt {
+        self.assert_not_paused();
+        let initial_storage_usage = env::storage_usage();
+        let price = self.internal_take_mint_price(&owner_id);
+        let receipt =
+            self.internal_mint(&owner_id, token_id, metadata, royalty, transferable, price);
+        self.internal_charge_mint_deposit_for(owner_id, deposit.0, initial_storage_usage, price);
+        receipt
+    }
+
+    /// Passes on the receipt of `on_gated_mint`, or refunds the whole deposit when it failed.
+    #[private]
+    pub fn resolve_gated_mint_result(
+        &mut self,
+        owner_id: AccountId,
+        token_id: TokenId,
+        deposit: U128,
+    ) -> Option<MintReceipt> {
+        if let PromiseResult::Successful(value) = env::promise_result(0) {
+            if let Ok(receipt) = near_sdk::serde_json::from_slice::<MintReceipt>(&value) {
+                return Some(receipt);
+            }
+        }
+        log!("Gated mint of {} failed, refunding the deposit", token_id);
+        if deposit.0 > 0 {
+            Promise::new(owner_id).transfer(deposit.0);
+        }
+        None
+    }
+
+    /// Resolves to `true` when `account_id` meets every requirement on partner collections.
+    pub fn check_gate(
+        &self,
+        account_id: ValidAccountId,
+        requirements: Vec<GateRequirement>,
+    ) -> Promise {
+        assert!(!requirements.is_empty(), "Requirements must not be empty");
+        assert!(
+            requirements.len() <= MAX_GATE_REQUIREMENTS,
+            "Cannot check more than {} requirements",
+            MAX_GATE_REQUIREMENTS
+        );
+        for requirement in requirements.iter() {
+            assert!(
+                self.partner_contracts.contains(&requirement.contract_id),
+                "Contract {} is not a partner",
+                requirement.contract_id
+            );
+        }
+
+        let checks = requirements
+            .iter()
+            .map(|requirement| {
+                ext_partner::nft_supply_for_owner(
+                    account_id.clone().into(),
+                    &requirement.contract_id,
+                    0,
+                    GAS_FOR_SUPPLY_FOR_OWNER,
+                )
+            })
+            .reduce(|checks, check| checks.and(check))
+            .unwrap();
+        checks.then(ext_gate_resolver::resolve_gate(
+            requirements,
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_GATE,
+        ))
+    }
+
+    #[private]
+    pub fn resolve_gate(&self, requirements: Vec<GateRequirement>) -> bool {
+        let supplies = (0..env::promise_results_count())
+            .map(|index| match env::promise_result(index) {
+                PromiseResult::Successful(value) => near_sdk::serde_json::from_slice(&value).ok(),
+                _ => None,
+            })
+            .collect::<Vec<Option<U128>>>();
+        gate_passed(&requirements, &supplies)
+    }
+}
+
+impl NftContract {
+    pub(crate) fn assert_mint_not_ft_gated(&self) {
+        if let Some(gate) = &self.mint_ft_gate {
+            env::panic(
+                format!(
+                    "Mints are gated by {}, use nft_mint_gated",
+                    gate.ft_token_id
+                )
+                .as_bytes(),
+            );
+        }
+    }
+}
+
+/// Every requirement must have a known supply of at least `min_tokens`.
+/// A failed or unparsable partner call fails the gate.
+pub(crate) fn gate_passed(requirements: &[GateRequirement], supplies: &[Option<U128>]) -> bool {
+    requirements.len() == supplies.len()
+        && requirements
+            .iter()
+            .zip(supplies)
+            .all(|(requirement, supply)| {
+                matches!(supply, Some(supply) if supply.0 >= requirement.min_tokens.0)
+            })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U128;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn gate_passes_on_every_requirement() {
+        let requirements = vec![
+            GateRequirement {
+                contract_id: "partner-a.near".to_string(),
+                min_tokens: U128(1),
+            },
+            GateRequirement {
+                contract_id: "partner-b.near".to_string(),
+                min_tokens: U128(2),
+            },
+        ];
+        assert!(gate_passed(&requirements, &[Some(U128(1)), Some(U128(3))]));
+        assert!(!gate_passed(&requirements, &[Some(U128(1)), Some(U128(1))]));
+        assert!(!gate_passed(&requirements, &[Some(U128(1)), None]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Contract partner-a.near is not a partner")]
+    fn check_gate_on_unknown_partner() {
+        testing_env!(get_context(owner(), 0));
+        let contract = NftContract::default();
+        contract.check_gate(
+            ValidAccountId::try_from(bob()).unwrap(),
+            vec![GateRequirement {
+                contract_id: "partner-a.near".to_string(),
+                min_tokens: U128(1),
+            }],
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Mints are gated by community.near, use nft_mint_gated")]
+    fn ft_gate_blocks_plain_mint() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = NftContract::default();
+        contract.set_mint_ft_gate(Some(FtGate {
+            ft_token_id: "community.near".to_string(),
+            min_balance: U128(100),
+        }));
+        assert_eq!(contract.mint_ft_gate().unwrap().min_balance, U128(100));
+
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        contract.nft_mint(
+            "0".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Token already exists")]
+    fn gated_mint_rejects_existing_token() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(get_context(owner(), 0));
+        contract.set_mint_ft_gate(Some(FtGate {
+            ft_token_id: "community.near".to_string(),
+            min_balance: U128(100),
+        }));
+
+        testing_env!(context);
+        contract.nft_mint_gated("0".to_string(), helper_token_metadata(), None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Mints are gated by community.near, use nft_mint_gated")]
+    fn ft_gate_blocks_series_mint() {
+        testing_env!(get_context(owner(), 10u128.pow(24)));
+        let mut contract = NftContract::default();
+        let series_id = contract.create_series(helper_token_metadata(), None, None, None, None);
+        contract.set_mint_ft_gate(Some(FtGate {
+            ft_token_id: "community.near".to_string(),
+            min_balance: U128(100),
+        }));
+        contract.nft_mint_series(series_id, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Must attach")]
+    fn gated_mint_charges_storage() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = NftContract::default();
+        contract.on_gated_mint(
+            bob(),
+            "0".to_string(),
+            helper_token_metadata(),
+            None,
+            true,
+            U128(0),
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use crate::*;
+use near_sdk::json_types::{U128, U64};
+
+/// Token sent with `nft_send`, waiting for its receiver to claim it.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Gift {
+    pub token_id: TokenId,
+    pub sender_id: AccountId,
+    pub receiver_id: AccountId,
+    pub sent_at: U64, // block timestamp in nanoseconds
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// Offers `token_id` to `receiver_id`, who must `nft_claim` it. Until then the token
+    /// stays with the caller but cannot move, and the caller can take it back with
+    /// `nft_send_cancel`. The deposit covers the storage of the pending gift, which goes back
+    /// to the caller once the gift is claimed or cancelled.
+    #[payable]
+    pub fn nft_send(&mut self, receiver_id: ValidAccountId, token_id: TokenId) {
+        self.assert_not_paused();
+        let token = self.tokens_by_id.get(&token_id).expect("Token not found");
+
+        if env::predecessor_account_id() != token.owner_id {
+            env::panic(b"Unauthorized");
+        }
+
+        token.assert_transferable();
+        assert_ne!(
+            receiver_id.as_ref(),
+            &token.owner_id,
+            "Token owner and receiver should be different"
+        );
+        self.assert_not_blacklisted(receiver_id.as_ref());
+        self.assert_not_leased(&token_id);
+        self.assert_not_dropped(&token_id);
+        self.assert_not_staked(&token_id);
+        self.assert_not_swapped(&token_id);
+        self.assert_no_auction_bids(&token_id);
+        assert!(self.gifts.get(&token_id).is_none(), "Token is already sent");
+
+        let initial_storage_usage = env::storage_usage();
+        self.internal_add_gift(&Gift {
+            token_id,
+            sender_id: token.owner_id,
+            receiver_id: receiver_id.into(),
+            sent_at: env::block_timestamp().into(),
+        });
+        self.internal_charge_storage(initial_storage_usage);
+    }
+
+    /// Accepts a token sent to the caller. The deposit covers the storage the caller takes,
+    /// such as their token set.
+    #[payable]
+    pub fn nft_claim(&mut self, token_id: TokenId) {
+        self.assert_not_paused();
+        let gift = self.gifts.get(&token_id).expect("Token is not sent");
+
+        if env::predecessor_account_id() != gift.receiver_id {
+            env::panic(b"Unauthorized");
+        }
+
+        let initial_storage_usage = env::storage_usage();
+        self.internal_remove_gift(&token_id);
+        let token = self.tokens_by_id.get(&token_id).unwrap();
+        self.internal_transfer(
+            token,
+            &gift.receiver_id,
+            Some(env::predecessor_account_id()),
+            None,
+        );
+        self.internal_record_activity(&token_id, ActivityKind::Transfer);
+        self.internal_charge_storage(initial_storage_usage);
+    }
+
+    /// Drops a pending gift, either taken back by its sender or declined by its receiver.
+    pub fn nft_send_cancel(&mut self, token_id: TokenId) {
+        let gift = self.gifts.get(&token_id).expect("Token is not sent");
+        let caller_id = env::predecessor_account_id();
+
+        if caller_id != gift.sender_id && caller_id != gift.receiver_id {
+            env::panic(b"Unauthorized");
+        }
+
+        self.internal_remove_gift(&token_id);
+    }
+
+    pub fn nft_pending_gift(&self, token_id: TokenId) -> Option<Gift> {
+        self.gifts.get(&token_id)
+    }
+
+    /// Page of the tokens waiting for `account_id` to claim them.
+    pub fn gifts_for_account(
+        &self,
+        account_id: ValidAccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<Gift> {
+        let token_ids = match self.gifts_by_receiver.get(account_id.as_ref()) {
+            Some(token_ids) => token_ids,
+            None => return vec![],
+        };
+        let from_index = from_index.map_or(0, |index| index.0 as usize);
+        let limit = limit.map_or(usize::MAX, |limit| limit as usize);
+        token_ids
+            .iter()
+            .skip(from_index)
+            .take(limit)
+            .map(|token_id| self.gifts.get(&token_id).unwrap())
+            .collect()
+    }
+
+    pub(crate) fn assert_not_sent(&self, token_id: &TokenId) {
+        assert!(
+            self.gifts.get(token_id).is_none(),
+            "Token is waiting to be claimed"
+        );
+    }
+
+    fn internal_add_gift(&mut self, gift: &Gift) {
+        self.gifts.insert(&gift.token_id, gift);
+
+        let mut token_ids = self
+            .gifts_by_receiver
+            .get(&gift.receiver_id)
+            .unwrap_or_else(|| {
+                UnorderedSet::new(
+                    StorageKey::GiftsByReceiverInner {
+                        account_id_hash: hash_account_id(&gift.receiver_id),
+                    }
+                    .try_to_vec()
+                    .unwrap(),
+                )
+            });
+        token_ids.insert(&gift.token_id);
+        self.gifts_by_receiver.insert(&gift.receiver_id, &token_ids);
+    }
+
+    pub(crate) fn internal_remove_gift(&mut self, token_id: &TokenId) -> Option<Gift> {
+        let initial_storage_usage = env::storage_usage();
+        let gift = self.gifts.remove(token_id)?;
+        let mut token_ids = self.gifts_by_receiver.get(&gift.receiver_id).unwrap();
+        token_ids.remove(token_id);
+        if token_ids.is_empty() {
+            self.gifts_by_receiver.remove(&gift.receiver_id);
+        } else {
+            self.gifts_by_receiver.insert(&gift.receiver_id, &token_ids);
+        }
+        let freed = initial_storage_usage.saturating_sub(env::storage_usage());
+        self.internal_refund_storage(&gift.sender_id, freed);
+        Some(gift)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn send_and_claim_gift() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(nft(), 10u128.pow(24)));
+        contract.nft_send(ValidAccountId::try_from(bob()).unwrap(), "0".to_string());
+        assert_eq!(contract.nft_token("0".to_string()).unwrap().owner_id, nft());
+        let gifts =
+            contract.gifts_for_account(ValidAccountId::try_from(bob()).unwrap(), None, None);
+        assert_eq!(gifts[0].sender_id, nft());
+
+        testing_env!(get_context(bob(), 10u128.pow(22)));
+        contract.nft_claim("0".to_string());
+        assert_eq!(contract.nft_token("0".to_string()).unwrap().owner_id, bob());
+        assert!(contract.nft_pending_gift("0".to_string()).is_none());
+        assert!(contract.token_set_payers.get(&bob()).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "to cover storage")]
+    fn claim_gift_without_storage_deposit() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(nft(), 10u128.pow(24)));
+        contract.nft_send(ValidAccountId::try_from(bob()).unwrap(), "0".to_string());
+
+        testing_env!(get_context(bob(), 0));
+        contract.nft_claim("0".to_string());
+    }
+
+    #[test]
+    fn cancel_gift_refunds_storage() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(nft(), 10u128.pow(23)));
+        let balance = contract.storage_deposit(None);
+        testing_env!(get_context(nft(), 0));
+        contract.nft_send(ValidAccountId::try_from(bob()).unwrap(), "0".to_string());
+        let sent = contract
+            .storage_balance_of(ValidAccountId::try_from(nft()).unwrap())
+            .unwrap();
+        assert!(sent.available.0 < balance.available.0);
+
+        contract.nft_send_cancel("0".to_string());
+        let cancelled = contract
+            .storage_balance_of(ValidAccountId::try_from(nft()).unwrap())
+            .unwrap();
+        assert!(cancelled.available.0 > sent.available.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Token is waiting to be claimed")]
+    fn transfer_sent_token() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(nft(), 10u128.pow(24)));
+        contract.nft_send(ValidAccountId::try_from(bob()).unwrap(), "0".to_string());
+        contract.nft_transfer(
+            ValidAccountId::try_from(owner()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+    }
+}
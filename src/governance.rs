@@ -0,0 +1,41 @@
+use crate::*;
+
+#[near_bindgen]
+impl NftContract {
+    /// Hands configuration over to a DAO account, or back to the owner with `None`.
+    /// Once set, only the DAO can change it.
+    pub fn set_dao_account(&mut self, dao_id: Option<ValidAccountId>) {
+        self.assert_admin();
+
+        log!("Set governance account to {:?}", dao_id);
+
+        self.dao_id = dao_id.map(|dao_id| dao_id.into());
+    }
+
+    pub fn dao_account(&self) -> Option<AccountId> {
+        self.dao_id.clone()
+    }
+
+    /// Account currently allowed to change the contract configuration.
+    pub fn governance_account(&self) -> AccountId {
+        self.dao_id.clone().unwrap_or_else(|| self.owner_id.clone())
+    }
+
+    /// Whether the current call may change the configuration: the DAO account, or the owner
+    /// when no DAO is set. While the multisig is on, only a confirmed proposal can, either
+    /// running one of the `AdminAction` variants or calling the contract from itself.
+    pub(crate) fn is_admin(&self) -> bool {
+        if self.multisig_threshold > 0 {
+            self.multisig_executing || env::predecessor_account_id() == env::current_account_id()
+        } else {
+            env::predecessor_account_id() == self.governance_account()
+        }
+    }
+
+    pub(crate) fn assert_admin(&self) {
+        self.assert_not_sunset();
+        if !self.is_admin() {
+            env::panic(b"Unauthorized");
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use crate::*;
+use near_sdk::json_types::U128;
+
+/// Number of ownership changes kept per token, the oldest are dropped first.
+pub const MAX_HISTORY_PER_TOKEN: usize = 50;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OwnershipRecord {
+    pub from_id: AccountId,
+    pub to_id: AccountId,
+    pub timestamp: U64,      // block timestamp in nanoseconds
+    pub price: Option<U128>, // in yoctoNEAR, when the token was sold
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// Ownership changes of `token_id`, oldest first.
+    pub fn nft_history(
+        &self,
+        token_id: TokenId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<OwnershipRecord> {
+        let from_index = from_index.map_or(0, |index| index.0 as usize);
+        let limit = limit.map_or(usize::MAX, |limit| limit as usize);
+        self.ownership_history
+            .get(&token_id)
+            .unwrap_or_default()
+            .into_iter()
+            .skip(from_index)
+            .take(limit)
+            .collect()
+    }
+
+    pub(crate) fn internal_record_ownership(
+        &mut self,
+        token_id: &TokenId,
+        from_id: &AccountId,
+        to_id: &AccountId,
+        price: Option<Balance>,
+    ) {
+        let mut history = self.ownership_history.get(token_id).unwrap_or_default();
+        if history.len() == MAX_HISTORY_PER_TOKEN {
+            history.remove(0);
+        }
+        history.push(OwnershipRecord {
+            from_id: from_id.clone(),
+            to_id: to_id.clone(),
+            timestamp: env::block_timestamp().into(),
+            price: price.map(U128),
+        });
+        self.ownership_history.insert(token_id, &history);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U128;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn ownership_history_on_transfers() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(context);
+        contract.nft_transfer(
+            ValidAccountId::try_from(bob()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        contract.decline_token("0".to_string());
+
+        let history = contract.nft_history("0".to_string(), None, None);
+        assert_eq!(history.len(), 2);
+        assert_eq!(
+            (history[0].from_id.clone(), history[0].to_id.clone()),
+            (nft(), bob())
+        );
+        assert_eq!(
+            (history[1].from_id.clone(), history[1].to_id.clone()),
+            (bob(), nft())
+        );
+        assert!(history[1].price.is_none());
+        assert_eq!(
+            contract
+                .nft_history("0".to_string(), Some(U128(1)), Some(5))
+                .len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use crate::*;
+
+/// Names of the arguments Keypom filled in a function-call drop, sent along so the
+/// contract can tell them apart from values set by the drop funder.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct KeypomArgs {
+    pub account_id_field: Option<String>,
+    pub drop_id_field: Option<String>,
+    pub key_id_field: Option<String>,
+    pub funder_id_field: Option<String>,
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// Trusts `keypom_id`, the Keypom contract, to mint through `nft_mint_keypom`.
+    pub fn set_keypom_contract(&mut self, keypom_id: Option<ValidAccountId>) {
+        self.assert_admin();
+        self.keypom_id = keypom_id.map(|keypom_id| keypom_id.into());
+    }
+
+    pub fn keypom_contract(&self) -> Option<AccountId> {
+        self.keypom_id.clone()
+    }
+
+    /// Mints `token_id` to `receiver_id` when a Keypom function-call drop is claimed, ex. by a
+    /// trial account created for a new user. Keypom must set `receiver_id` to the claiming
+    /// account, as declared by `keypom_args`. The deposit of the drop pays the mint price and
+    /// the storage, and the rest goes back to Keypom.
+    #[payable]
+    pub fn nft_mint_keypom(
+        &mut self,
+        receiver_id: ValidAccountId,
+        token_id: TokenId,
+        metadata: TokenMetadata,
+        keypom_args: KeypomArgs,
+    ) -> MintReceipt {
+        self.assert_not_paused();
+        self.assert_mint_not_ft_gated();
+        if self.keypom_id.as_ref() != Some(&env::predecessor_account_id()) {
+            env::panic(b"Unauthorized");
+        }
+        assert_eq!(
+            keypom_args.account_id_field.as_deref(),
+            Some("receiver_id"),
+            "Keypom must fill in receiver_id"
+        );
+
+        let initial_storage_usage = env::storage_usage();
+        let price = self.internal_take_mint_price(&env::predecessor_account_id());
+        let receipt =
+            self.internal_mint(receiver_id.as_ref(), token_id, metadata, None, true, price);
+        self.internal_charge_mint_deposit(initial_storage_usage, price);
+        receipt
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn keypom_mints_to_trial_account() {
+        let keypom = "v2.keypom.near".to_string();
+        testing_env!(get_context(owner(), 0));
+        let mut contract = NftContract::default();
+        contract.set_keypom_contract(Some(ValidAccountId::try_from(keypom.clone()).unwrap()));
+
+        testing_env!(get_context(keypom, 10u128.pow(24)));
+        let keypom_args = KeypomArgs {
+            account_id_field: Some("receiver_id".to_string()),
+            drop_id_field: None,
+            key_id_field: None,
+            funder_id_field: None,
+        };
+        contract.nft_mint_keypom(
+            ValidAccountId::try_from("trial.keypom.near").unwrap(),
+            "0".to_string(),
+            helper_token_metadata(),
+            keypom_args,
+        );
+        let token = contract.nft_token("0".to_string()).unwrap();
+        assert_eq!(token.owner_id, "trial.keypom.near");
+    }
+
+    #[test]
+    #[should_panic(expected = "Unauthorized")]
+    fn only_keypom_mints_for_drops() {
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        let mut contract = NftContract::default();
+        contract.nft_mint_keypom(
+            ValidAccountId::try_from(bob()).unwrap(),
+            "0".to_string(),
+            helper_token_metadata(),
+            KeypomArgs {
+                account_id_field: Some("receiver_id".to_string()),
+                drop_id_field: None,
+                key_id_field: None,
+                funder_id_field: None,
+            },
+        );
+    }
+}
@@ -0,0 +1,268 @@
+use crate::*;
+use near_sdk::json_types::{U128, U64};
+#[cfg(feature = "rental")]
+use near_sdk::Promise;
+
+/// Right of `lessee_id` to use a token while its owner keeps it. The lease starts once the
+/// lessee accepts it and pays the fee, and ends on its own at `expires_at`.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Lease {
+    pub token_id: TokenId,
+    pub owner_id: AccountId,
+    pub lessee_id: AccountId,
+    pub duration: U64,           // in nanoseconds
+    pub fee: U128,               // in yoctoNEAR, paid by the lessee to the owner
+    pub expires_at: Option<U64>, // block timestamp in nanoseconds, set once accepted
+}
+
+impl Lease {
+    pub(crate) fn is_active(&self) -> bool {
+        matches!(self.expires_at, Some(expires_at) if env::block_timestamp() < expires_at.0)
+    }
+}
+
+#[cfg(feature = "rental")]
+#[near_bindgen]
+impl NftContract {
+    /// Offers `lessee` the use of `token_id` for `duration` against `fee`. A free lease
+    /// starts right away, otherwise when the lessee accepts it with `lease_accept`.
+    /// The deposit covers the storage of the lease.
+    #[payable]
+    pub fn nft_lease(
+        &mut self,
+        token_id: TokenId,
+        lessee: ValidAccountId,
+        duration: U64,
+        fee: U128,
+    ) {
+        self.assert_not_paused();
+        let token = self.tokens_by_id.get(&token_id).expect("Token not found");
+
+        if env::predecessor_account_id() != token.owner_id {
+            env::panic(b"Unauthorized");
+        }
+
+        assert!(duration.0 > 0, "Duration must be greater than zero");
+        assert_ne!(
+            lessee.as_ref(),
+            &token.owner_id,
+            "Owner cannot lease to itself"
+        );
+        self.assert_not_leased(&token_id);
+        self.assert_not_staked(&token_id);
+        self.assert_not_swapped(&token_id);
+        self.assert_no_auction_bids(&token_id);
+
+        let initial_storage_usage = env::storage_usage();
+        let mut lease = Lease {
+            token_id,
+            owner_id: token.owner_id,
+            lessee_id: lessee.into(),
+            duration,
+            fee,
+            expires_at: None,
+        };
+        if fee.0 == 0 {
+            lease.expires_at =
+                Some(math::add_u64(env::block_timestamp(), duration.0, "Expiry").into());
+        }
+        self.internal_add_lease(&lease);
+        self.internal_charge_storage(initial_storage_usage);
+    }
+
+    /// Starts the lease offered to the caller, paying its fee to the owner. The rest of the
+    /// deposit is refunded.
+    #[payable]
+    pub fn lease_accept(&mut self, token_id: TokenId) {
+        self.assert_not_paused();
+        let mut lease = self.leases.get(&token_id).expect("Lease not found");
+
+        if env::predecessor_account_id() != lease.lessee_id {
+            env::panic(b"Unauthorized");
+        }
+
+        assert!(lease.expires_at.is_none(), "Lease is already accepted");
+        let attached = env::attached_deposit();
+        assert!(
+            attached >= lease.fee.0,
+            "Must attach the lease fee of {} yoctoNEAR",
+            lease.fee.0
+        );
+
+        lease.expires_at =
+            Some(math::add_u64(env::block_timestamp(), lease.duration.0, "Expiry").into());
+        self.leases.insert(&token_id, &lease);
+        Promise::new(lease.owner_id).transfer(lease.fee.0);
+        if attached > lease.fee.0 {
+            Promise::new(lease.lessee_id).transfer(attached - lease.fee.0);
+        }
+    }
+
+    /// Withdraws a lease offer that was not accepted yet, or clears an expired lease.
+    pub fn lease_cancel(&mut self, token_id: TokenId) {
+        let lease = self.leases.get(&token_id).expect("Lease not found");
+
+        if env::predecessor_account_id() != lease.owner_id {
+            env::panic(b"Unauthorized");
+        }
+
+        assert!(!lease.is_active(), "Lease is active");
+        self.internal_remove_lease(&token_id);
+    }
+
+    /// Account currently allowed to use `token_id`, if it is leased.
+    pub fn user_of(&self, token_id: TokenId) -> Option<AccountId> {
+        self.nft_active_lease(token_id).map(|lease| lease.lessee_id)
+    }
+
+    pub fn nft_active_lease(&self, token_id: TokenId) -> Option<Lease> {
+        self.leases.get(&token_id).filter(Lease::is_active)
+    }
+
+    /// Page of the active leases held by `account_id`.
+    pub fn leases_for_account(
+        &self,
+        account_id: ValidAccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<Lease> {
+        let token_ids = match self.leases_by_lessee.get(account_id.as_ref()) {
+            Some(token_ids) => token_ids,
+            None => return vec![],
+        };
+        let from_index = from_index.map_or(0, |index| index.0 as usize);
+        let limit = limit.map_or(usize::MAX, |limit| limit as usize);
+        token_ids
+            .iter()
+            .filter_map(|token_id| self.nft_active_lease(token_id))
+            .skip(from_index)
+            .take(limit)
+            .collect()
+    }
+}
+
+impl NftContract {
+    pub(crate) fn assert_not_leased(&self, token_id: &TokenId) {
+        if let Some(lease) = self.leases.get(token_id) {
+            assert!(
+                !lease.is_active(),
+                "Token is leased until {}",
+                lease.expires_at.unwrap().0
+            );
+        }
+    }
+
+    #[cfg(feature = "rental")]
+    fn internal_add_lease(&mut self, lease: &Lease) {
+        self.internal_remove_lease(&lease.token_id);
+        self.leases.insert(&lease.token_id, lease);
+
+        let mut token_ids = self
+            .leases_by_lessee
+            .get(&lease.lessee_id)
+            .unwrap_or_else(|| {
+                UnorderedSet::new(
+                    StorageKey::LeasesByLesseeInner {
+                        account_id_hash: hash_account_id(&lease.lessee_id),
+                    }
+                    .try_to_vec()
+                    .unwrap(),
+                )
+            });
+        token_ids.insert(&lease.token_id);
+        self.leases_by_lessee.insert(&lease.lessee_id, &token_ids);
+    }
+
+    /// Removes the lease on `token_id`, refunding its storage to the owner who offered it.
+    pub(crate) fn internal_remove_lease(&mut self, token_id: &TokenId) -> Option<Lease> {
+        let initial_storage_usage = env::storage_usage();
+        let lease = self.leases.remove(token_id)?;
+        let mut token_ids = self.leases_by_lessee.get(&lease.lessee_id).unwrap();
+        token_ids.remove(token_id);
+        if token_ids.is_empty() {
+            self.leases_by_lessee.remove(&lease.lessee_id);
+        } else {
+            self.leases_by_lessee.insert(&lease.lessee_id, &token_ids);
+        }
+        let freed = initial_storage_usage.saturating_sub(env::storage_usage());
+        self.internal_refund_storage(&lease.owner_id, freed);
+        Some(lease)
+    }
+}
+
+#[cfg(all(test, feature = "rental"))]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U128;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn lease_until_expiry() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(nft(), 10u128.pow(24)));
+        contract.nft_lease(
+            "0".to_string(),
+            ValidAccountId::try_from(bob()).unwrap(),
+            U64(100),
+            U128(10),
+        );
+        assert_eq!(contract.user_of("0".to_string()), None);
+
+        testing_env!(get_context(bob(), 15));
+        contract.lease_accept("0".to_string());
+        assert_eq!(contract.user_of("0".to_string()), Some(bob()));
+        let leases =
+            contract.leases_for_account(ValidAccountId::try_from(bob()).unwrap(), None, None);
+        assert_eq!(leases.len(), 1);
+        assert_eq!(contract.nft_token("0".to_string()).unwrap().owner_id, nft());
+
+        let mut context = get_context(nft(), 10u128.pow(24));
+        context.block_timestamp = 100;
+        testing_env!(context);
+        assert_eq!(contract.user_of("0".to_string()), None);
+        contract.nft_transfer(
+            ValidAccountId::try_from(owner()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+        assert!(contract
+            .leases_for_account(ValidAccountId::try_from(bob()).unwrap(), None, None)
+            .is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Token is leased until 100")]
+    fn transfer_leased_token() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(nft(), 10u128.pow(24)));
+        contract.nft_lease(
+            "0".to_string(),
+            ValidAccountId::try_from(bob()).unwrap(),
+            U64(100),
+            U128(0),
+        );
+        contract.nft_transfer(
+            ValidAccountId::try_from(owner()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Expiry overflow")]
+    fn lease_expiry_overflow() {
+        let (mut contract, mut context) = helper_mint();
+        context.block_timestamp = 1;
+        testing_env!(context);
+        contract.nft_lease(
+            "0".to_string(),
+            ValidAccountId::try_from(bob()).unwrap(),
+            U64(u64::MAX),
+            U128(0),
+        );
+    }
+}
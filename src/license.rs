@@ -0,0 +1,73 @@
+use crate::*;
+
+/// Licensing terms attached to a token.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct License {
+    pub identifier: String, // SPDX-like identifier, ex. "CC-BY-4.0", or URI to the license terms
+    pub hash: Option<Base64VecU8>, // Base64-encoded sha256 hash of the license terms. Recommended if `identifier` is a URI.
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// Sets or clears the license of a token. Only its creator can do it, and only before its metadata is frozen.
+    pub fn nft_set_license(&mut self, token_id: TokenId, license: Option<License>) {
+        self.assert_not_paused();
+        let mut token = self.tokens_by_id.get(&token_id).expect("Token not found");
+
+        if env::predecessor_account_id() != token.creator_id {
+            env::panic(b"Unauthorized");
+        }
+
+        assert!(!token.frozen, "Token metadata is frozen");
+        if let Some(license) = &license {
+            assert!(
+                !license.identifier.is_empty(),
+                "License identifier must not be empty"
+            );
+            if let Some(hash) = &license.hash {
+                assert_eq!(hash.0.len(), 32, "License hash must be 32 bytes");
+            }
+        }
+
+        token.license = license;
+        self.tokens_by_id.insert(&token_id, &token);
+        self.internal_emit_metadata_update(vec![token_id], "license");
+    }
+
+    pub fn nft_license(&self, token_id: TokenId) -> Option<License> {
+        self.tokens_by_id
+            .get(&token_id)
+            .expect("Token not found")
+            .license
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn set_license_from_creator() {
+        let (mut contract, _) = helper_mint();
+        let license = License {
+            identifier: "CC-BY-4.0".to_string(),
+            hash: None,
+        };
+        contract.nft_set_license("0".to_string(), Some(license.clone()));
+        assert_eq!(contract.nft_license("0".to_string()), Some(license.clone()));
+        assert_eq!(
+            contract.nft_token("0".to_string()).unwrap().license,
+            Some(license)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Token metadata is frozen")]
+    fn set_license_after_freeze() {
+        let (mut contract, _) = helper_mint();
+        contract.nft_freeze_metadata("0".to_string());
+        contract.nft_set_license("0".to_string(), None);
+    }
+}
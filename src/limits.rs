@@ -0,0 +1,187 @@
+use crate::*;
+
+/// Longest token id accepted until the admin changes it with `set_max_token_id_len`.
+pub const DEFAULT_MAX_TOKEN_ID_LEN: u32 = 128;
+
+/// Largest size in bytes of the free-form token metadata fields, so that a single token
+/// cannot bloat storage or push enumeration views past the gas limit.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MetadataLimits {
+    pub title: u32,
+    pub description: u32,
+    pub extra: u32,
+    pub media: u32,
+    pub reference: u32,
+    pub attributes: u32, // number of attributes
+    pub attribute: u32,  // bytes of the trait type, value and display type of each attribute
+    pub external_link: u32,
+}
+
+impl Default for MetadataLimits {
+    fn default() -> Self {
+        Self {
+            title: 256,
+            description: 4096,
+            extra: 4096,
+            media: 1024,
+            reference: 1024,
+            attributes: 32,
+            attribute: 256,
+            external_link: 256,
+        }
+    }
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// Caps the length of the ids of tokens minted from now on, in bytes.
+    pub fn set_max_token_id_len(&mut self, max_token_id_len: u32) {
+        self.assert_admin();
+        assert!(max_token_id_len > 0, "Max token id length must be positive");
+        self.max_token_id_len = max_token_id_len;
+    }
+
+    pub fn max_token_id_len(&self) -> u32 {
+        self.max_token_id_len
+    }
+
+    /// Caps the metadata of tokens minted or revealed from now on. Existing tokens are kept.
+    pub fn set_metadata_limits(&mut self, limits: MetadataLimits) {
+        self.assert_admin();
+        self.metadata_limits = limits;
+    }
+
+    pub fn metadata_limits(&self) -> MetadataLimits {
+        self.metadata_limits.clone()
+    }
+}
+
+impl NftContract {
+    /// Token ids are part of many storage keys, so the ones chosen by callers are kept short
+    /// and limited to ASCII letters, digits and `-`, `_`, `.`. The contract builds the ids
+    /// with `:` and `/`: series editions `<series_id>:<edition>`, collection tokens
+    /// `<collection_id>/<token_id>` and wrappers `<nft_contract_id>:<token_id>`.
+    pub(crate) fn assert_valid_token_id(&self, token_id: &TokenId) {
+        assert!(
+            !token_id.is_empty() && token_id.len() <= self.max_token_id_len as usize,
+            "Token id must have between 1 and {} bytes",
+            self.max_token_id_len
+        );
+        assert!(
+            token_id
+                .bytes()
+                .all(|c| c.is_ascii_alphanumeric() || b"-_.".contains(&c)),
+            "Token id can only have ASCII letters, digits, -, _ or ."
+        );
+    }
+
+    pub(crate) fn assert_metadata_within_limits(&self, metadata: &TokenMetadata) {
+        let limits = &self.metadata_limits;
+        assert_field_len("title", &metadata.title, limits.title);
+        assert_field_len("description", &metadata.description, limits.description);
+        assert_field_len("extra", &metadata.extra, limits.extra);
+        assert_field_len("media", &metadata.media, limits.media);
+        assert_field_len("reference", &metadata.reference, limits.reference);
+        if let Some(attributes) = &metadata.attributes {
+            assert!(
+                attributes.len() <= limits.attributes as usize,
+                "Metadata cannot have more than {} attributes",
+                limits.attributes
+            );
+            for attribute in attributes {
+                let len = attribute.trait_type.len()
+                    + attribute.value.len()
+                    + attribute.display_type.as_ref().map_or(0, String::len);
+                assert!(
+                    len <= limits.attribute as usize,
+                    "Metadata attribute {} cannot exceed {} bytes",
+                    attribute.trait_type,
+                    limits.attribute
+                );
+            }
+        }
+    }
+}
+
+fn assert_field_len(field: &str, value: &Option<String>, limit: u32) {
+    if let Some(value) = value {
+        assert!(
+            value.len() <= limit as usize,
+            "Metadata {} cannot exceed {} bytes",
+            field,
+            limit
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    #[should_panic(expected = "Token id can only have ASCII letters, digits, -, _ or .")]
+    fn mint_token_id_with_unsafe_chars() {
+        let (mut contract, _) = helper_mint();
+        contract.nft_mint(
+            "1\n2".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Token id must have between 1 and 4 bytes")]
+    fn mint_token_id_too_long() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(owner(), 10u128.pow(24)));
+        contract.set_max_token_id_len(4);
+        contract.nft_mint(
+            "12345".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Metadata title cannot exceed 8 bytes")]
+    fn mint_title_too_long() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(owner(), 10u128.pow(24)));
+        contract.set_metadata_limits(MetadataLimits {
+            title: 8,
+            ..MetadataLimits::default()
+        });
+        let mut metadata = helper_token_metadata();
+        metadata.title = Some("Arch Nemesis".to_string());
+        contract.nft_mint("1".to_string(), metadata, None, None, None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Metadata cannot have more than 1 attributes")]
+    fn mint_past_attributes_limit() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(owner(), 10u128.pow(24)));
+        contract.set_metadata_limits(MetadataLimits {
+            attributes: 1,
+            ..MetadataLimits::default()
+        });
+        let attribute = Attribute {
+            trait_type: "Background".to_string(),
+            value: "Blue".to_string(),
+            display_type: None,
+        };
+        let mut metadata = helper_token_metadata();
+        metadata.attributes = Some(vec![attribute.clone(), attribute]);
+        contract.nft_mint("1".to_string(), metadata, None, None, None, None);
+    }
+}
@@ -0,0 +1,98 @@
+use crate::*;
+use near_sdk::json_types::U64;
+use near_sdk::{ext_contract, Gas};
+
+/// Number of listener contracts the owner can register.
+pub const MAX_LISTENERS: u64 = 5;
+
+const GAS_FOR_ON_NFT_EVENT: Gas = 10_000_000_000_000;
+
+#[ext_contract(ext_listener)]
+pub trait NftEventListener {
+    /// Receives the NEP-297 `event` JSON after every mint, burn and transfer.
+    fn on_nft_event(&mut self, event_id: U64, event: String);
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// Notifies `account_id` through `on_nft_event` after every mint, burn and transfer.
+    /// The notifications are not awaited, so a failing listener never blocks a call.
+    pub fn add_listener(&mut self, account_id: ValidAccountId) {
+        self.assert_admin();
+        assert!(
+            self.listeners.len() < MAX_LISTENERS,
+            "Cannot register more than {} listeners",
+            MAX_LISTENERS
+        );
+        assert!(
+            self.listeners.insert(account_id.as_ref()),
+            "Listener is already registered"
+        );
+    }
+
+    pub fn remove_listener(&mut self, account_id: ValidAccountId) {
+        self.assert_admin();
+        assert!(
+            self.listeners.remove(account_id.as_ref()),
+            "Listener is not registered"
+        );
+    }
+
+    pub fn listeners(&self) -> Vec<AccountId> {
+        self.listeners.to_vec()
+    }
+
+    pub(crate) fn internal_notify_listeners(&self, event_id: u64, event: &str) {
+        for account_id in self.listeners.iter() {
+            ext_listener::on_nft_event(
+                event_id.into(),
+                event.to_string(),
+                &account_id,
+                0,
+                GAS_FOR_ON_NFT_EVENT,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn transfer_with_listener() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(owner(), 0));
+        contract.add_listener(ValidAccountId::try_from(bob()).unwrap());
+        assert_eq!(contract.listeners(), vec![bob()]);
+
+        testing_env!(get_context(nft(), 10u128.pow(24)));
+        contract.nft_transfer(
+            ValidAccountId::try_from(owner()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+        assert_eq!(
+            contract.nft_token("0".to_string()).unwrap().owner_id,
+            owner()
+        );
+
+        testing_env!(get_context(owner(), 0));
+        contract.remove_listener(ValidAccountId::try_from(bob()).unwrap());
+        assert!(contract.listeners().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot register more than 5 listeners")]
+    fn too_many_listeners() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(owner(), 0));
+        for i in 0..=MAX_LISTENERS {
+            contract.add_listener(ValidAccountId::try_from(format!("game{}.near", i)).unwrap());
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use crate::*;
+use near_sdk::json_types::U128;
+#[cfg(feature = "marketplace")]
+use near_sdk::Promise;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Listing {
+    pub token_id: TokenId,
+    pub owner_id: AccountId,
+    pub price: U128,                    // in yoctoNEAR, or in `ft_token_id` when set
+    pub ft_token_id: Option<AccountId>, // fungible token the price is in, paid with `ft_transfer_call`
+}
+
+#[cfg(feature = "marketplace")]
+#[near_bindgen]
+impl NftContract {
+    /// Lists `token_id` at `price` in NEAR, or in the fungible token `ft_token_id`. The
+    /// deposit covers the storage of the listing, which is refunded once it is removed.
+    #[payable]
+    pub fn nft_list(
+        &mut self,
+        token_id: TokenId,
+        price: U128,
+        ft_token_id: Option<ValidAccountId>,
+    ) {
+        self.assert_not_paused();
+        let token = self.tokens_by_id.get(&token_id).expect("Token not found");
+
+        if env::predecessor_account_id() != token.owner_id {
+            env::panic(b"Unauthorized");
+        }
+
+        token.assert_transferable();
+        assert!(price.0 > 0, "Price must be greater than zero");
+        assert!(
+            self.auctions.get(&token_id).is_none(),
+            "Token is in auction"
+        );
+
+        let initial_storage_usage = env::storage_usage();
+        self.internal_remove_listing(&token_id);
+        let listing = Listing {
+            token_id: token_id.clone(),
+            owner_id: token.owner_id,
+            price,
+            ft_token_id: ft_token_id.map(|ft_token_id| ft_token_id.into()),
+        };
+        self.listings.insert(&token_id, &listing);
+        self.listings_by_price
+            .insert(&(price.0, token_id.clone()), &());
+        self.internal_record_activity(&token_id, ActivityKind::List);
+        self.internal_charge_storage(initial_storage_usage);
+    }
+
+    pub fn nft_delist(&mut self, token_id: TokenId) {
+        self.assert_not_paused();
+        let listing = self.listings.get(&token_id).expect("Token is not listed");
+
+        if env::predecessor_account_id() != listing.owner_id {
+            env::panic(b"Unauthorized");
+        }
+
+        self.internal_remove_listing(&token_id);
+        self.internal_record_activity(&token_id, ActivityKind::Delist);
+    }
+
+    /// Buys a listed token at its price. The deposit covers the price and the storage taken
+    /// by the new owner, the rest is refunded. The price is split between the seller and
+    /// the royalty payees, after the referral cut of `referrer_id`.
+    #[payable]
+    pub fn nft_buy(&mut self, token_id: TokenId, referrer_id: Option<ValidAccountId>) {
+        self.assert_not_paused();
+        let buyer_id = env::predecessor_account_id();
+        let listing = self.listings.get(&token_id).expect("Token is not listed");
+        let token = self.tokens_by_id.get(&token_id).unwrap();
+        assert_eq!(token.owner_id, listing.owner_id, "Listing is stale");
+        assert!(
+            listing.ft_token_id.is_none(),
+            "Listing is priced in a fungible token"
+        );
+        self.assert_can_receive(&token.owner_id, &buyer_id);
+
+        let initial_storage_usage = env::storage_usage();
+        let referral = self.internal_pay_referral(referrer_id, listing.price.0);
+        self.internal_pay_out(&token, listing.price.0 - referral);
+        self.internal_transfer(token, &buyer_id, None, Some(listing.price.0));
+        self.internal_record_activity(&token_id, ActivityKind::Sale);
+
+        let (storage_used, _) = self.internal_storage_delta(initial_storage_usage);
+        let required = math::add_u128(
+            listing.price.0,
+            Balance::from(storage_used) * env::storage_byte_cost(),
+            "Deposit",
+        );
+        let attached = env::attached_deposit();
+        assert!(
+            attached >= required,
+            "Must attach {} yoctoNEAR to cover the price and storage",
+            required
+        );
+
+        log!(
+            "Sale of {} to @{} for {}",
+            token_id,
+            buyer_id,
+            listing.price.0
+        );
+
+        if attached > required {
+            Promise::new(buyer_id).transfer(attached - required);
+        }
+    }
+
+    pub fn nft_listing(&self, token_id: TokenId) -> Option<Listing> {
+        self.listings.get(&token_id)
+    }
+
+    /// Active listings ordered by price, cheapest first unless `ascending` is `false`.
+    pub fn listings_by_price(
+        &self,
+        ascending: bool,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<Listing> {
+        let from_index = from_index.map_or(0, |index| index.0 as usize);
+        let limit = limit.map_or(usize::MAX, |limit| limit as usize);
+        let keys: Box<dyn Iterator<Item = ((Balance, TokenId), ())>> = if ascending {
+            Box::new(self.listings_by_price.iter())
+        } else {
+            Box::new(self.listings_by_price.iter_rev())
+        };
+        keys.skip(from_index)
+            .take(limit)
+            .map(|((_, token_id), _)| self.listings.get(&token_id).unwrap())
+            .collect()
+    }
+}
+
+impl NftContract {
+    pub(crate) fn internal_remove_listing(&mut self, token_id: &TokenId) -> Option<Listing> {
+        let initial_storage_usage = env::storage_usage();
+        let listing = self.listings.remove(token_id)?;
+        self.listings_by_price
+            .remove(&(listing.price.0, token_id.clone()));
+        let freed = initial_storage_usage.saturating_sub(env::storage_usage());
+        self.internal_refund_storage(&listing.owner_id, freed);
+        Some(listing)
+    }
+}
+
+#[cfg(all(test, feature = "marketplace"))]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U128;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::test_utils::get_logs;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn buy_listed_token() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(context);
+        contract.nft_list("0".to_string(), U128(100), None);
+
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        contract.nft_buy("0".to_string(), None);
+        assert_eq!(contract.nft_token("0".to_string()).unwrap().owner_id, bob());
+        assert!(contract.nft_listing("0".to_string()).is_none());
+        let history = contract.nft_history("0".to_string(), None, None);
+        assert_eq!(history[0].price, Some(U128(100)));
+        assert!(get_logs().contains(
+            &"EVENT_JSON:{\"standard\":\"nep171\",\"version\":\"1.1.0\",\"event\":\"nft_transfer\",\"data\":[{\"old_owner_id\":\"nft.near\",\"new_owner_id\":\"bob.near\",\"token_ids\":[\"0\"]}]}"
+                .to_string()
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "Receiver does not accept tokens from nft.near")]
+    fn buy_from_sender_outside_allowlist() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(context);
+        contract.nft_list("0".to_string(), U128(100), None);
+
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        contract.set_receiving_policy(ReceivingPolicy::Allowlist(vec![owner()]));
+        contract.nft_buy("0".to_string(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Must attach")]
+    fn buy_below_price() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(context);
+        contract.nft_list("0".to_string(), U128(100), None);
+
+        testing_env!(get_context(bob(), 99));
+        contract.nft_buy("0".to_string(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "to cover storage")]
+    fn list_without_storage_deposit() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(nft(), 0));
+        contract.nft_list("0".to_string(), U128(100), None);
+    }
+
+    #[test]
+    fn listings_sorted_by_price() {
+        let (mut contract, _) = helper_mint();
+        contract.nft_mint(
+            "1".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.nft_mint(
+            "2".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.nft_list("0".to_string(), U128(30), None);
+        contract.nft_list("1".to_string(), U128(10), None);
+        contract.nft_list("2".to_string(), U128(20), None);
+        contract.nft_list("0".to_string(), U128(5), None);
+
+        let ids = |listings: Vec<Listing>| {
+            listings
+                .into_iter()
+                .map(|listing| listing.token_id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            ids(contract.listings_by_price(true, None, None)),
+            vec!["0", "1", "2"]
+        );
+        assert_eq!(
+            ids(contract.listings_by_price(false, Some(U128(1)), Some(1))),
+            vec!["1"]
+        );
+
+        contract.nft_delist("1".to_string());
+        contract.nft_transfer(
+            ValidAccountId::try_from(bob()).unwrap(),
+            "2".to_string(),
+            None,
+        );
+        assert_eq!(ids(contract.listings_by_price(true, None, None)), vec!["0"]);
+        assert!(contract.nft_listing("2".to_string()).is_none());
+    }
+}
@@ -0,0 +1,64 @@
+use crate::*;
+
+/// `a + b`, panicking with "`what` overflow" instead of wrapping.
+pub(crate) fn add_u32(a: u32, b: u32, what: &str) -> u32 {
+    a.checked_add(b)
+        .unwrap_or_else(|| env::panic(format!("{} overflow", what).as_bytes()))
+}
+
+/// `a + b`, panicking with "`what` overflow" instead of wrapping.
+pub(crate) fn add_u64(a: u64, b: u64, what: &str) -> u64 {
+    a.checked_add(b)
+        .unwrap_or_else(|| env::panic(format!("{} overflow", what).as_bytes()))
+}
+
+/// `a + b`, panicking with "`what` overflow" instead of wrapping.
+pub(crate) fn add_u128(a: u128, b: u128, what: &str) -> u128 {
+    a.checked_add(b)
+        .unwrap_or_else(|| env::panic(format!("{} overflow", what).as_bytes()))
+}
+
+/// `a * b`, panicking with "`what` overflow" instead of wrapping.
+pub(crate) fn mul_u128(a: u128, b: u128, what: &str) -> u128 {
+    a.checked_mul(b)
+        .unwrap_or_else(|| env::panic(format!("{} overflow", what).as_bytes()))
+}
+
+/// `amount * numerator / denominator` rounded down, for a share with `numerator` not above
+/// `denominator`, such as basis points. Never overflows, even for `amount` near `u128::MAX`.
+pub(crate) fn mul_div(amount: u128, numerator: u128, denominator: u128) -> u128 {
+    assert!(
+        numerator <= denominator,
+        "Share cannot exceed the whole amount"
+    );
+    amount / denominator * numerator + amount % denominator * numerator / denominator
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn mul_div_at_u128_boundary() {
+        assert_eq!(math::mul_div(u128::MAX, 5_000, 10_000), u128::MAX / 2);
+        assert_eq!(math::mul_div(u128::MAX, 10_000, 10_000), u128::MAX);
+        assert_eq!(math::mul_div(7, 3, 10), 2);
+        assert_eq!(math::add_u64(u64::MAX - 1, 1, "Count"), u64::MAX);
+    }
+
+    #[test]
+    #[should_panic(expected = "Minted count overflow")]
+    fn minted_count_overflow() {
+        let (mut contract, _) = helper_mint();
+        contract.minted_count = u64::MAX;
+        contract.nft_mint(
+            "1".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+    }
+}
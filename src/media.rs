@@ -0,0 +1,190 @@
+use crate::*;
+
+/// Number of fallback gateways the owner can configure.
+pub const MAX_FALLBACK_GATEWAYS: usize = 5;
+
+/// Token `media` resolved for wallets that don't join it with `base_uri` themselves.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ResolvedMedia {
+    pub url: String,
+    pub mime_type: Option<String>, // guessed from the data URL or the file extension
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// Permanently locks `base_uri` in the contract metadata.
+    pub fn freeze_base_uri(&mut self) {
+        self.assert_admin();
+        assert!(!self.base_uri_frozen, "Base URI is already frozen");
+        log!("Freeze base URI {:?}", self.nft_metadata().base_uri);
+        self.base_uri_frozen = true;
+    }
+
+    pub fn is_base_uri_frozen(&self) -> bool {
+        self.base_uri_frozen
+    }
+
+    /// Gateways tried in order after `base_uri` to resolve relative `media` paths.
+    pub fn set_fallback_gateways(&mut self, gateways: Vec<String>) {
+        self.assert_admin();
+        assert!(
+            gateways.len() <= MAX_FALLBACK_GATEWAYS,
+            "Cannot set more than {} fallback gateways",
+            MAX_FALLBACK_GATEWAYS
+        );
+        self.fallback_gateways = gateways;
+        self.internal_emit_contract_metadata_update(Some("fallback gateways".to_string()));
+    }
+
+    pub fn fallback_gateways(&self) -> Vec<String> {
+        self.fallback_gateways.clone()
+    }
+
+    /// Gateway to join with the relative `media` and `reference` paths of the token: the
+    /// `base_uri` of its collection or series when set, otherwise the contract one.
+    pub fn nft_base_uri(&self, token_id: TokenId) -> Option<String> {
+        let token = self.tokens_by_id.get(&token_id).expect("Token not found");
+        token
+            .collection_id
+            .as_ref()
+            .and_then(|collection_id| self.collections.get(collection_id))
+            .and_then(|collection| collection.metadata.base_uri)
+            .or_else(|| {
+                token
+                    .series_id
+                    .as_ref()
+                    .and_then(|series_id| self.series.get(series_id))
+                    .and_then(|series| series.base_uri)
+            })
+            .or_else(|| self.nft_metadata().base_uri)
+    }
+
+    /// Media URL of the token joined with the primary gateway, with its mime type.
+    pub fn nft_media(&self, token_id: TokenId) -> Option<ResolvedMedia> {
+        let media = self
+            .nft_token(token_id.clone())
+            .expect("Token not found")
+            .metadata
+            .media?;
+        let url = self
+            .nft_media_urls(token_id)
+            .into_iter()
+            .next()
+            .unwrap_or(media);
+        Some(ResolvedMedia {
+            mime_type: guess_mime_type(&url),
+            url,
+        })
+    }
+
+    /// Candidate URLs for the token `media`, the primary gateway first.
+    pub fn nft_media_urls(&self, token_id: TokenId) -> Vec<String> {
+        let base_uri = self.nft_base_uri(token_id.clone());
+        let token = self.nft_token(token_id).expect("Token not found");
+        match token.metadata.media {
+            None => vec![],
+            Some(media) if is_absolute_url(&media) => vec![media],
+            Some(media) => base_uri
+                .iter()
+                .chain(self.fallback_gateways.iter())
+                .map(|gateway| join_url(gateway, &media))
+                .collect(),
+        }
+    }
+}
+
+fn is_absolute_url(url: &str) -> bool {
+    url.contains("://") || url.starts_with("data:")
+}
+
+fn guess_mime_type(url: &str) -> Option<String> {
+    if let Some(data) = url.strip_prefix("data:") {
+        let end = data.find(&[';', ','][..])?;
+        return Some(data[..end].to_string()).filter(|mime_type| !mime_type.is_empty());
+    }
+    let path = url.split(&['?', '#'][..]).next().unwrap_or(url);
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    let (_, extension) = file_name.rsplit_once('.')?;
+    let mime_type = match extension.to_ascii_lowercase().as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "avif" => "image/avif",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "glb" => "model/gltf-binary",
+        "gltf" => "model/gltf+json",
+        "html" | "htm" => "text/html",
+        "json" => "application/json",
+        _ => return None,
+    };
+    Some(mime_type.to_string())
+}
+
+fn join_url(base: &str, path: &str) -> String {
+    format!(
+        "{}/{}",
+        base.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn media_urls_with_fallback_gateways() {
+        let (mut contract, _) = helper_mint();
+        let mut metadata = helper_token_metadata();
+        metadata.media = Some("/bafy/1.png".to_string());
+        metadata.media_hash = Some(Base64VecU8(vec![0; 32]));
+        contract.nft_mint("1".to_string(), metadata, None, None, None, None);
+
+        testing_env!(get_context(owner(), 0));
+        let mut contract_metadata = contract.nft_metadata();
+        contract_metadata.base_uri = Some("https://ipfs.io/ipfs/".to_string());
+        contract.set_contract_metadata(contract_metadata);
+        contract.set_fallback_gateways(vec!["https://dweb.link/ipfs".to_string()]);
+
+        assert!(contract.nft_media_urls("0".to_string()).is_empty());
+        assert_eq!(
+            contract.nft_media_urls("1".to_string()),
+            vec![
+                "https://ipfs.io/ipfs/bafy/1.png",
+                "https://dweb.link/ipfs/bafy/1.png"
+            ]
+        );
+    }
+
+    #[test]
+    fn media_resolved_with_mime_type() {
+        let (mut contract, _) = helper_mint();
+        let mut metadata = helper_token_metadata();
+        metadata.media = Some("bafy/1.PNG?v=2".to_string());
+        metadata.media_hash = Some(Base64VecU8(vec![0; 32]));
+        contract.nft_mint("1".to_string(), metadata, None, None, None, None);
+
+        testing_env!(get_context(owner(), 0));
+        let mut contract_metadata = contract.nft_metadata();
+        contract_metadata.base_uri = Some("https://ipfs.io/ipfs".to_string());
+        contract.set_contract_metadata(contract_metadata);
+
+        assert_eq!(contract.nft_media("0".to_string()), None);
+        assert_eq!(
+            contract.nft_media("1".to_string()),
+            Some(ResolvedMedia {
+                url: "https://ipfs.io/ipfs/bafy/1.PNG?v=2".to_string(),
+                mime_type: Some("image/png".to_string()),
+            })
+        );
+    }
+}
@@ -0,0 +1,343 @@
+use crate::*;
+
+/// Storage key holding the version of the layout stored under `STATE`.
+/// A missing key means version 1, the layout deployed before versioning was introduced.
+/// `new`, `upgrade` and `migrate` write it, so later versions always find it.
+const STATE_VERSION_KEY: &[u8] = b"STATE_VERSION";
+
+/// Prefix of the key a version 1 token set is moved to while its tokens are converted,
+/// so the current token set of the owner can take its place in `tokens_per_owner`.
+const V1_TOKEN_SET_PREFIX: &[u8] = b"V1_TOKEN_SET";
+
+/// Version of the current `NftContract` layout.
+///
+/// Only released layouts get a version. The layouts between version 1 and 2 were never
+/// deployed outside of development, so they cannot be migrated from.
+pub const STATE_VERSION: u8 = 2;
+
+/// Layout deployed before versioning, with the token metadata kept in each token.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct NftContractV1 {
+    pub metadata: NFTMetadata,
+    pub tokens_by_id: LookupMap<TokenId, TokenV1>,
+    pub tokens_per_owner: LookupMap<AccountId, UnorderedSet<TokenId>>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct TokenV1 {
+    pub token_id: TokenId,
+    pub owner_id: AccountId,
+    pub metadata: TokenMetadataV1,
+}
+
+/// Token metadata of version 1, with dates stored as ISO 8601 strings and no attributes.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct TokenMetadataV1 {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub media: Option<String>,
+    pub media_hash: Option<Base64VecU8>,
+    pub copies: Option<U64>,
+    pub issued_at: Option<String>,
+    pub expires_at: Option<String>,
+    pub starts_at: Option<String>,
+    pub updated_at: Option<String>,
+    pub extra: Option<String>,
+    pub reference: Option<String>,
+    pub reference_hash: Option<Base64VecU8>,
+}
+
+impl TokenMetadataV1 {
+    /// Dates that are not valid ISO 8601 are dropped, version 1 never checked them.
+    fn into_current(self) -> TokenMetadata {
+        let parse = |date: Option<String>| date.and_then(|date| time::parse_iso8601(&date).ok());
+        TokenMetadata {
+            title: self.title,
+            description: self.description,
+            media: self.media,
+            media_hash: self.media_hash,
+            copies: self.copies,
+            issued_at: parse(self.issued_at),
+            expires_at: parse(self.expires_at),
+            starts_at: parse(self.starts_at),
+            updated_at: parse(self.updated_at),
+            extra: self.extra,
+            reference: self.reference,
+            reference_hash: self.reference_hash,
+            attributes: None,
+        }
+    }
+}
+
+/// Writes the version of the current layout, for the next `migrate` to read.
+pub(crate) fn write_state_version() {
+    env::storage_write(STATE_VERSION_KEY, &[STATE_VERSION]);
+}
+
+/// Every layout of the contract state that has been deployed.
+///
+/// When a field is added to `NftContract`, copy the previous struct as `NftContractV<n>`,
+/// add a variant for it here, bump `STATE_VERSION`, and convert it in `into_current`.
+pub enum VersionedContract {
+    V1(Box<NftContractV1>),
+    Current(Box<NftContract>),
+}
+
+impl VersionedContract {
+    /// Reads the stored state using the layout recorded for it.
+    fn read(version: u8) -> Self {
+        match version {
+            1 => {
+                let state = env::storage_read(b"STATE").expect("Contract state not found");
+                VersionedContract::V1(Box::new(
+                    NftContractV1::try_from_slice(&state).unwrap_or_else(|_| {
+                        env::panic(b"State without a version is not version 1")
+                    }),
+                ))
+            }
+            STATE_VERSION => VersionedContract::Current(Box::new(
+                env::state_read().expect("Contract state not found"),
+            )),
+            _ => env::panic(format!("Unknown state version {}", version).as_bytes()),
+        }
+    }
+
+    fn into_current(self) -> NftContract {
+        match self {
+            // Version 1 tokens are converted in pages by `migrate_v1_tokens`, since they
+            // may not fit in the gas of one call. The contract stays paused until then.
+            VersionedContract::V1(v1) => {
+                let mut contract = NftContract::default();
+                contract.metadata.set(&v1.metadata);
+                contract.paused = true;
+                contract
+            }
+            VersionedContract::Current(contract) => *contract,
+        }
+    }
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// Converts the stored state to the current layout. Called by `upgrade` right after deploying new code.
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let version = env::storage_read(STATE_VERSION_KEY).map_or(1, |version| version[0]);
+        assert!(
+            version <= STATE_VERSION,
+            "Cannot migrate from newer state version {}",
+            version
+        );
+
+        log!(
+            "Migrate state from version {} to {}",
+            version,
+            STATE_VERSION
+        );
+
+        let contract = VersionedContract::read(version).into_current();
+        write_state_version();
+        contract
+    }
+
+    /// Converts tokens stored by version 1, which `migrate` leaves in place. Version 1 kept
+    /// no index of its tokens, so the admin passes their ids, ex. from the mint transactions,
+    /// in pages that fit in the gas of a call. Ids already converted are skipped.
+    /// Unpause the contract once every token is converted.
+    pub fn migrate_v1_tokens(&mut self, token_ids: Vec<TokenId>) {
+        self.assert_admin();
+        let mut v1_tokens: LookupMap<TokenId, TokenV1> =
+            LookupMap::new(StorageKey::TokensById.try_to_vec().unwrap());
+        let mut migrated = 0;
+        for token_id in token_ids {
+            if self.token_ids.contains(&token_id) {
+                continue;
+            }
+            let v1_token = v1_tokens
+                .remove(&token_id)
+                .unwrap_or_else(|| env::panic(b"Token not found"));
+            self.internal_remove_v1_token_from_owner(&v1_token.owner_id, &token_id);
+            let token =
+                self.internal_new_token(token_id, v1_token.owner_id.clone(), v1_token.owner_id);
+            self.internal_insert_token(&token, &v1_token.metadata.into_current());
+            migrated += 1;
+        }
+        log!("Migrate {} tokens from state version 1", migrated);
+    }
+
+    pub fn state_version(&self) -> u8 {
+        env::storage_read(STATE_VERSION_KEY).map_or(1, |version| version[0])
+    }
+}
+
+impl NftContract {
+    /// Drops `token_id` from the version 1 token set of `owner_id`. That set sits where
+    /// `tokens_per_owner` keeps the current token set of the owner, so it is moved aside the
+    /// first time. Both sets use the same inner prefix without sharing keys below it.
+    fn internal_remove_v1_token_from_owner(&mut self, owner_id: &AccountId, token_id: &TokenId) {
+        let key = [
+            StorageKey::TokensPerOwner.try_to_vec().unwrap(),
+            owner_id.try_to_vec().unwrap(),
+        ]
+        .concat();
+        let moved_key = [V1_TOKEN_SET_PREFIX, &key].concat();
+        if let Some(set) = env::storage_read(&key) {
+            if is_v1_token_set(owner_id, &set) {
+                env::storage_write(&moved_key, &set);
+                env::storage_remove(&key);
+            }
+        }
+        let mut tokens_set = match env::storage_read(&moved_key) {
+            Some(set) => UnorderedSet::<TokenId>::try_from_slice(&set).unwrap(),
+            None => return,
+        };
+        tokens_set.remove(token_id);
+        if tokens_set.is_empty() {
+            env::storage_remove(&moved_key);
+        } else {
+            env::storage_write(&moved_key, &tokens_set.try_to_vec().unwrap());
+        }
+    }
+}
+
+/// Whether `set` is the version 1 `UnorderedSet` of `owner_id` rather than its current
+/// `TreeMap`. Both start with a prefix built from the owner hash, with a different suffix.
+fn is_v1_token_set(owner_id: &AccountId, set: &[u8]) -> bool {
+    let empty = UnorderedSet::<TokenId>::new(
+        StorageKey::TokenPerOwnerInner {
+            account_id_hash: hash_account_id(owner_id),
+        }
+        .try_to_vec()
+        .unwrap(),
+    )
+    .try_to_vec()
+    .unwrap();
+    let mut len = [0; 4];
+    len.copy_from_slice(&empty[..4]);
+    let index_prefix_len = 4 + u32::from_le_bytes(len) as usize;
+    set.starts_with(&empty[..index_prefix_len])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U128;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn migrate_keeps_state() {
+        let (mut contract, _) = helper_mint();
+        contract.nft_set_license(
+            "0".to_string(),
+            Some(License {
+                identifier: "MIT".to_string(),
+                hash: None,
+            }),
+        );
+        env::state_write(&contract);
+        migrate::write_state_version(); // as `new` does
+        let contract = NftContract::migrate();
+        assert_eq!(contract.state_version(), STATE_VERSION);
+        assert_eq!(contract.nft_token("0".to_string()).unwrap().owner_id, nft());
+        assert_eq!(
+            contract.nft_license("0".to_string()).unwrap().identifier,
+            "MIT"
+        );
+    }
+
+    #[test]
+    fn migrate_from_v1() {
+        testing_env!(get_context(owner(), 0));
+        let mut v1 = NftContractV1 {
+            metadata: NFTMetadata {
+                spec: "z-nft-1.0.0".to_string(),
+                name: "Blockchain Z-days Demo".to_string(),
+                symbol: "ZNFT".to_string(),
+                icon: None,
+                base_uri: None,
+                reference: None,
+                reference_hash: None,
+            },
+            tokens_by_id: LookupMap::new(StorageKey::TokensById.try_to_vec().unwrap()),
+            tokens_per_owner: LookupMap::new(StorageKey::TokensPerOwner.try_to_vec().unwrap()),
+        };
+        for (token_id, owner_id) in &[("0", bob()), ("1", bob()), ("2", nft())] {
+            let token = TokenV1 {
+                token_id: token_id.to_string(),
+                owner_id: owner_id.clone(),
+                metadata: TokenMetadataV1 {
+                    title: Some(format!("Token {}", token_id)),
+                    description: None,
+                    media: None,
+                    media_hash: None,
+                    copies: None,
+                    issued_at: Some("2021-06-01T00:00:00Z".to_string()),
+                    expires_at: Some("soon".to_string()),
+                    starts_at: None,
+                    updated_at: None,
+                    extra: None,
+                    reference: None,
+                    reference_hash: None,
+                },
+            };
+            v1.tokens_by_id.insert(&token.token_id, &token);
+            let mut tokens_set = v1.tokens_per_owner.get(owner_id).unwrap_or_else(|| {
+                UnorderedSet::new(
+                    StorageKey::TokenPerOwnerInner {
+                        account_id_hash: hash_account_id(owner_id),
+                    }
+                    .try_to_vec()
+                    .unwrap(),
+                )
+            });
+            tokens_set.insert(&token.token_id);
+            v1.tokens_per_owner.insert(owner_id, &tokens_set);
+        }
+        env::state_write(&v1);
+
+        let mut contract = NftContract::migrate();
+        assert_eq!(contract.state_version(), STATE_VERSION);
+        assert_eq!(contract.nft_metadata().name, "Blockchain Z-days Demo");
+        assert!(contract.paused);
+
+        contract.migrate_v1_tokens(vec!["0".to_string(), "1".to_string()]);
+        contract.migrate_v1_tokens(vec!["1".to_string(), "2".to_string()]);
+        assert_eq!(contract.nft_total_supply(), U128(3));
+        let token = contract.nft_token("0".to_string()).unwrap();
+        assert_eq!(token.owner_id, bob());
+        assert_eq!(token.metadata.title, Some("Token 0".to_string()));
+        assert_eq!(token.metadata.issued_at, Some(1_622_505_600_000_000_000));
+        assert_eq!(token.metadata.expires_at, None);
+        let bob_tokens: Vec<TokenId> = contract
+            .nft_tokens_for_owner(ValidAccountId::try_from(bob()).unwrap(), None, None)
+            .into_iter()
+            .map(|token| token.token_id)
+            .collect();
+        assert_eq!(bob_tokens, vec!["0".to_string(), "1".to_string()]);
+        assert_eq!(
+            contract.nft_supply_for_owner(ValidAccountId::try_from(nft()).unwrap()),
+            U128(1)
+        );
+
+        contract.unpause();
+        testing_env!(get_context(bob(), 10u128.pow(22)));
+        contract.nft_transfer(
+            ValidAccountId::try_from(nft()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+        assert_eq!(contract.nft_token("0".to_string()).unwrap().owner_id, nft());
+    }
+
+    #[test]
+    #[should_panic(expected = "Token not found")]
+    fn migrate_unknown_v1_token() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = NftContract::default();
+        contract.migrate_v1_tokens(vec!["0".to_string()]);
+    }
+}
@@ -0,0 +1,280 @@
+use crate::*;
+use near_sdk::Promise;
+
+/// Admin action that runs once enough signers confirm it. While the multisig is on, every
+/// admin method can only run through a proposal: the variants below within the confirming
+/// call, any other method with `FunctionCall`, which calls it from the contract itself.
+/// Calls made with a full access key of the contract account also count as coming from the
+/// contract, so those keys should be deleted once the multisig is on.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum AdminAction {
+    Pause,
+    Unpause,
+    SetContractMetadata {
+        metadata: NFTMetadata,
+    },
+    SetDaoAccount {
+        dao_id: Option<ValidAccountId>,
+    },
+    AddPauser {
+        account_id: ValidAccountId,
+    },
+    RemovePauser {
+        account_id: ValidAccountId,
+    },
+    BlacklistAdd {
+        account_id: ValidAccountId,
+    },
+    BlacklistRemove {
+        account_id: ValidAccountId,
+    },
+    SetMultisig {
+        signers: Vec<ValidAccountId>,
+        threshold: u64,
+    },
+    FunctionCall {
+        method_name: String,
+        args: Base64VecU8, // as the method expects them, ex. JSON or borsh for `upgrade`
+        gas: U64,
+    },
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MultisigProposal {
+    pub id: u64,
+    pub action: AdminAction,
+    pub confirmations: Vec<AccountId>, // signers who confirmed, starting with the proposer
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// Requires `threshold` of `signers` to confirm every admin method. A threshold
+    /// of zero turns the multisig off. Once it is on, it can only be changed through a
+    /// `set_multisig` proposal. Confirmations of removed signers no longer count.
+    pub fn set_multisig(&mut self, signers: Vec<ValidAccountId>, threshold: u64) {
+        self.assert_admin();
+        assert!(
+            threshold as usize <= signers.len(),
+            "Threshold cannot exceed the number of signers"
+        );
+
+        log!("Set multisig to {} of {}", threshold, signers.len());
+
+        self.multisig_signers = signers.into_iter().map(|signer| signer.into()).collect();
+        self.multisig_threshold = threshold;
+    }
+
+    pub fn multisig_signers(&self) -> Vec<AccountId> {
+        self.multisig_signers.clone()
+    }
+
+    pub fn multisig_threshold(&self) -> u64 {
+        self.multisig_threshold
+    }
+
+    pub fn multisig_proposal(&self, id: u64) -> Option<MultisigProposal> {
+        self.multisig_proposals.get(&id).map(|mut proposal| {
+            self.internal_drop_removed_signers(&mut proposal);
+            proposal
+        })
+    }
+
+    /// Proposes `action`, counting the proposer as its first confirmation.
+    pub fn multisig_propose(&mut self, action: AdminAction) -> u64 {
+        assert!(self.multisig_threshold > 0, "Multisig is not enabled");
+        let signer_id = self.assert_signer();
+
+        let id = self.next_multisig_proposal_id;
+        self.next_multisig_proposal_id += 1;
+
+        log!("Multisig proposal {} by @{}", id, signer_id);
+
+        let proposal = MultisigProposal {
+            id,
+            action,
+            confirmations: vec![signer_id],
+        };
+        self.internal_confirm_or_execute(proposal);
+        id
+    }
+
+    pub fn multisig_confirm(&mut self, id: u64) {
+        let signer_id = self.assert_signer();
+        let mut proposal = self
+            .multisig_proposals
+            .get(&id)
+            .expect("Proposal not found");
+        assert!(
+            !proposal.confirmations.contains(&signer_id),
+            "Proposal already confirmed by this signer"
+        );
+
+        log!("Multisig proposal {} confirmed by @{}", id, signer_id);
+
+        proposal.confirmations.push(signer_id);
+        self.internal_confirm_or_execute(proposal);
+    }
+
+    /// Runs the proposal when it reaches the threshold, stores it otherwise.
+    fn internal_confirm_or_execute(&mut self, mut proposal: MultisigProposal) {
+        self.internal_drop_removed_signers(&mut proposal);
+        if (proposal.confirmations.len() as u64) < self.multisig_threshold {
+            self.multisig_proposals.insert(&proposal.id, &proposal);
+            return;
+        }
+
+        log!("Multisig proposal {} executed", proposal.id);
+
+        self.multisig_proposals.remove(&proposal.id);
+        self.multisig_executing = true;
+        match proposal.action {
+            AdminAction::Pause => self.pause(),
+            AdminAction::Unpause => self.unpause(),
+            AdminAction::SetContractMetadata { metadata } => self.set_contract_metadata(metadata),
+            AdminAction::SetDaoAccount { dao_id } => self.set_dao_account(dao_id),
+            AdminAction::AddPauser { account_id } => self.add_pauser(account_id),
+            AdminAction::RemovePauser { account_id } => self.remove_pauser(account_id),
+            AdminAction::BlacklistAdd { account_id } => self.blacklist_add(account_id),
+            AdminAction::BlacklistRemove { account_id } => self.blacklist_remove(account_id),
+            AdminAction::SetMultisig { signers, threshold } => {
+                self.set_multisig(signers, threshold)
+            }
+            AdminAction::FunctionCall {
+                method_name,
+                args,
+                gas,
+            } => {
+                Promise::new(env::current_account_id()).function_call(
+                    method_name.into_bytes(),
+                    args.0,
+                    0,
+                    gas.0,
+                );
+            }
+        }
+        self.multisig_executing = false;
+    }
+
+    /// Forgets the confirmations of accounts that are no longer signers.
+    fn internal_drop_removed_signers(&self, proposal: &mut MultisigProposal) {
+        let signers = &self.multisig_signers;
+        proposal
+            .confirmations
+            .retain(|account_id| signers.contains(account_id));
+    }
+
+    fn assert_signer(&self) -> AccountId {
+        let account_id = env::predecessor_account_id();
+        if !self.multisig_signers.contains(&account_id) {
+            env::panic(b"Unauthorized");
+        }
+        account_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U128;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn multisig_executes_at_threshold() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = NftContract::default();
+        contract.set_multisig(
+            vec![
+                ValidAccountId::try_from(owner()).unwrap(),
+                ValidAccountId::try_from(bob()).unwrap(),
+                ValidAccountId::try_from(nft()).unwrap(),
+            ],
+            2,
+        );
+        let id = contract.multisig_propose(AdminAction::Pause);
+        assert!(!contract.is_paused());
+        testing_env!(get_context(bob(), 0));
+        contract.multisig_confirm(id);
+        assert!(contract.is_paused());
+        assert!(contract.multisig_proposal(id).is_none());
+    }
+
+    #[test]
+    fn removed_signer_confirmations_do_not_count() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = NftContract::default();
+        contract.set_multisig(
+            vec![
+                ValidAccountId::try_from(owner()).unwrap(),
+                ValidAccountId::try_from(bob()).unwrap(),
+                ValidAccountId::try_from(nft()).unwrap(),
+            ],
+            2,
+        );
+        let pause_id = contract.multisig_propose(AdminAction::Pause);
+        testing_env!(get_context(bob(), 0));
+        let id = contract.multisig_propose(AdminAction::SetMultisig {
+            signers: vec![
+                ValidAccountId::try_from(bob()).unwrap(),
+                ValidAccountId::try_from(nft()).unwrap(),
+            ],
+            threshold: 2,
+        });
+        testing_env!(get_context(nft(), 0));
+        contract.multisig_confirm(id);
+        assert_eq!(contract.multisig_signers(), vec![bob(), nft()]);
+
+        testing_env!(get_context(bob(), 0));
+        contract.multisig_confirm(pause_id);
+        assert!(!contract.is_paused());
+        assert_eq!(
+            contract.multisig_proposal(pause_id).unwrap().confirmations,
+            vec![bob()]
+        );
+    }
+
+    /// Contract governed by the DAO `nft()`, with `bob()` as the only multisig signer.
+    fn helper_multisig() -> NftContract {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = NftContract::default();
+        contract.set_dao_account(Some(ValidAccountId::try_from(nft()).unwrap()));
+        testing_env!(get_context(nft(), 0));
+        contract.set_multisig(vec![ValidAccountId::try_from(bob()).unwrap()], 1);
+        contract
+    }
+
+    #[test]
+    #[should_panic(expected = "Unauthorized")]
+    fn direct_admin_call_under_multisig() {
+        let mut contract = helper_multisig();
+        contract.blacklist_add(ValidAccountId::try_from(bob()).unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "Unauthorized")]
+    fn direct_setting_under_multisig() {
+        let mut contract = helper_multisig();
+        contract.set_mint_price(U128(7));
+    }
+
+    #[test]
+    fn function_call_proposal_under_multisig() {
+        let mut contract = helper_multisig();
+        testing_env!(get_context(bob(), 0));
+        contract.multisig_propose(AdminAction::FunctionCall {
+            method_name: "set_mint_price".to_string(),
+            args: Base64VecU8(br#"{"price":"7"}"#.to_vec()),
+            gas: U64(10u64.pow(13)),
+        });
+
+        // The proposal calls the contract from itself, which is `owner()` in these tests.
+        testing_env!(get_context(owner(), 0));
+        contract.set_mint_price(U128(7));
+        assert_eq!(contract.current_mint_price(), U128(7));
+    }
+}
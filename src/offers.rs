@@ -0,0 +1,293 @@
+use crate::*;
+#[cfg(feature = "marketplace")]
+use near_sdk::collections::UnorderedMap;
+use near_sdk::json_types::{U128, U64};
+#[cfg(feature = "marketplace")]
+use near_sdk::Promise;
+
+/// Offer held in escrow by the contract until it is accepted or cancelled.
+/// It can only be accepted while the token has the owner it was made to.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Offer {
+    pub token_id: TokenId,
+    pub bidder_id: AccountId,
+    pub owner_id: AccountId, // owner of the token when the offer was placed
+    pub amount: U128,        // in yoctoNEAR
+    pub placed_at: U64,      // block timestamp in nanoseconds
+}
+
+#[cfg(feature = "marketplace")]
+#[near_bindgen]
+impl NftContract {
+    /// Offers `amount` for `token_id`, replacing and refunding an earlier offer of the caller.
+    /// The deposit covers the amount and the storage of the offer, the rest is refunded.
+    #[payable]
+    pub fn offer_place(&mut self, token_id: TokenId, amount: U128) {
+        self.assert_not_paused();
+        let bidder_id = env::predecessor_account_id();
+        self.assert_not_blacklisted(&bidder_id);
+        let token = self.tokens_by_id.get(&token_id).expect("Token not found");
+        assert_ne!(bidder_id, token.owner_id, "Owner cannot place an offer");
+        token.assert_transferable();
+        assert!(amount.0 > 0, "Amount must be greater than zero");
+
+        let initial_storage_usage = env::storage_usage();
+        let previous = self.internal_remove_offer(&token_id, &bidder_id);
+        let offer = Offer {
+            token_id: token_id.clone(),
+            bidder_id: bidder_id.clone(),
+            owner_id: token.owner_id,
+            amount,
+            placed_at: env::block_timestamp().into(),
+        };
+        self.internal_add_offer(&offer);
+
+        let (storage_used, _) = self.internal_storage_delta(initial_storage_usage);
+        let required = math::add_u128(
+            amount.0,
+            Balance::from(storage_used) * env::storage_byte_cost(),
+            "Deposit",
+        );
+        let refund = previous.map_or(0, |previous| previous.amount.0) + env::attached_deposit();
+        assert!(
+            refund >= required,
+            "Must attach {} yoctoNEAR to cover the offer and storage",
+            required
+        );
+        if refund > required {
+            Promise::new(bidder_id).transfer(refund - required);
+        }
+    }
+
+    /// Withdraws the caller's offer on `token_id` and refunds it with its storage. Offers
+    /// stay after the token changes hands or is burned, so this is how their bidders get
+    /// them back: refunding every offer on a transfer would not fit in the gas of a call.
+    pub fn offer_cancel(&mut self, token_id: TokenId) {
+        let bidder_id = env::predecessor_account_id();
+        let offer = self
+            .internal_remove_offer(&token_id, &bidder_id)
+            .expect("Offer not found");
+        Promise::new(bidder_id).transfer(offer.amount.0);
+    }
+
+    /// Sells the token to `bidder_id` for its offer. Only the token owner can accept, and only
+    /// offers made to them. The other offers on the token stay until cancelled.
+    pub fn offer_accept(&mut self, token_id: TokenId, bidder_id: ValidAccountId) {
+        self.assert_not_paused();
+        let token = self.tokens_by_id.get(&token_id).expect("Token not found");
+
+        if env::predecessor_account_id() != token.owner_id {
+            env::panic(b"Unauthorized");
+        }
+
+        let offer = self
+            .internal_remove_offer(&token_id, bidder_id.as_ref())
+            .expect("Offer not found");
+        assert_eq!(
+            offer.owner_id, token.owner_id,
+            "Offer was made to a former owner"
+        );
+        self.internal_pay_out(&token, offer.amount.0);
+        self.internal_transfer(
+            token,
+            &offer.bidder_id,
+            Some(env::predecessor_account_id()),
+            Some(offer.amount.0),
+        );
+        self.internal_record_activity(&token_id, ActivityKind::Sale);
+
+        log!(
+            "Sale of {} to @{} for {}",
+            token_id,
+            offer.bidder_id,
+            offer.amount.0
+        );
+    }
+
+    pub fn offers_for_token(
+        &self,
+        token_id: TokenId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<Offer> {
+        let offers = match self.offers_by_token.get(&token_id) {
+            Some(offers) => offers,
+            None => return vec![],
+        };
+        let from_index = from_index.map_or(0, |index| index.0 as usize);
+        let limit = limit.map_or(usize::MAX, |limit| limit as usize);
+        offers.values().skip(from_index).take(limit).collect()
+    }
+
+    pub fn offers_by_bidder(
+        &self,
+        account_id: ValidAccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<Offer> {
+        let token_ids = match self.offers_by_bidder.get(account_id.as_ref()) {
+            Some(token_ids) => token_ids,
+            None => return vec![],
+        };
+        let from_index = from_index.map_or(0, |index| index.0 as usize);
+        let limit = limit.map_or(usize::MAX, |limit| limit as usize);
+        token_ids
+            .iter()
+            .skip(from_index)
+            .take(limit)
+            .map(|token_id| {
+                self.offers_by_token
+                    .get(&token_id)
+                    .and_then(|offers| offers.get(account_id.as_ref()))
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    fn internal_add_offer(&mut self, offer: &Offer) {
+        let mut offers = self
+            .offers_by_token
+            .get(&offer.token_id)
+            .unwrap_or_else(|| {
+                let mut token_hash = CryptoHash::default();
+                token_hash.copy_from_slice(&env::sha256(offer.token_id.as_bytes()));
+                UnorderedMap::new(
+                    StorageKey::OffersByTokenInner { token_hash }
+                        .try_to_vec()
+                        .unwrap(),
+                )
+            });
+        offers.insert(&offer.bidder_id, offer);
+        self.offers_by_token.insert(&offer.token_id, &offers);
+
+        let mut token_ids = self
+            .offers_by_bidder
+            .get(&offer.bidder_id)
+            .unwrap_or_else(|| {
+                UnorderedSet::new(
+                    StorageKey::OffersByBidderInner {
+                        account_id_hash: hash_account_id(&offer.bidder_id),
+                    }
+                    .try_to_vec()
+                    .unwrap(),
+                )
+            });
+        token_ids.insert(&offer.token_id);
+        self.offers_by_bidder.insert(&offer.bidder_id, &token_ids);
+    }
+}
+
+#[cfg(feature = "marketplace")]
+impl NftContract {
+    /// Removes the offer of `bidder_id` on `token_id`, refunding its storage to the bidder.
+    fn internal_remove_offer(
+        &mut self,
+        token_id: &TokenId,
+        bidder_id: &AccountId,
+    ) -> Option<Offer> {
+        let initial_storage_usage = env::storage_usage();
+        let mut offers = self.offers_by_token.get(token_id)?;
+        let offer = offers.remove(bidder_id)?;
+        if offers.is_empty() {
+            self.offers_by_token.remove(token_id);
+        } else {
+            self.offers_by_token.insert(token_id, &offers);
+        }
+
+        let mut token_ids = self.offers_by_bidder.get(bidder_id).unwrap();
+        token_ids.remove(token_id);
+        if token_ids.is_empty() {
+            self.offers_by_bidder.remove(bidder_id);
+        } else {
+            self.offers_by_bidder.insert(bidder_id, &token_ids);
+        }
+        let freed = initial_storage_usage.saturating_sub(env::storage_usage());
+        self.internal_refund_storage(bidder_id, freed);
+        Some(offer)
+    }
+}
+
+#[cfg(all(test, feature = "marketplace"))]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U128;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn offer_place_and_accept() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        contract.offer_place("0".to_string(), U128(100));
+        contract.offer_place("0".to_string(), U128(150));
+        let offers =
+            contract.offers_by_bidder(ValidAccountId::try_from(bob()).unwrap(), None, None);
+        assert_eq!(offers.len(), 1);
+        assert_eq!(offers[0].amount, U128(150));
+
+        testing_env!(get_context(nft(), 0));
+        contract.offer_accept("0".to_string(), ValidAccountId::try_from(bob()).unwrap());
+        assert_eq!(contract.nft_token("0".to_string()).unwrap().owner_id, bob());
+        assert!(contract
+            .offers_for_token("0".to_string(), None, None)
+            .is_empty());
+    }
+
+    #[test]
+    fn offers_kept_on_transfer_until_cancelled() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        contract.offer_place("0".to_string(), U128(100));
+        let bidder = ValidAccountId::try_from(bob()).unwrap();
+
+        testing_env!(context);
+        contract.nft_transfer(
+            ValidAccountId::try_from(owner()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+        assert_eq!(
+            contract.offers_by_bidder(bidder.clone(), None, None)[0].owner_id,
+            nft()
+        );
+
+        testing_env!(get_context(bob(), 0));
+        contract.offer_cancel("0".to_string());
+        assert!(contract
+            .offers_for_token("0".to_string(), None, None)
+            .is_empty());
+        assert!(contract.offers_by_bidder(bidder, None, None).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Offer was made to a former owner")]
+    fn accept_offer_made_to_former_owner() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        contract.offer_place("0".to_string(), U128(100));
+
+        testing_env!(context);
+        contract.nft_transfer(
+            ValidAccountId::try_from(owner()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+        testing_env!(get_context(owner(), 0));
+        contract.offer_accept("0".to_string(), ValidAccountId::try_from(bob()).unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "Offer not found")]
+    fn accept_cancelled_offer() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        contract.offer_place("0".to_string(), U128(100));
+        contract.offer_cancel("0".to_string());
+
+        testing_env!(get_context(nft(), 0));
+        contract.offer_accept("0".to_string(), ValidAccountId::try_from(bob()).unwrap());
+    }
+}
@@ -0,0 +1,80 @@
+use crate::*;
+
+#[near_bindgen]
+impl NftContract {
+    pub fn owner(&self) -> AccountId {
+        self.owner_id.clone()
+    }
+
+    pub fn pending_owner(&self) -> Option<AccountId> {
+        self.pending_owner_id.clone()
+    }
+
+    /// First step of the ownership transfer, `new_owner_id` has to accept it.
+    /// While the multisig is on, it takes a confirmed proposal.
+    pub fn propose_owner(&mut self, new_owner_id: ValidAccountId) {
+        self.assert_owner();
+
+        log!("Propose @{} as owner", new_owner_id);
+
+        self.pending_owner_id = Some(new_owner_id.into());
+    }
+
+    pub fn accept_ownership(&mut self) {
+        self.assert_not_sunset();
+        let account_id = env::predecessor_account_id();
+        if self.pending_owner_id.as_ref() != Some(&account_id) {
+            env::panic(b"Unauthorized");
+        }
+
+        log!(
+            "Transfer ownership from @{} to @{}",
+            self.owner_id,
+            account_id
+        );
+
+        self.owner_id = account_id;
+        self.pending_owner_id = None;
+    }
+
+    pub(crate) fn assert_owner(&self) {
+        if self.multisig_threshold > 0 {
+            return self.assert_admin();
+        }
+        self.assert_not_sunset();
+        if env::predecessor_account_id() != self.owner_id {
+            env::panic(b"Unauthorized");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn two_step_ownership_transfer() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = NftContract::default();
+        contract.propose_owner(ValidAccountId::try_from(bob()).unwrap());
+        assert_eq!(contract.owner(), owner());
+        assert_eq!(contract.pending_owner(), Some(bob()));
+
+        testing_env!(get_context(bob(), 0));
+        contract.accept_ownership();
+        assert_eq!(contract.owner(), bob());
+        assert_eq!(contract.pending_owner(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unauthorized")]
+    fn accept_ownership_without_proposal() {
+        testing_env!(get_context(bob(), 0));
+        let mut contract = NftContract::default();
+        contract.accept_ownership();
+    }
+}
@@ -0,0 +1,91 @@
+use crate::*;
+
+#[near_bindgen]
+impl NftContract {
+    pub fn add_pauser(&mut self, account_id: ValidAccountId) {
+        self.assert_admin();
+        self.pausers.insert(account_id.as_ref());
+    }
+
+    pub fn remove_pauser(&mut self, account_id: ValidAccountId) {
+        self.assert_admin();
+        self.pausers.remove(account_id.as_ref());
+    }
+
+    pub fn pausers(&self) -> Vec<AccountId> {
+        self.pausers.to_vec()
+    }
+
+    /// Stops every user method that changes state, until `unpause` is called.
+    pub fn pause(&mut self) {
+        self.assert_pauser();
+        log!("Pause by @{}", env::predecessor_account_id());
+        self.paused = true;
+    }
+
+    pub fn unpause(&mut self) {
+        self.assert_pauser();
+        log!("Unpause by @{}", env::predecessor_account_id());
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub(crate) fn assert_not_paused(&self) {
+        self.assert_not_sunset();
+        assert!(!self.paused, "Contract is paused");
+    }
+
+    fn assert_pauser(&self) {
+        self.assert_not_sunset();
+        let account_id = env::predecessor_account_id();
+        if !self.is_admin() && !self.pausers.contains(&account_id) {
+            env::panic(b"Unauthorized");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    #[should_panic(expected = "Unauthorized")]
+    fn pause_from_owner_under_dao() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = NftContract::default();
+        contract.set_dao_account(Some(ValidAccountId::try_from(bob()).unwrap()));
+        assert_eq!(contract.governance_account(), bob());
+        contract.pause();
+    }
+
+    #[test]
+    #[should_panic(expected = "Contract is paused")]
+    fn transfer_while_paused() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(owner(), 0));
+        contract.add_pauser(ValidAccountId::try_from(bob()).unwrap());
+        testing_env!(get_context(bob(), 0));
+        contract.pause();
+        assert!(contract.is_paused());
+        testing_env!(get_context(nft(), 0));
+        contract.nft_transfer(
+            ValidAccountId::try_from(bob()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Unauthorized")]
+    fn pause_from_non_pauser() {
+        let (mut contract, _) = helper_mint();
+        contract.pause();
+    }
+}
@@ -0,0 +1,284 @@
+use crate::*;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::Promise;
+
+/// Number of drop phases the owner can configure.
+pub const MAX_MINT_PHASES: usize = 10;
+
+/// Drop phase where the mint price declines linearly from `start_price` at `starts_at`
+/// to `floor_price` at `ends_at`. Equal prices make a fixed-price phase.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MintPhase {
+    pub starts_at: U64, // block timestamp in nanoseconds
+    pub ends_at: U64,   // block timestamp in nanoseconds, excluded
+    pub start_price: U128,
+    pub floor_price: U128,
+}
+
+impl MintPhase {
+    fn price_at(&self, timestamp: u64) -> Balance {
+        let elapsed = Balance::from(timestamp - self.starts_at.0);
+        let duration = Balance::from(self.ends_at.0 - self.starts_at.0);
+        let decline = self.start_price.0 - self.floor_price.0;
+        self.start_price.0 - math::mul_div(decline, elapsed, duration)
+    }
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// Price of a mint outside the drop phases.
+    pub fn set_mint_price(&mut self, price: U128) {
+        self.assert_admin();
+        self.mint_price = price.0;
+    }
+
+    pub fn mint_price(&self) -> U128 {
+        U128(self.mint_price)
+    }
+
+    /// Replaces the drop phases, which must be in order and not overlap.
+    pub fn set_mint_phases(&mut self, phases: Vec<MintPhase>) {
+        self.assert_admin();
+        assert!(
+            phases.len() <= MAX_MINT_PHASES,
+            "Cannot set more than {} mint phases",
+            MAX_MINT_PHASES
+        );
+        for phase in phases.iter() {
+            assert!(
+                phase.starts_at.0 < phase.ends_at.0,
+                "Mint phase must end after it starts"
+            );
+            assert!(
+                phase.floor_price.0 <= phase.start_price.0,
+                "Floor price must not exceed the start price"
+            );
+        }
+        for pair in phases.windows(2) {
+            assert!(
+                pair[0].ends_at.0 <= pair[1].starts_at.0,
+                "Mint phases must be in order and not overlap"
+            );
+        }
+        self.mint_phases = phases;
+    }
+
+    pub fn mint_phases(&self) -> Vec<MintPhase> {
+        self.mint_phases.clone()
+    }
+
+    /// Price of a mint at the current block: from the running drop phase if any,
+    /// otherwise the mint price, but never below the floor of the last phase that ended.
+    pub fn current_mint_price(&self) -> U128 {
+        let now = env::block_timestamp();
+        if let Some(phase) = self
+            .mint_phases
+            .iter()
+            .find(|phase| phase.starts_at.0 <= now && now < phase.ends_at.0)
+        {
+            return U128(phase.price_at(now));
+        }
+        let price = self
+            .mint_phases
+            .iter()
+            .rev()
+            .find(|phase| phase.ends_at.0 <= now)
+            .map_or(self.mint_price, |phase| {
+                self.mint_price.max(phase.floor_price.0)
+            });
+        U128(price)
+    }
+
+    /// Lets `account_id` mint `count` more tokens without paying the mint price.
+    pub fn grant_free_mints(&mut self, account_id: ValidAccountId, count: u32) {
+        self.assert_admin();
+        assert!(count > 0, "Must grant at least one free mint");
+        let free_mints = self.free_mints.get(account_id.as_ref()).unwrap_or(0);
+        self.free_mints.insert(
+            account_id.as_ref(),
+            &math::add_u32(free_mints, count, "Free mints"),
+        );
+
+        log!("Grant {} free mints to @{}", count, account_id);
+    }
+
+    pub fn free_mints(&self, account_id: ValidAccountId) -> u32 {
+        self.free_mints.get(account_id.as_ref()).unwrap_or(0)
+    }
+
+    /// Price of a mint by `account_id`, using up one of their free mints if any.
+    pub(crate) fn internal_take_mint_price(&mut self, account_id: &AccountId) -> Balance {
+        match self.free_mints.get(account_id) {
+            Some(free_mints) => {
+                if free_mints > 1 {
+                    self.free_mints.insert(account_id, &(free_mints - 1));
+                } else {
+                    self.free_mints.remove(account_id);
+                }
+                0
+            }
+            None => self.current_mint_price().0,
+        }
+    }
+
+    /// Takes `price` and the storage used since `initial_storage_usage` out of the attached
+    /// deposit and refunds the rest.
+    pub(crate) fn internal_charge_mint_deposit(
+        &mut self,
+        initial_storage_usage: u64,
+        price: Balance,
+    ) {
+        self.internal_charge_mint_deposit_for(
+            env::predecessor_account_id(),
+            env::attached_deposit(),
+            initial_storage_usage,
+            price,
+        );
+    }
+
+    /// Same as `internal_charge_mint_deposit` for a mint by `account_id` paid with `attached`,
+    /// when they are not the caller and its deposit, as in callbacks.
+    pub(crate) fn internal_charge_mint_deposit_for(
+        &mut self,
+        account_id: AccountId,
+        attached: Balance,
+        initial_storage_usage: u64,
+        price: Balance,
+    ) {
+        self.internal_record_token_set_payer(&account_id);
+        let storage_used = env::storage_usage().saturating_sub(initial_storage_usage);
+        let storage_cost = Balance::from(storage_used) * env::storage_byte_cost();
+        let required = math::add_u128(price, storage_cost, "Mint deposit");
+        if attached < required {
+            env::panic(
+                format!(
+                    "Must attach {} yoctoNEAR to mint: {} for the mint price and {} for {} bytes of storage",
+                    required, price, storage_cost, storage_used
+                )
+                .as_bytes(),
+            );
+        }
+        if attached > required {
+            Promise::new(account_id).transfer(attached - required);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U128;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn free_mint_skips_price() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = NftContract::default();
+        contract.set_mint_price(U128(10));
+        contract.grant_free_mints(ValidAccountId::try_from(bob()).unwrap(), 1);
+
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        let receipt = contract.nft_mint(
+            "0".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+        assert_eq!(receipt.fee_paid, U128(0));
+        assert_eq!(
+            contract.free_mints(ValidAccountId::try_from(bob()).unwrap()),
+            0
+        );
+
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        let receipt = contract.nft_mint(
+            "1".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+        assert_eq!(receipt.fee_paid, U128(10));
+    }
+
+    #[test]
+    #[should_panic(expected = "Must grant at least one free mint")]
+    fn grant_zero_free_mints() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = NftContract::default();
+        contract.grant_free_mints(ValidAccountId::try_from(bob()).unwrap(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "yoctoNEAR to mint: 10 for the mint price")]
+    fn mint_without_price() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = NftContract::default();
+        contract.set_mint_price(U128(10));
+        contract.nft_mint(
+            "0".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    fn dutch_auction_mint_price() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = NftContract::default();
+        contract.set_mint_price(U128(1));
+        contract.set_mint_phases(vec![MintPhase {
+            starts_at: U64(100),
+            ends_at: U64(200),
+            start_price: U128(1000),
+            floor_price: U128(500),
+        }]);
+
+        let mut context = get_context(nft(), 10u128.pow(24));
+        context.block_timestamp = 50;
+        testing_env!(context.clone());
+        assert_eq!(contract.current_mint_price(), U128(1));
+
+        context.block_timestamp = 150;
+        testing_env!(context.clone());
+        assert_eq!(contract.current_mint_price(), U128(750));
+        let receipt = contract.nft_mint(
+            "0".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+        assert_eq!(receipt.fee_paid, U128(750));
+
+        // The drop ended, the price stays at its floor.
+        context.block_timestamp = 200;
+        testing_env!(context);
+        assert_eq!(contract.current_mint_price(), U128(500));
+    }
+
+    #[test]
+    #[should_panic(expected = "Mint phases must be in order and not overlap")]
+    fn overlapping_mint_phases() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = NftContract::default();
+        let phase = MintPhase {
+            starts_at: U64(100),
+            ends_at: U64(200),
+            start_price: U128(1000),
+            floor_price: U128(500),
+        };
+        contract.set_mint_phases(vec![phase.clone(), phase]);
+    }
+}
@@ -0,0 +1,106 @@
+use crate::*;
+use near_sdk::json_types::U128;
+
+#[near_bindgen]
+impl NftContract {
+    /// Points `token_id` to a page about it, ex. the artist profile, shown by galleries as
+    /// `external_link`, up to the `external_link` metadata limit. `None` removes the link.
+    /// The deposit covers its storage.
+    #[payable]
+    pub fn nft_set_external_link(&mut self, token_id: TokenId, external_link: Option<String>) {
+        self.assert_not_paused();
+        let token = self.tokens_by_id.get(&token_id).expect("Token not found");
+
+        if env::predecessor_account_id() != token.owner_id {
+            env::panic(b"Unauthorized");
+        }
+
+        let initial_storage_usage = env::storage_usage();
+        match external_link {
+            Some(external_link) => {
+                let limit = self.metadata_limits.external_link;
+                assert!(
+                    !external_link.is_empty() && external_link.len() <= limit as usize,
+                    "External link must have between 1 and {} bytes",
+                    limit
+                );
+                self.external_links.insert(&token_id, &external_link);
+            }
+            None => {
+                self.external_links.remove(&token_id);
+            }
+        }
+        self.internal_emit_metadata_update(vec![token_id], "external link");
+        self.internal_charge_storage(initial_storage_usage);
+    }
+
+    /// Page of the tokens minted by `creator_id` that still exist, for artist pages.
+    pub fn nft_tokens_by_creator(
+        &self,
+        creator_id: ValidAccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<JsonToken> {
+        self.nft_tokens_filtered(
+            TokenFilter::Creator {
+                account_id: creator_id.into(),
+            },
+            from_index,
+            limit,
+        )
+    }
+
+    pub fn nft_supply_for_creator(&self, creator_id: ValidAccountId) -> U128 {
+        self.nft_supply_filtered(TokenFilter::Creator {
+            account_id: creator_id.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U128;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn external_link_and_creator_tokens() {
+        let (mut contract, context) = helper_mint();
+        contract.nft_set_external_link(
+            "0".to_string(),
+            Some("https://example.com/artists/nft".to_string()),
+        );
+        assert_eq!(
+            contract.nft_token("0".to_string()).unwrap().external_link,
+            Some("https://example.com/artists/nft".to_string())
+        );
+
+        contract.nft_mint(
+            "1".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+        let creator = ValidAccountId::try_from(nft()).unwrap();
+        assert_eq!(contract.nft_supply_for_creator(creator.clone()), U128(2));
+        contract.nft_transfer(
+            ValidAccountId::try_from(bob()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+        let tokens = contract.nft_tokens_by_creator(creator.clone(), None, None);
+        assert_eq!(tokens.len(), 2);
+
+        testing_env!(get_context(bob(), 0));
+        contract.nft_burn("0".to_string());
+        testing_env!(context);
+        let tokens = contract.nft_tokens_by_creator(creator, None, None);
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].token_id, "1");
+    }
+}
@@ -0,0 +1,175 @@
+use crate::*;
+use near_sdk::json_types::{U128, U64};
+use std::collections::HashMap;
+
+#[near_bindgen]
+impl NftContract {
+    /// Number of existing tokens with each value of `trait_type`, for rarity tools.
+    pub fn trait_counts(&self, trait_type: String) -> HashMap<String, U64> {
+        self.trait_counts
+            .get(&trait_type)
+            .map_or_else(HashMap::new, |counts| {
+                counts
+                    .iter()
+                    .map(|(value, count)| (value, U64(count)))
+                    .collect()
+            })
+    }
+
+    /// Page of the existing tokens whose `trait_type` attribute is `value`.
+    pub fn tokens_with_trait(
+        &self,
+        trait_type: String,
+        value: String,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<JsonToken> {
+        self.nft_tokens_filtered(
+            TokenFilter::Attribute { trait_type, value },
+            from_index,
+            limit,
+        )
+    }
+}
+
+impl NftContract {
+    /// Counts the attributes of a token as it is minted, or revealed if it was minted
+    /// unrevealed. An attribute repeated in `metadata` counts once.
+    pub(crate) fn internal_add_token_traits(
+        &mut self,
+        token_id: &TokenId,
+        metadata: &TokenMetadata,
+    ) {
+        for attribute in metadata.attributes.iter().flatten() {
+            let key = (attribute.trait_type.clone(), attribute.value.clone());
+            if !self.internal_filter_add(&attribute_filter(attribute), token_id) {
+                continue;
+            }
+
+            let mut counts = self.trait_counts.get(&key.0).unwrap_or_else(|| {
+                let mut trait_type_hash = CryptoHash::default();
+                trait_type_hash.copy_from_slice(&env::sha256(key.0.as_bytes()));
+                UnorderedMap::new(
+                    StorageKey::TraitCountsInner { trait_type_hash }
+                        .try_to_vec()
+                        .unwrap(),
+                )
+            });
+            let count = counts.get(&key.1).unwrap_or(0);
+            counts.insert(&key.1, &math::add_u64(count, 1, "Trait count"));
+            self.trait_counts.insert(&key.0, &counts);
+        }
+    }
+
+    pub(crate) fn internal_remove_token_traits(
+        &mut self,
+        token_id: &TokenId,
+        metadata: &TokenMetadata,
+    ) {
+        for attribute in metadata.attributes.iter().flatten() {
+            let key = (attribute.trait_type.clone(), attribute.value.clone());
+            if !self.internal_filter_remove(&attribute_filter(attribute), token_id) {
+                continue;
+            }
+
+            let mut counts = self.trait_counts.get(&key.0).unwrap();
+            let count = counts.get(&key.1).unwrap_or(0);
+            if count > 1 {
+                counts.insert(&key.1, &(count - 1));
+            } else {
+                counts.remove(&key.1);
+            }
+            if counts.is_empty() {
+                self.trait_counts.remove(&key.0);
+            } else {
+                self.trait_counts.insert(&key.0, &counts);
+            }
+        }
+    }
+}
+
+fn attribute_filter(attribute: &Attribute) -> TokenFilter {
+    TokenFilter::Attribute {
+        trait_type: attribute.trait_type.clone(),
+        value: attribute.value.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U128;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn mint_with_attributes() {
+        let context = get_context(nft(), 10u128.pow(24));
+        testing_env!(context);
+        let mut contract = NftContract::default();
+        let attributes = vec![Attribute {
+            trait_type: "Background".to_string(),
+            value: "Blue".to_string(),
+            display_type: None,
+        }];
+        let mut metadata = helper_token_metadata();
+        metadata.attributes = Some(attributes.clone());
+        contract.nft_mint("0".to_string(), metadata, None, None, None, None);
+
+        let token = contract.nft_token("0".to_string()).unwrap();
+        assert_eq!(token.metadata.attributes, Some(attributes));
+
+        let metadata: TokenMetadata =
+            near_sdk::serde_json::from_str(r#"{"title":"Mochi Rising","extra":"{}"}"#).unwrap();
+        assert_eq!(metadata.attributes, None);
+    }
+
+    #[test]
+    fn trait_rarity_index() {
+        let context = get_context(nft(), 10u128.pow(24));
+        testing_env!(context);
+        let mut contract = NftContract::default();
+        for (token_id, background) in [("0", "Blue"), ("1", "Blue"), ("2", "Red")].iter() {
+            let mut metadata = helper_token_metadata();
+            metadata.attributes = Some(vec![Attribute {
+                trait_type: "Background".to_string(),
+                value: background.to_string(),
+                display_type: None,
+            }]);
+            contract.nft_mint(token_id.to_string(), metadata, None, None, None, None);
+        }
+        contract.nft_burn("2".to_string());
+
+        let counts = contract.trait_counts("Background".to_string());
+        assert_eq!(counts.len(), 1);
+        assert_eq!(counts["Blue"], U64(2));
+        let tokens =
+            contract.tokens_with_trait("Background".to_string(), "Blue".to_string(), None, None);
+        assert_eq!(tokens.len(), 2);
+        assert!(contract
+            .tokens_with_trait("Background".to_string(), "Red".to_string(), None, None)
+            .is_empty());
+    }
+
+    #[test]
+    fn traits_indexed_at_reveal() {
+        testing_env!(get_context(owner(), 10u128.pow(24)));
+        let mut contract = NftContract::default();
+        contract.set_placeholder_metadata(Some(helper_token_metadata()));
+        let mut metadata = helper_token_metadata();
+        metadata.attributes = Some(vec![Attribute {
+            trait_type: "Background".to_string(),
+            value: "Gold".to_string(),
+            display_type: None,
+        }]);
+        contract.nft_mint("0".to_string(), metadata, None, None, None, None);
+        assert!(contract.trait_counts("Background".to_string()).is_empty());
+
+        contract.reveal(U128(0), 10);
+        assert_eq!(
+            contract.trait_counts("Background".to_string())["Gold"],
+            U64(1)
+        );
+    }
+}
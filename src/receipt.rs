@@ -0,0 +1,51 @@
+use crate::*;
+use near_sdk::json_types::U128;
+use near_sdk::BlockHeight;
+
+/// Proof of a mint returned to the caller and logged, so purchasers can back support requests.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MintReceipt {
+    pub token_id: TokenId,
+    pub owner_id: AccountId,
+    pub block_height: BlockHeight,
+    pub storage_used: U64, // bytes added to the contract storage by the mint
+    pub storage_charged: U128, // cost of `storage_used` in yoctoNEAR
+    pub fee_paid: U128,    // mint price paid in yoctoNEAR, zero for free mints
+    pub event_id: U64,     // id of the `nft_mint` event, see `events_since`
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::test_utils::get_logs;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn mint_returns_receipt() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(context);
+        let receipt = contract.nft_mint(
+            "1".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+        assert_eq!(receipt.token_id, "1");
+        assert_eq!(receipt.owner_id, nft());
+        assert_eq!(receipt.event_id.0, 1);
+        assert!(receipt.storage_used.0 > 0);
+        assert_eq!(
+            receipt.storage_charged.0,
+            Balance::from(receipt.storage_used.0) * env::storage_byte_cost()
+        );
+        assert_eq!(
+            get_logs()[0],
+            r#"EVENT_JSON:{"standard":"nep171","version":"1.1.0","event":"nft_mint","data":[{"owner_id":"nft.near","token_ids":["1"]}]}"#
+        );
+    }
+}
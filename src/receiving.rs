@@ -0,0 +1,123 @@
+use crate::*;
+
+/// Number of senders an account can put in its allowlist.
+pub const MAX_ALLOWED_SENDERS: usize = 50;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum ReceivingPolicy {
+    Open,                      // any account can send tokens
+    Allowlist(Vec<AccountId>), // only the listed senders can send tokens
+    Closed,                    // nobody can send tokens
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// Sets which accounts can send tokens to the caller. The deposit covers the storage of
+    /// the policy, and going back to `Open` refunds it.
+    #[payable]
+    pub fn set_receiving_policy(&mut self, policy: ReceivingPolicy) {
+        self.assert_not_paused();
+        let account_id = env::predecessor_account_id();
+        let initial_storage_usage = env::storage_usage();
+        match &policy {
+            ReceivingPolicy::Open => {
+                self.receiving_policies.remove(&account_id);
+            }
+            ReceivingPolicy::Allowlist(senders) => {
+                assert!(
+                    senders.len() <= MAX_ALLOWED_SENDERS,
+                    "Cannot allow more than {} senders",
+                    MAX_ALLOWED_SENDERS
+                );
+                self.receiving_policies.insert(&account_id, &policy);
+            }
+            ReceivingPolicy::Closed => {
+                self.receiving_policies.insert(&account_id, &policy);
+            }
+        }
+        self.internal_charge_storage(initial_storage_usage);
+    }
+
+    pub fn receiving_policy(&self, account_id: ValidAccountId) -> ReceivingPolicy {
+        self.receiving_policies
+            .get(account_id.as_ref())
+            .unwrap_or(ReceivingPolicy::Open)
+    }
+
+    pub(crate) fn assert_can_receive(&self, sender_id: &AccountId, receiver_id: &AccountId) {
+        let allowed = match self.receiving_policies.get(receiver_id) {
+            None | Some(ReceivingPolicy::Open) => true,
+            Some(ReceivingPolicy::Allowlist(senders)) => senders.contains(sender_id),
+            Some(ReceivingPolicy::Closed) => false,
+        };
+        assert!(
+            allowed,
+            "Receiver does not accept tokens from {}",
+            sender_id
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn transfer_to_allowlisted_sender() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(bob(), 10u128.pow(22)));
+        contract.set_receiving_policy(ReceivingPolicy::Allowlist(vec![nft()]));
+        testing_env!(get_context(nft(), 10u128.pow(24)));
+        contract.nft_transfer(
+            ValidAccountId::try_from(bob()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+        assert_eq!(contract.nft_token("0".to_string()).unwrap().owner_id, bob());
+    }
+
+    #[test]
+    #[should_panic(expected = "Receiver does not accept tokens from nft.near")]
+    fn transfer_to_closed_receiver() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(bob(), 10u128.pow(22)));
+        contract.set_receiving_policy(ReceivingPolicy::Closed);
+        testing_env!(get_context(nft(), 0));
+        contract.nft_transfer(
+            ValidAccountId::try_from(bob()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Receiver does not accept tokens from nft.near")]
+    fn mint_to_closed_receiver() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(get_context(bob(), 10u128.pow(22)));
+        contract.set_receiving_policy(ReceivingPolicy::Closed);
+        testing_env!(context);
+        contract.nft_mint(
+            "1".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            Some(ValidAccountId::try_from(bob()).unwrap()),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "to cover storage")]
+    fn close_without_storage_deposit() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(bob(), 0));
+        contract.set_receiving_policy(ReceivingPolicy::Closed);
+    }
+}
@@ -0,0 +1,304 @@
+use crate::*;
+
+/// Shortest delay an owner can give their guardian, in nanoseconds (1 day).
+pub const MIN_RECOVERY_DELAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+/// Longest delay an owner can give their guardian, in nanoseconds (365 days).
+pub const MAX_RECOVERY_DELAY: u64 = 365 * MIN_RECOVERY_DELAY;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Guardian {
+    pub guardian_id: AccountId,
+    pub delay: U64, // time the owner has to cancel a recovery, in nanoseconds
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Recovery {
+    pub new_owner_id: AccountId,
+    pub started_at: U64, // block timestamp in nanoseconds
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// Lets `guardian_id` move the caller's tokens to a new account, `delay` after announcing it.
+    /// The deposit covers the storage of the guardian, refunded by `remove_guardian`.
+    #[payable]
+    pub fn set_guardian(&mut self, guardian_id: ValidAccountId, delay: U64) {
+        self.assert_not_paused();
+        let account_id = env::predecessor_account_id();
+        assert_ne!(
+            &account_id,
+            guardian_id.as_ref(),
+            "Cannot be your own guardian"
+        );
+        assert!(
+            (MIN_RECOVERY_DELAY..=MAX_RECOVERY_DELAY).contains(&delay.0),
+            "Delay must be between {} and {} nanoseconds",
+            MIN_RECOVERY_DELAY,
+            MAX_RECOVERY_DELAY
+        );
+
+        self.internal_remove_recovery(&account_id);
+        let initial_storage_usage = env::storage_usage();
+        self.guardians.insert(
+            &account_id,
+            &Guardian {
+                guardian_id: guardian_id.into(),
+                delay,
+            },
+        );
+        self.internal_charge_storage(initial_storage_usage);
+    }
+
+    /// Removes the caller's guardian, cancelling any pending recovery.
+    pub fn remove_guardian(&mut self) {
+        self.assert_not_sunset();
+        let account_id = env::predecessor_account_id();
+        self.internal_remove_recovery(&account_id);
+        let initial_storage_usage = env::storage_usage();
+        self.guardians.remove(&account_id);
+        let freed = initial_storage_usage.saturating_sub(env::storage_usage());
+        self.internal_refund_storage(&account_id, freed);
+    }
+
+    pub fn guardian_of(&self, account_id: ValidAccountId) -> Option<Guardian> {
+        self.guardians.get(account_id.as_ref())
+    }
+
+    pub fn pending_recovery(&self, account_id: ValidAccountId) -> Option<Recovery> {
+        self.recoveries.get(account_id.as_ref())
+    }
+
+    /// Announces the recovery of `account_id` tokens to `new_owner_id`, opening the challenge window.
+    /// The deposit covers the storage of the recovery, refunded to the guardian once it is over.
+    #[payable]
+    pub fn recovery_start(&mut self, account_id: ValidAccountId, new_owner_id: ValidAccountId) {
+        self.assert_not_paused();
+        let guardian = self.assert_guardian(account_id.as_ref());
+        assert_ne!(
+            account_id, new_owner_id,
+            "New owner must be a different account"
+        );
+        assert!(
+            self.recoveries.get(account_id.as_ref()).is_none(),
+            "Recovery is already pending"
+        );
+
+        log!(
+            "Recovery of @{} to @{} started by @{}, executable after {} ns",
+            account_id,
+            new_owner_id,
+            guardian.guardian_id,
+            guardian.delay.0
+        );
+
+        let initial_storage_usage = env::storage_usage();
+        self.recoveries.insert(
+            account_id.as_ref(),
+            &Recovery {
+                new_owner_id: new_owner_id.into(),
+                started_at: env::block_timestamp().into(),
+            },
+        );
+        self.internal_charge_storage(initial_storage_usage);
+    }
+
+    /// Challenges a pending recovery of the caller's tokens.
+    pub fn recovery_cancel(&mut self) {
+        self.assert_not_sunset();
+        let account_id = env::predecessor_account_id();
+        assert!(
+            self.internal_remove_recovery(&account_id).is_some(),
+            "No pending recovery"
+        );
+
+        log!("Recovery of @{} cancelled", account_id);
+    }
+
+    /// Moves up to `limit` tokens of `account_id` to the new owner once the delay is over.
+    /// Locked tokens, ex. staked, leased or bridged, are skipped and stay with the account.
+    /// Returns the number of tokens the account still holds, or zero once the recovery
+    /// completes, which is when only locked tokens are left.
+    pub fn recovery_finish(&mut self, account_id: ValidAccountId, limit: Option<u64>) -> U64 {
+        self.assert_not_paused();
+        let guardian = self.assert_guardian(account_id.as_ref());
+        let recovery = self
+            .recoveries
+            .get(account_id.as_ref())
+            .expect("No pending recovery");
+        assert!(
+            env::block_timestamp()
+                >= math::add_u64(recovery.started_at.0, guardian.delay.0, "Deadline"),
+            "Challenge window is not over"
+        );
+
+        let limit = limit.map_or(usize::MAX, |limit| limit as usize);
+        let token_ids: Vec<TokenId> =
+            self.tokens_per_owner
+                .get(account_id.as_ref())
+                .map_or(vec![], |tokens| {
+                    tokens
+                        .iter()
+                        .map(|(_, token_id)| token_id)
+                        .filter(|token_id| !self.internal_is_locked(token_id))
+                        .take(limit)
+                        .collect()
+                });
+        let done = token_ids.len() < limit;
+        for token_id in token_ids.iter() {
+            let token = self.tokens_by_id.get(token_id).unwrap();
+            self.internal_transfer(
+                token,
+                &recovery.new_owner_id,
+                Some(env::predecessor_account_id()),
+                None,
+            );
+            self.internal_record_activity(token_id, ActivityKind::Recover);
+        }
+
+        if done {
+            log!("Recovery of @{} finished", account_id);
+            self.internal_remove_recovery(account_id.as_ref());
+            return 0.into();
+        }
+        self.tokens_per_owner
+            .get(account_id.as_ref())
+            .map_or(0, |tokens| tokens.len())
+            .into()
+    }
+
+    fn assert_guardian(&self, account_id: &AccountId) -> Guardian {
+        let guardian = self.guardians.get(account_id).expect("No guardian set");
+        if env::predecessor_account_id() != guardian.guardian_id {
+            env::panic(b"Unauthorized");
+        }
+        guardian
+    }
+}
+
+impl NftContract {
+    /// Removes the pending recovery of `account_id`, if any, refunding its storage to the
+    /// guardian who started it.
+    pub(crate) fn internal_remove_recovery(&mut self, account_id: &AccountId) -> Option<Recovery> {
+        let initial_storage_usage = env::storage_usage();
+        let recovery = self.recoveries.remove(account_id)?;
+        let freed = initial_storage_usage.saturating_sub(env::storage_usage());
+        if let Some(guardian) = self.guardians.get(account_id) {
+            self.internal_refund_storage(&guardian.guardian_id, freed);
+        }
+        Some(recovery)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U128;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn guardian_recovery_after_delay() {
+        let (mut contract, _) = helper_mint();
+        contract.set_guardian(
+            ValidAccountId::try_from(bob()).unwrap(),
+            U64(MIN_RECOVERY_DELAY),
+        );
+        testing_env!(get_context(bob(), 10u128.pow(22)));
+        contract.recovery_start(
+            ValidAccountId::try_from(nft()).unwrap(),
+            ValidAccountId::try_from(owner()).unwrap(),
+        );
+
+        let mut context = get_context(bob(), 0);
+        context.block_timestamp = MIN_RECOVERY_DELAY;
+        testing_env!(context);
+        let remaining = contract.recovery_finish(ValidAccountId::try_from(nft()).unwrap(), None);
+        assert_eq!(remaining.0, 0);
+        assert_eq!(
+            contract.nft_token("0".to_string()).unwrap().owner_id,
+            owner()
+        );
+        assert!(contract
+            .pending_recovery(ValidAccountId::try_from(nft()).unwrap())
+            .is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "No pending recovery")]
+    fn guardian_recovery_cancelled_by_owner() {
+        let (mut contract, context) = helper_mint();
+        contract.set_guardian(
+            ValidAccountId::try_from(bob()).unwrap(),
+            U64(MIN_RECOVERY_DELAY),
+        );
+        testing_env!(get_context(bob(), 10u128.pow(22)));
+        contract.recovery_start(
+            ValidAccountId::try_from(nft()).unwrap(),
+            ValidAccountId::try_from(owner()).unwrap(),
+        );
+        testing_env!(context);
+        contract.recovery_cancel();
+
+        let mut context = get_context(bob(), 0);
+        context.block_timestamp = MIN_RECOVERY_DELAY;
+        testing_env!(context);
+        contract.recovery_finish(ValidAccountId::try_from(nft()).unwrap(), None);
+    }
+
+    #[test]
+    fn recovery_skips_locked_tokens() {
+        let (mut contract, _) = helper_mint();
+        contract.nft_mint(
+            "1".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.swap_propose(
+            "0".to_string(),
+            ValidAccountId::try_from(nft()).unwrap(),
+            "1".to_string(),
+            U64(MIN_RECOVERY_DELAY * 2),
+            U128(0),
+        );
+        contract.set_guardian(
+            ValidAccountId::try_from(bob()).unwrap(),
+            U64(MIN_RECOVERY_DELAY),
+        );
+        testing_env!(get_context(bob(), 10u128.pow(22)));
+        contract.recovery_start(
+            ValidAccountId::try_from(nft()).unwrap(),
+            ValidAccountId::try_from(owner()).unwrap(),
+        );
+
+        let mut context = get_context(bob(), 0);
+        context.block_timestamp = MIN_RECOVERY_DELAY;
+        testing_env!(context);
+        let remaining = contract.recovery_finish(ValidAccountId::try_from(nft()).unwrap(), None);
+        assert_eq!(remaining.0, 0);
+        assert_eq!(contract.nft_token("0".to_string()).unwrap().owner_id, nft());
+        assert_eq!(
+            contract.nft_token("1".to_string()).unwrap().owner_id,
+            owner()
+        );
+        assert!(contract
+            .pending_recovery(ValidAccountId::try_from(nft()).unwrap())
+            .is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Delay must be between")]
+    fn guardian_delay_above_maximum() {
+        let (mut contract, _) = helper_mint();
+        contract.set_guardian(
+            ValidAccountId::try_from(bob()).unwrap(),
+            U64(MAX_RECOVERY_DELAY + 1),
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use crate::*;
+use near_sdk::json_types::U128;
+use near_sdk::Promise;
+
+#[near_bindgen]
+impl NftContract {
+    /// Share of mint and sale payments in NEAR forwarded to the referrer, in basis points.
+    pub fn set_referral_fee(&mut self, referral_fee: u32) {
+        self.assert_admin();
+        assert!(
+            referral_fee <= ROYALTY_DENOMINATOR,
+            "Referral fee cannot exceed {}",
+            ROYALTY_DENOMINATOR
+        );
+        self.referral_fee = referral_fee;
+    }
+
+    pub fn referral_fee(&self) -> u32 {
+        self.referral_fee
+    }
+
+    /// Total referral fees paid to `account_id`, in yoctoNEAR.
+    pub fn referral_earnings(&self, account_id: ValidAccountId) -> U128 {
+        U128(self.referral_earnings.get(account_id.as_ref()).unwrap_or(0))
+    }
+
+    /// Forwards the referral cut of `payment` to `referrer_id`, returning the amount paid.
+    pub(crate) fn internal_pay_referral(
+        &mut self,
+        referrer_id: Option<ValidAccountId>,
+        payment: Balance,
+    ) -> Balance {
+        let referrer_id: AccountId = match referrer_id {
+            Some(referrer_id) => referrer_id.into(),
+            None => return 0,
+        };
+        assert_ne!(
+            referrer_id,
+            env::predecessor_account_id(),
+            "Cannot refer yourself"
+        );
+
+        let cut = math::mul_div(
+            payment,
+            Balance::from(self.referral_fee),
+            Balance::from(ROYALTY_DENOMINATOR),
+        );
+        if cut == 0 {
+            return 0;
+        }
+        let earnings = self.referral_earnings.get(&referrer_id).unwrap_or(0);
+        self.referral_earnings
+            .insert(&referrer_id, &(earnings + cut));
+        log!("Referral fee of {} to @{}", cut, referrer_id);
+        Promise::new(referrer_id).transfer(cut);
+        cut
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U128;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn referral_cut_of_mint_price() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = NftContract::default();
+        contract.set_mint_price(U128(1000));
+        contract.set_referral_fee(500);
+
+        testing_env!(get_context(nft(), 10u128.pow(24)));
+        contract.nft_mint(
+            "0".to_string(),
+            helper_token_metadata(),
+            None,
+            Some(ValidAccountId::try_from(bob()).unwrap()),
+            None,
+            None,
+        );
+        assert_eq!(
+            contract.referral_earnings(ValidAccountId::try_from(bob()).unwrap()),
+            U128(50)
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use crate::*;
+
+/// Replacement proposed by the admin for a defective token, pending the holder's confirmation.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ReissueProposal {
+    pub new_token_id: TokenId,
+    pub metadata: TokenMetadata,
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// First step of a metadata correction, the holder completes it with `reissue`.
+    pub fn propose_reissue(
+        &mut self,
+        token_id: TokenId,
+        new_token_id: TokenId,
+        corrected_metadata: TokenMetadata,
+    ) {
+        self.assert_admin();
+        self.assert_valid_token_id(&new_token_id);
+        assert!(
+            self.tokens_by_id.get(&token_id).is_some(),
+            "Token not found"
+        );
+        assert!(
+            self.tokens_by_id.get(&new_token_id).is_none(),
+            "Token already exists"
+        );
+
+        self.reissue_proposals.insert(
+            &token_id,
+            &ReissueProposal {
+                new_token_id,
+                metadata: corrected_metadata,
+            },
+        );
+    }
+
+    pub fn cancel_reissue(&mut self, token_id: TokenId) {
+        self.assert_admin();
+        assert!(
+            self.reissue_proposals.remove(&token_id).is_some(),
+            "No reissue proposed"
+        );
+    }
+
+    pub fn reissue_proposal(&self, token_id: TokenId) -> Option<ReissueProposal> {
+        self.reissue_proposals.get(&token_id)
+    }
+
+    /// Burns `token_id` and mints the proposed replacement to its holder, who must confirm the
+    /// exact `corrected_metadata`. Returns the id of the replacement.
+    pub fn reissue(&mut self, token_id: TokenId, corrected_metadata: TokenMetadata) -> TokenId {
+        self.assert_not_paused();
+        let token = self.tokens_by_id.get(&token_id).expect("Token not found");
+
+        if env::predecessor_account_id() != token.owner_id {
+            env::panic(b"Unauthorized");
+        }
+
+        let proposal = self
+            .reissue_proposals
+            .remove(&token_id)
+            .expect("No reissue proposed");
+        assert!(
+            proposal.metadata == corrected_metadata,
+            "Metadata does not match the proposal"
+        );
+
+        log!("Reissue {} as {}", token_id, proposal.new_token_id);
+
+        self.internal_burn(&token);
+        let replacement = Token {
+            token_id: proposal.new_token_id,
+            owner_id: token.owner_id,
+            frozen: token.frozen,
+            revealed: true,
+            creator_id: token.creator_id,
+            license: token.license,
+            reissued_from: Some(token_id.clone()),
+            royalty: token.royalty,
+            approved_account_ids: HashMap::new(),
+            approvals_expire_at: HashMap::new(),
+            next_approval_id: 0,
+            transferable: token.transferable,
+            mint_sequence: self.minted_count,
+            series_id: token.series_id,
+            collection_id: token.collection_id,
+        };
+        self.internal_add_token(&replacement, &corrected_metadata);
+        self.internal_record_activity(&replacement.token_id, ActivityKind::Reissue);
+
+        let memo = Some(format!("reissue of {}", token_id));
+        self.internal_emit(NftEvent::NftBurn(vec![NftBurnLog {
+            owner_id: replacement.owner_id.clone(),
+            token_ids: vec![token_id],
+            memo: memo.clone(),
+        }]));
+        self.internal_emit(NftEvent::NftMint(vec![NftMintLog {
+            owner_id: replacement.owner_id.clone(),
+            token_ids: vec![replacement.token_id.clone()],
+            memo,
+        }]));
+        replacement.token_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U128;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn reissue_with_corrected_metadata() {
+        let (mut contract, context) = helper_mint();
+        let mut corrected = helper_token_metadata();
+        corrected.title = Some("Mochi Rising (corrected)".to_string());
+        testing_env!(get_context(owner(), 0));
+        contract.propose_reissue("0".to_string(), "0-r1".to_string(), corrected.clone());
+
+        testing_env!(context);
+        let new_token_id = contract.reissue("0".to_string(), corrected);
+        assert_eq!(new_token_id, "0-r1");
+        assert!(contract.nft_token("0".to_string()).is_none());
+        let token = contract.nft_token(new_token_id).unwrap();
+        assert_eq!(token.owner_id, nft());
+        assert_eq!(token.reissued_from, Some("0".to_string()));
+        assert_eq!(
+            contract.nft_supply_for_owner(ValidAccountId::try_from(nft()).unwrap()),
+            U128(1)
+        );
+    }
+
+    #[test]
+    fn reissue_keeps_token_frozen() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(context.clone());
+        contract.nft_freeze_metadata("0".to_string());
+        let mut corrected = helper_token_metadata();
+        corrected.title = Some("Mochi Rising (corrected)".to_string());
+        testing_env!(get_context(owner(), 0));
+        contract.propose_reissue("0".to_string(), "0-r1".to_string(), corrected.clone());
+
+        testing_env!(context);
+        let new_token_id = contract.reissue("0".to_string(), corrected);
+        assert!(contract.nft_token(new_token_id).unwrap().frozen);
+    }
+
+    #[test]
+    #[should_panic(expected = "Metadata does not match the proposal")]
+    fn reissue_with_other_metadata() {
+        let (mut contract, context) = helper_mint();
+        let mut corrected = helper_token_metadata();
+        corrected.title = Some("Mochi Rising (corrected)".to_string());
+        testing_env!(get_context(owner(), 0));
+        contract.propose_reissue("0".to_string(), "0-r1".to_string(), corrected);
+
+        testing_env!(context);
+        contract.reissue("0".to_string(), helper_token_metadata());
+    }
+}
@@ -0,0 +1,204 @@
+use crate::*;
+use near_sdk::json_types::U128;
+
+#[near_bindgen]
+impl NftContract {
+    /// While set, newly minted tokens are unrevealed and shown with this metadata
+    /// until the owner reveals them. It cannot change while any token is unrevealed.
+    pub fn set_placeholder_metadata(&mut self, metadata: Option<TokenMetadata>) {
+        self.assert_admin();
+        assert!(
+            self.pending_reveal.is_empty(),
+            "Cannot change the placeholder metadata while tokens are unrevealed"
+        );
+        if let Some(metadata) = &metadata {
+            metadata.assert_valid();
+            self.assert_metadata_within_limits(metadata);
+        }
+        self.placeholder_metadata = metadata;
+        // Shown by every unrevealed token, too many to list.
+        self.internal_emit_contract_metadata_update(Some("placeholder metadata".to_string()));
+    }
+
+    pub fn nft_placeholder_metadata(&self) -> Option<TokenMetadata> {
+        self.placeholder_metadata.clone()
+    }
+
+    /// Reveals a batch of the tokens still shown with the placeholder metadata. Revealed
+    /// tokens leave the pending ones, so repeated calls from index 0 reveal them all.
+    pub fn reveal(&mut self, from_index: U128, limit: u64) {
+        self.assert_admin();
+
+        let token_ids: Vec<TokenId> = self
+            .pending_reveal
+            .iter()
+            .skip(from_index.0 as usize)
+            .take(limit as usize)
+            .collect();
+
+        let mut revealed = Vec::new();
+        for token_id in token_ids {
+            self.pending_reveal.remove(&token_id);
+            let mut token = self.tokens_by_id.get(&token_id).unwrap();
+            token.revealed = true;
+            self.tokens_by_id.insert(&token_id, &token);
+            let metadata = self.token_metadata_by_id.get(&token_id).unwrap();
+            self.internal_add_token_traits(&token_id, &metadata);
+            self.internal_record_activity(&token_id, ActivityKind::Reveal);
+            revealed.push(token_id);
+        }
+
+        if !revealed.is_empty() {
+            self.internal_emit_metadata_update(revealed.clone(), "reveal");
+            self.internal_emit_extension(NftEvent::NftReveal(vec![NftRevealLog {
+                token_ids: revealed,
+            }]));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::get_logs;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn reveal_series_editions() {
+        testing_env!(get_context(owner(), 10u128.pow(24)));
+        let mut contract = NftContract::default();
+        let mut placeholder = helper_token_metadata();
+        placeholder.title = Some("Mystery box".to_string());
+        contract.set_placeholder_metadata(Some(placeholder));
+        let series_id =
+            contract.create_series(helper_token_metadata(), Some(U128(0)), None, None, None);
+        contract.nft_mint_series(series_id.clone(), None);
+        contract.nft_mint_series(series_id.clone(), None);
+        assert_eq!(
+            contract
+                .nft_token("1:1".to_string())
+                .unwrap()
+                .metadata
+                .title,
+            Some("Mystery box".to_string())
+        );
+
+        let reveal = || SeriesReveal::BaseUri {
+            base_uri: "https://example.com/drop/".to_string(),
+            reference_hashes: vec![
+                Base64VecU8(env::sha256(b"1.json")),
+                Base64VecU8(env::sha256(b"2.json")),
+            ],
+        };
+        contract.reveal_series(series_id.clone(), reveal(), U128(1), 10);
+        assert!(!contract.nft_token("1:1".to_string()).unwrap().revealed);
+        contract.reveal_series(series_id, reveal(), U128(0), 1);
+        assert!(contract.pending_reveal.is_empty());
+        let token = contract.nft_token("1:2".to_string()).unwrap();
+        assert!(token.revealed);
+        assert_eq!(token.metadata.title, Some("Mochi Rising #2".to_string()));
+        assert_eq!(
+            token.metadata.reference,
+            Some("https://example.com/drop/2.json".to_string())
+        );
+        assert_eq!(
+            token.metadata.reference_hash,
+            Some(Base64VecU8(env::sha256(b"2.json")))
+        );
+        assert_eq!(
+            contract.nft_copies_minted("https://example.com/drop/2.json".to_string()),
+            U64(1)
+        );
+        let update = get_logs()
+            .into_iter()
+            .filter(|log| log.contains("nft_metadata_update"))
+            .count();
+        assert_eq!(update, 2);
+    }
+
+    #[test]
+    fn commit_provenance_hash_before_mint() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = NftContract::default();
+        let provenance_hash = Base64VecU8(env::sha256(b"metadata"));
+        contract.commit_provenance_hash(provenance_hash.clone());
+        assert_eq!(contract.nft_provenance_hash(), Some(provenance_hash));
+    }
+
+    #[test]
+    #[should_panic(expected = "Minting has already started")]
+    fn commit_provenance_hash_after_mint() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(owner(), 0));
+        contract.commit_provenance_hash(Base64VecU8(env::sha256(b"metadata")));
+    }
+
+    #[test]
+    fn delayed_reveal() {
+        testing_env!(get_context(owner(), 10u128.pow(24)));
+        let mut contract = NftContract::default();
+        let mut placeholder = helper_token_metadata();
+        placeholder.title = Some("Mystery box".to_string());
+        contract.set_placeholder_metadata(Some(placeholder));
+        contract.nft_mint(
+            "0".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.nft_mint(
+            "1".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        let title = |contract: &NftContract, token_id: &str| {
+            let token = contract.nft_token(token_id.to_string()).unwrap();
+            token.metadata.title.unwrap()
+        };
+        assert_eq!(title(&contract, "0"), "Mystery box");
+
+        testing_env!(get_context(owner(), 0));
+        contract.reveal(U128(0), 1);
+        assert_eq!(title(&contract, "0"), "Mochi Rising");
+        assert_eq!(title(&contract, "1"), "Mystery box");
+        assert_eq!(contract.pending_reveal.len(), 1);
+        let logs = get_logs();
+        assert_eq!(
+            logs[0],
+            r#"EVENT_JSON:{"standard":"nep171","version":"1.1.0","event":"nft_metadata_update","data":[{"token_ids":["0"],"memo":"reveal"}]}"#
+        );
+        #[cfg(feature = "verbose-events")]
+        assert_eq!(
+            logs[1..],
+            [
+                r#"EVENT_JSON:{"standard":"nft-demo","version":"1.0.0","event":"nft_reveal","data":[{"token_ids":["0"]}]}"#
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot change the placeholder metadata while tokens are unrevealed")]
+    fn unset_placeholder_before_reveal() {
+        testing_env!(get_context(owner(), 10u128.pow(24)));
+        let mut contract = NftContract::default();
+        contract.set_placeholder_metadata(Some(helper_token_metadata()));
+        contract.nft_mint(
+            "0".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+        contract.set_placeholder_metadata(None);
+    }
+}
@@ -0,0 +1,260 @@
+use crate::*;
+use near_sdk::json_types::U128;
+#[cfg(feature = "marketplace")]
+use near_sdk::Promise;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+/// Royalty shares are in basis points of the sale price.
+pub const ROYALTY_DENOMINATOR: u32 = 10_000;
+
+/// Number of royalty payees a token can have.
+pub const MAX_ROYALTY_PAYEES: usize = 10;
+
+/// Payee accounts with their share of a sale, in basis points.
+pub type Royalty = HashMap<AccountId, u32>;
+
+/// Amounts to pay out of a sale, following NEP-199.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Payout {
+    pub payout: HashMap<AccountId, U128>,
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// Caps the total royalty of tokens minted from now on, in basis points.
+    pub fn set_max_royalty(&mut self, max_royalty: u32) {
+        self.assert_admin();
+        assert!(
+            max_royalty <= ROYALTY_DENOMINATOR,
+            "Max royalty cannot exceed {}",
+            ROYALTY_DENOMINATOR
+        );
+        self.max_royalty = max_royalty;
+    }
+
+    pub fn max_royalty(&self) -> u32 {
+        self.max_royalty
+    }
+}
+
+#[cfg(feature = "royalties")]
+#[near_bindgen]
+impl NftContract {
+    /// How `nft_transfer_payout` would split a sale of `token_id` for `balance` settled by a
+    /// marketplace that is not exempt from the transfer fee.
+    pub fn nft_payout(&self, token_id: TokenId, balance: U128, max_len_payout: u32) -> Payout {
+        let token = self.tokens_by_id.get(&token_id).expect("Token not found");
+        self.internal_sale_payout(&token, balance.0, None, max_len_payout)
+    }
+
+    /// Transfers `token_id` like `nft_transfer` and returns how the marketplace must split
+    /// the sale for `balance`. Unless the caller is exempt, the transfer fee goes to the
+    /// treasury and the royalty payees and owner share the rest.
+    #[payable]
+    pub fn nft_transfer_payout(
+        &mut self,
+        receiver_id: ValidAccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        balance: U128,
+        max_len_payout: u32,
+    ) -> Payout {
+        let token = self.tokens_by_id.get(&token_id).expect("Token not found");
+        let payout = self.internal_sale_payout(
+            &token,
+            balance.0,
+            Some(&env::predecessor_account_id()),
+            max_len_payout,
+        );
+        self.nft_transfer(receiver_id, token_id, approval_id);
+        payout
+    }
+}
+
+#[cfg(feature = "royalties")]
+impl NftContract {
+    /// Splits a sale of `token` for `balance` settled by `marketplace_id` between the
+    /// treasury, the royalty payees and the owner.
+    fn internal_sale_payout(
+        &self,
+        token: &Token,
+        balance: Balance,
+        marketplace_id: Option<&AccountId>,
+        max_len_payout: u32,
+    ) -> Payout {
+        let mut payout = HashMap::new();
+        let mut balance = balance;
+        if let Some((treasury_id, fee)) = self.internal_transfer_fee(balance, marketplace_id) {
+            payout.insert(treasury_id, fee);
+            balance -= fee;
+        }
+        for (account_id, amount) in self.internal_payout(token, balance) {
+            *payout.entry(account_id).or_insert(0) += amount;
+        }
+        assert!(
+            payout.len() <= max_len_payout as usize,
+            "Cannot pay out to more than {} accounts",
+            max_len_payout
+        );
+        Payout {
+            payout: payout
+                .into_iter()
+                .map(|(account_id, amount)| (account_id, U128(amount)))
+                .collect(),
+        }
+    }
+}
+
+impl NftContract {
+    pub(crate) fn assert_valid_royalty(&self, royalty: &Royalty) {
+        assert!(
+            royalty.len() <= MAX_ROYALTY_PAYEES,
+            "Cannot have more than {} royalty payees",
+            MAX_ROYALTY_PAYEES
+        );
+        for account_id in royalty.keys() {
+            assert!(
+                ValidAccountId::try_from(account_id.as_str()).is_ok(),
+                "Invalid royalty payee {}",
+                account_id
+            );
+        }
+        let total: u32 = royalty
+            .values()
+            .fold(0, |total, share| total.saturating_add(*share));
+        assert!(
+            total <= self.max_royalty,
+            "Royalty of {} exceeds the maximum of {}",
+            total,
+            self.max_royalty
+        );
+    }
+}
+
+#[cfg(any(feature = "marketplace", feature = "royalties"))]
+impl NftContract {
+    /// Splits a sale of `token` for `balance`, the owner receiving what the payees don't.
+    pub(crate) fn internal_payout(
+        &self,
+        token: &Token,
+        balance: Balance,
+    ) -> HashMap<AccountId, Balance> {
+        let mut payout = HashMap::new();
+        let mut paid = 0;
+        for (account_id, share) in self.internal_token_royalty(token) {
+            let amount = math::mul_div(
+                balance,
+                Balance::from(share),
+                Balance::from(ROYALTY_DENOMINATOR),
+            );
+            paid += amount;
+            *payout.entry(account_id).or_insert(0) += amount;
+        }
+        *payout.entry(token.owner_id.clone()).or_insert(0) += balance - paid;
+        payout
+    }
+
+    /// Sends the proceeds of a sale of `token` for `balance` to its owner and royalty payees.
+    #[cfg(feature = "marketplace")]
+    pub(crate) fn internal_pay_out(&self, token: &Token, balance: Balance) {
+        for (account_id, amount) in self.internal_payout(token, balance) {
+            if amount > 0 {
+                Promise::new(account_id).transfer(amount);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    #[cfg(feature = "royalties")]
+    use near_sdk::json_types::U128;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[cfg(feature = "royalties")]
+    #[test]
+    fn payout_with_royalty_split() {
+        testing_env!(get_context(nft(), 10u128.pow(24)));
+        let mut contract = NftContract::default();
+        let mut royalty = HashMap::new();
+        royalty.insert(bob(), 700);
+        royalty.insert(owner(), 300);
+        contract.nft_mint(
+            "0".to_string(),
+            helper_token_metadata(),
+            Some(royalty),
+            None,
+            None,
+            None,
+        );
+
+        let payout = contract.nft_payout("0".to_string(), U128(1000), 3).payout;
+        assert_eq!(payout.len(), 3);
+        assert_eq!(payout[&bob()], U128(70));
+        assert_eq!(payout[&owner()], U128(30));
+        assert_eq!(payout[&nft()], U128(900));
+    }
+
+    #[test]
+    #[should_panic(expected = "Royalty of 6000 exceeds the maximum of 5000")]
+    fn mint_with_royalty_over_cap() {
+        testing_env!(get_context(nft(), 10u128.pow(24)));
+        let mut contract = NftContract::default();
+        let mut royalty = HashMap::new();
+        royalty.insert(bob(), 6000);
+        contract.nft_mint(
+            "0".to_string(),
+            helper_token_metadata(),
+            Some(royalty),
+            None,
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid royalty payee")]
+    fn mint_with_invalid_royalty_payee() {
+        testing_env!(get_context(owner(), 10u128.pow(24)));
+        let mut contract = NftContract::default();
+        let mut royalty = Royalty::new();
+        royalty.insert("Not An Account".to_string(), 500);
+        contract.nft_mint(
+            "0".to_string(),
+            helper_token_metadata(),
+            Some(royalty),
+            None,
+            None,
+            None,
+        );
+    }
+
+    #[cfg(feature = "royalties")]
+    #[test]
+    fn payout_of_max_balance() {
+        testing_env!(get_context(owner(), 10u128.pow(24)));
+        let mut contract = NftContract::default();
+        contract.set_max_royalty(ROYALTY_DENOMINATOR);
+        let mut royalty = Royalty::new();
+        royalty.insert(bob(), 2_500);
+        contract.nft_mint(
+            "0".to_string(),
+            helper_token_metadata(),
+            Some(royalty),
+            None,
+            None,
+            None,
+        );
+
+        let payout = contract
+            .nft_payout("0".to_string(), U128(u128::MAX), 10)
+            .payout;
+        assert_eq!(payout[&bob()], U128(u128::MAX / 4));
+        assert_eq!(payout[&owner()], U128(u128::MAX - u128::MAX / 4));
+    }
+}
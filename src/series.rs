@@ -0,0 +1,498 @@
+use crate::*;
+use near_sdk::json_types::{U128, U64};
+
+/// Number of accounts a series can restrict its mints to.
+pub const MAX_SERIES_MINTERS: usize = 50;
+
+pub type SeriesId = String;
+
+/// Template shared by numbered editions, minted as `<series_id>:<edition>`.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Series {
+    pub series_id: SeriesId,
+    pub creator_id: AccountId, // creator of every edition
+    pub metadata: TokenMetadata,
+    pub price: Option<U128>,      // `None` charges the contract mint price
+    pub royalty: Option<Royalty>, // royalty of every edition, `None` for none
+    pub max_supply: Option<U64>,  // `None` for an open edition
+    pub minted: U64,              // editions ever minted, burned ones included
+    pub minters: Option<Vec<AccountId>>, // `None` lets anyone mint
+    pub base_uri: Option<String>, // overrides the contract `base_uri` for relative paths
+    pub closed: bool,             // no more editions can be minted
+}
+
+/// Series with its supply, as shown by storefronts.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SeriesDetails {
+    #[serde(flatten)]
+    pub series: Series,
+    pub supply: U64,            // editions not burned
+    pub remaining: Option<U64>, // editions left to mint, `None` for an open edition
+}
+
+/// Final metadata given to the editions of a series by `reveal_series`.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum SeriesReveal {
+    BaseUri {
+        base_uri: String,                   // edition `n` references `<base_uri>/<n>.json`
+        reference_hashes: Vec<Base64VecU8>, // hash of the reference of edition `n` at `n - 1`
+    },
+    Metadata(Box<TokenMetadata>), // replaces the series metadata
+}
+
+impl Series {
+    /// Metadata of edition number `edition`, counted from 1.
+    fn edition_metadata(&self, edition: u64) -> TokenMetadata {
+        let mut metadata = self.metadata.clone();
+        metadata.title = metadata
+            .title
+            .map(|title| format!("{} #{}", title, edition));
+        metadata.copies = self.max_supply;
+        metadata
+    }
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// Creates a series of editions sharing `metadata` and `royalty`, each sold for `price`,
+    /// or the contract mint price when not given. Up to `max_supply` editions can be minted,
+    /// only by `minters` when given. Returns its id.
+    pub fn create_series(
+        &mut self,
+        metadata: TokenMetadata,
+        price: Option<U128>,
+        royalty: Option<Royalty>,
+        max_supply: Option<U64>,
+        minters: Option<Vec<ValidAccountId>>,
+    ) -> SeriesId {
+        self.assert_admin();
+        metadata.assert_valid();
+        self.assert_metadata_within_limits(&metadata);
+        if let Some(royalty) = &royalty {
+            self.assert_valid_royalty(royalty);
+        }
+        if let Some(max_supply) = max_supply {
+            assert!(max_supply.0 > 0, "Max supply must be greater than zero");
+        }
+        if let Some(minters) = &minters {
+            assert!(
+                minters.len() <= MAX_SERIES_MINTERS,
+                "Cannot allow more than {} minters",
+                MAX_SERIES_MINTERS
+            );
+        }
+
+        let series_id = (self.series.len() + 1).to_string();
+        let series = Series {
+            series_id: series_id.clone(),
+            creator_id: env::predecessor_account_id(),
+            metadata,
+            price,
+            royalty,
+            max_supply,
+            minted: U64(0),
+            base_uri: None,
+            closed: false,
+            minters: minters.map(|minters| minters.into_iter().map(Into::into).collect()),
+        };
+        self.series.insert(&series_id, &series);
+        log!("Create series {}", series_id);
+        series_id
+    }
+
+    /// Mints the next edition of `series_id` to `receiver_id`, the caller by default.
+    /// The attached deposit pays the series price and the storage, and the rest is refunded.
+    /// Editions of series without a price are charged like `nft_mint`, drop phases and free
+    /// mints included.
+    #[payable]
+    pub fn nft_mint_series(
+        &mut self,
+        series_id: SeriesId,
+        receiver_id: Option<ValidAccountId>,
+    ) -> MintReceipt {
+        self.assert_not_paused();
+        self.assert_mint_not_ft_gated();
+        let mut series = self.series.get(&series_id).expect("Series not found");
+        let minter_id = env::predecessor_account_id();
+        if let Some(minters) = &series.minters {
+            if !minters.contains(&minter_id) {
+                env::panic(b"Unauthorized");
+            }
+        }
+        assert!(!series.closed, "Series is closed");
+        let edition = math::add_u64(series.minted.0, 1, "Series supply");
+        if let Some(max_supply) = series.max_supply {
+            assert!(edition <= max_supply.0, "Series is sold out");
+        }
+
+        let royalty = series.royalty.clone().unwrap_or_default();
+        let initial_storage_usage = env::storage_usage();
+        let price = match series.price {
+            Some(price) => price.0,
+            None => self.internal_take_mint_price(&minter_id),
+        };
+
+        let owner_id = receiver_id.map_or_else(|| minter_id.clone(), Into::into);
+        if owner_id != minter_id {
+            self.assert_can_receive(&minter_id, &owner_id);
+        }
+        series.minted = edition.into();
+        self.series.insert(&series_id, &series);
+
+        let token = Token {
+            token_id: format!("{}:{}", series_id, edition),
+            owner_id,
+            frozen: false,
+            revealed: self.placeholder_metadata.is_none(),
+            creator_id: series.creator_id.clone(),
+            license: None,
+            reissued_from: None,
+            royalty,
+            approved_account_ids: HashMap::new(),
+            approvals_expire_at: HashMap::new(),
+            next_approval_id: 0,
+            transferable: true,
+            mint_sequence: self.minted_count,
+            series_id: Some(series_id),
+            collection_id: None,
+        };
+        let receipt = self.internal_mint_token(token, series.edition_metadata(edition), price);
+        self.internal_charge_mint_deposit(initial_storage_usage, price);
+        receipt
+    }
+
+    /// Permanently stops the mints of `series_id`, capping its supply to the editions
+    /// minted so far. Only the admin or the series creator can close it.
+    pub fn close_series(&mut self, series_id: SeriesId) {
+        self.assert_not_sunset();
+        let mut series = self.series.get(&series_id).expect("Series not found");
+        if !self.is_admin() && env::predecessor_account_id() != series.creator_id {
+            env::panic(b"Unauthorized");
+        }
+        assert!(!series.closed, "Series is already closed");
+
+        series.closed = true;
+        series.max_supply = Some(series.minted);
+        self.series.insert(&series_id, &series);
+        self.internal_emit_extension(NftEvent::SeriesClose(vec![SeriesCloseLog {
+            series_id,
+            final_supply: series.minted,
+        }]));
+    }
+
+    /// Gateway joined with the relative `media` and `reference` paths of the editions of
+    /// `series_id`, instead of the contract `base_uri`. Only the admin or the series creator
+    /// can set it.
+    pub fn set_series_base_uri(&mut self, series_id: SeriesId, base_uri: Option<String>) {
+        self.assert_not_sunset();
+        let mut series = self.series.get(&series_id).expect("Series not found");
+        if !self.is_admin() && env::predecessor_account_id() != series.creator_id {
+            env::panic(b"Unauthorized");
+        }
+        series.base_uri = base_uri;
+        self.series.insert(&series_id, &series);
+        // Shown by every edition, too many to list.
+        self.internal_emit_contract_metadata_update(Some(format!(
+            "base uri of series {}",
+            series_id
+        )));
+    }
+
+    /// Gives a page of the editions of `series_id` their final metadata and reveals the ones
+    /// still shown with the placeholder metadata, announced by a single metadata update event.
+    /// Large series are revealed over several calls. Frozen editions keep their metadata.
+    pub fn reveal_series(
+        &mut self,
+        series_id: SeriesId,
+        reveal: SeriesReveal,
+        from_index: U128,
+        limit: u64,
+    ) {
+        self.assert_admin();
+        let mut series = self.series.get(&series_id).expect("Series not found");
+        if let SeriesReveal::Metadata(metadata) = &reveal {
+            metadata.assert_valid();
+            self.assert_metadata_within_limits(metadata);
+            series.metadata = *metadata.clone();
+            self.series.insert(&series_id, &series);
+        }
+
+        let filter = TokenFilter::Series {
+            series_id: series_id.clone(),
+        };
+        let token_ids: Vec<TokenId> =
+            self.internal_filter_tokens(&filter)
+                .map_or_else(Vec::new, |token_ids| {
+                    token_ids
+                        .iter()
+                        .skip(from_index.0 as usize)
+                        .take(limit as usize)
+                        .collect()
+                });
+        let mut updated = Vec::new();
+        let mut revealed = Vec::new();
+        for token_id in token_ids {
+            let mut token = self.tokens_by_id.get(&token_id).unwrap();
+            if token.frozen {
+                continue;
+            }
+            let edition: u64 = token_id.rsplit(':').next().unwrap().parse().unwrap();
+            let previous = self.token_metadata_by_id.get(&token_id).unwrap();
+            let mut metadata = series.edition_metadata(edition);
+            metadata.issued_at = previous.issued_at;
+            metadata.updated_at = Some(env::block_timestamp());
+            if let SeriesReveal::BaseUri {
+                base_uri,
+                reference_hashes,
+            } = &reveal
+            {
+                let reference_hash =
+                    reference_hashes
+                        .get(edition as usize - 1)
+                        .unwrap_or_else(|| {
+                            env::panic(
+                                format!("Missing reference_hash of edition {}", edition).as_bytes(),
+                            )
+                        });
+                metadata.reference = Some(format!(
+                    "{}/{}.json",
+                    base_uri.trim_end_matches('/'),
+                    edition
+                ));
+                metadata.reference_hash = Some(reference_hash.clone());
+                metadata.assert_valid();
+            }
+            if metadata.reference != previous.reference {
+                self.internal_count_copy(&series.creator_id, &metadata);
+            }
+            if token.revealed {
+                self.internal_remove_token_traits(&token_id, &previous);
+            }
+            self.token_metadata_by_id.insert(&token_id, &metadata);
+            self.internal_add_token_traits(&token_id, &metadata);
+
+            if !token.revealed {
+                token.revealed = true;
+                self.tokens_by_id.insert(&token_id, &token);
+                self.pending_reveal.remove(&token_id);
+                self.internal_record_activity(&token_id, ActivityKind::Reveal);
+                revealed.push(token_id.clone());
+            }
+            updated.push(token_id);
+        }
+
+        if !updated.is_empty() {
+            self.internal_emit_metadata_update(updated, "reveal series");
+        }
+        if !revealed.is_empty() {
+            self.internal_emit_extension(NftEvent::NftReveal(vec![NftRevealLog {
+                token_ids: revealed,
+            }]));
+        }
+    }
+
+    /// Details of `series_id` for a storefront drop page.
+    pub fn get_series_details(&self, series_id: SeriesId) -> Option<SeriesDetails> {
+        self.series
+            .get(&series_id)
+            .map(|series| self.internal_series_details(series))
+    }
+
+    /// Page of the series in creation order, with their details.
+    pub fn get_series(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<SeriesDetails> {
+        let from_index = from_index.map_or(0, |index| index.0 as usize);
+        let limit = limit.map_or(usize::MAX, |limit| limit as usize);
+        self.series
+            .values()
+            .skip(from_index)
+            .take(limit)
+            .map(|series| self.internal_series_details(series))
+            .collect()
+    }
+
+    /// Page of the editions of `series_id` that were not burned.
+    pub fn tokens_in_series(
+        &self,
+        series_id: SeriesId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<JsonToken> {
+        self.nft_tokens_filtered(TokenFilter::Series { series_id }, from_index, limit)
+    }
+}
+
+impl NftContract {
+    fn internal_series_details(&self, series: Series) -> SeriesDetails {
+        let supply = self
+            .nft_supply_filtered(TokenFilter::Series {
+                series_id: series.series_id.clone(),
+            })
+            .0 as u64;
+        let remaining = series
+            .max_supply
+            .map(|max_supply| U64(max_supply.0 - series.minted.0));
+        SeriesDetails {
+            series,
+            supply: U64(supply),
+            remaining,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U128;
+    use near_sdk::serde::export::TryFrom;
+    #[cfg(feature = "verbose-events")]
+    use near_sdk::test_utils::get_logs;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn mint_series_editions() {
+        testing_env!(get_context(owner(), 10u128.pow(24)));
+        let mut contract = NftContract::default();
+        let series_id = contract.create_series(
+            helper_token_metadata(),
+            Some(U128(10)),
+            None,
+            Some(U64(2)),
+            None,
+        );
+        assert_eq!(series_id, "1");
+
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        contract.nft_mint_series(series_id.clone(), None);
+        contract.nft_mint_series(
+            series_id.clone(),
+            Some(ValidAccountId::try_from(nft()).unwrap()),
+        );
+        let token = contract.nft_token("1:2".to_string()).unwrap();
+        assert_eq!(token.owner_id, nft());
+        assert_eq!(token.creator_id, owner());
+        assert_eq!(token.series_id, Some(series_id.clone()));
+        assert_eq!(token.metadata.title, Some("Mochi Rising #2".to_string()));
+        assert_eq!(token.metadata.copies, Some(U64(2)));
+
+        let details = contract.get_series_details(series_id.clone()).unwrap();
+        assert_eq!(details.supply, U64(2));
+        assert_eq!(details.remaining, Some(U64(0)));
+        let filter = TokenFilter::Series {
+            series_id: series_id.clone(),
+        };
+        assert_eq!(contract.nft_supply_filtered(filter), U128(2));
+        assert_eq!(
+            contract
+                .tokens_in_series(series_id.clone(), None, None)
+                .len(),
+            2
+        );
+        assert_eq!(contract.get_series(None, None).len(), 1);
+        contract.nft_burn("1:1".to_string());
+        let details = contract.get_series_details(series_id).unwrap();
+        assert_eq!(details.supply, U64(1));
+        assert_eq!(details.series.minted, U64(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Series is sold out")]
+    fn series_respects_max_supply() {
+        testing_env!(get_context(owner(), 10u128.pow(24)));
+        let mut contract = NftContract::default();
+        let series_id = contract.create_series(
+            helper_token_metadata(),
+            Some(U128(0)),
+            None,
+            Some(U64(1)),
+            None,
+        );
+        contract.nft_mint_series(series_id.clone(), None);
+        contract.nft_mint_series(series_id, None);
+    }
+
+    #[test]
+    fn series_royalty_and_default_price() {
+        testing_env!(get_context(owner(), 10u128.pow(24)));
+        let mut contract = NftContract::default();
+        contract.set_max_royalty(1_000);
+        contract.set_mint_price(U128(7));
+        let mut royalty = Royalty::new();
+        royalty.insert(owner(), 500);
+        let series_id = contract.create_series(
+            helper_token_metadata(),
+            None,
+            Some(royalty.clone()),
+            None,
+            None,
+        );
+
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        let receipt = contract.nft_mint_series(series_id, None);
+        assert_eq!(receipt.fee_paid, U128(7));
+        let token = contract.tokens_by_id.get(&receipt.token_id).unwrap();
+        assert_eq!(token.royalty, royalty);
+    }
+
+    #[test]
+    #[should_panic(expected = "Must attach")]
+    fn series_mint_charges_storage() {
+        testing_env!(get_context(owner(), 10u128.pow(24)));
+        let mut contract = NftContract::default();
+        let series_id =
+            contract.create_series(helper_token_metadata(), Some(U128(10)), None, None, None);
+
+        testing_env!(get_context(bob(), 10));
+        contract.nft_mint_series(series_id, None);
+    }
+
+    #[test]
+    fn close_series_stops_mints() {
+        testing_env!(get_context(owner(), 10u128.pow(24)));
+        let mut contract = NftContract::default();
+        let series_id =
+            contract.create_series(helper_token_metadata(), Some(U128(0)), None, None, None);
+        contract.nft_mint_series(series_id.clone(), None);
+        contract.close_series(series_id.clone());
+
+        let details = contract.get_series_details(series_id.clone()).unwrap();
+        assert!(details.series.closed);
+        assert_eq!(details.series.max_supply, Some(U64(1)));
+        assert_eq!(details.remaining, Some(U64(0)));
+        #[cfg(feature = "verbose-events")]
+        assert_eq!(
+            get_logs().last().unwrap(),
+            r#"EVENT_JSON:{"standard":"nft-demo","version":"1.0.0","event":"series_close","data":[{"series_id":"1","final_supply":"1"}]}"#
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Series is closed")]
+    fn mint_closed_series() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = NftContract::default();
+        let series_id =
+            contract.create_series(helper_token_metadata(), Some(U128(0)), None, None, None);
+        contract.close_series(series_id.clone());
+        contract.nft_mint_series(series_id, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Token id can only have ASCII letters, digits, -, _ or .")]
+    fn mint_series_edition_id() {
+        let (mut contract, _) = helper_mint();
+        contract.nft_mint(
+            "1:1".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+    }
+}
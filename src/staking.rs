@@ -0,0 +1,458 @@
+use crate::*;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::{ext_contract, Gas, PromiseResult};
+
+const NANOS_PER_SECOND: u128 = 1_000_000_000;
+
+const GAS_FOR_REWARD_TRANSFER: Gas = 10_000_000_000_000;
+const GAS_FOR_RESOLVE_REWARDS: Gas = 10_000_000_000_000;
+
+/// Rewards in a NEP-141 fungible token for the tokens staked in the contract.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StakingPool {
+    pub ft_token_id: AccountId,
+    pub reward_per_second: U128, // per staked token, in the smallest unit of the FT
+    pub reward_per_token: U128,  // accrued by a token staked since the pool started
+    pub updated_at: U64,         // block timestamp `reward_per_token` was accrued up to
+    pub staked_count: U64,
+}
+
+impl StakingPool {
+    /// `reward_per_token` accrued up to now. The elapsed time is split into whole seconds
+    /// and the nanoseconds left, so a high rate over a long gap does not overflow.
+    fn current_reward_per_token(&self) -> Balance {
+        let elapsed = env::block_timestamp().saturating_sub(self.updated_at.0) as u128;
+        let rate = self.reward_per_second.0;
+        let accrued = math::add_u128(
+            math::mul_u128(rate, elapsed / NANOS_PER_SECOND, "Staking reward"),
+            math::mul_div(rate, elapsed % NANOS_PER_SECOND, NANOS_PER_SECOND),
+            "Staking reward",
+        );
+        math::add_u128(self.reward_per_token.0, accrued, "Staking reward")
+    }
+
+    fn checkpoint(&mut self) {
+        self.reward_per_token = self.current_reward_per_token().into();
+        self.updated_at = env::block_timestamp().into();
+    }
+}
+
+/// Token locked in the contract by its owner, earning pool rewards.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Stake {
+    pub token_id: TokenId,
+    pub owner_id: AccountId,
+    pub staked_at: U64,
+    pub reward_per_token_paid: U128, // pool `reward_per_token` when rewards were last claimed
+}
+
+/// A stake with the rewards it can claim now.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StakeView {
+    #[serde(flatten)]
+    pub stake: Stake,
+    pub pending_rewards: U128,
+}
+
+#[ext_contract(ext_reward_ft)]
+trait RewardToken {
+    fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>);
+}
+
+#[ext_contract(ext_rewards_resolver)]
+trait RewardsResolver {
+    fn on_rewards_paid(&mut self, account_id: AccountId, ft_token_id: AccountId, amount: U128);
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// Pays `reward_per_second` of `ft_token_id` to each staked token. The contract must hold
+    /// enough of the token to pay the claims. The reward token can only change while nothing
+    /// is staked, so every reward accrued before is paid in the old token. Failed payments in
+    /// the old token are claimed with `nft_claim_unpaid_rewards`.
+    pub fn set_staking_rewards(&mut self, ft_token_id: ValidAccountId, reward_per_second: U128) {
+        self.assert_admin();
+        let pool = match self.staking_pool.take() {
+            Some(mut pool) => {
+                assert!(
+                    &pool.ft_token_id == ft_token_id.as_ref() || pool.staked_count.0 == 0,
+                    "Cannot change the reward token while tokens are staked"
+                );
+                pool.checkpoint();
+                pool.ft_token_id = ft_token_id.into();
+                pool.reward_per_second = reward_per_second;
+                pool
+            }
+            None => StakingPool {
+                ft_token_id: ft_token_id.into(),
+                reward_per_second,
+                reward_per_token: U128(0),
+                updated_at: env::block_timestamp().into(),
+                staked_count: U64(0),
+            },
+        };
+        self.staking_pool = Some(pool);
+    }
+
+    pub fn staking_pool(&self) -> Option<StakingPool> {
+        self.staking_pool.clone()
+    }
+
+    /// Locks `token_id` in the contract so it earns rewards. The token keeps its owner but
+    /// cannot move or be burned until it is unstaked. The deposit covers the storage of the stake.
+    #[payable]
+    pub fn nft_stake(&mut self, token_id: TokenId) {
+        self.assert_not_paused();
+        let token = self.tokens_by_id.get(&token_id).expect("Token not found");
+
+        if env::predecessor_account_id() != token.owner_id {
+            env::panic(b"Unauthorized");
+        }
+
+        self.assert_not_staked(&token_id);
+        self.assert_not_leased(&token_id);
+        self.assert_not_sent(&token_id);
+        self.assert_not_dropped(&token_id);
+        self.assert_not_swapped(&token_id);
+        self.assert_no_auction_bids(&token_id);
+        let mut pool = self.staking_pool.take().expect("Staking is not enabled");
+        pool.checkpoint();
+        pool.staked_count = (pool.staked_count.0 + 1).into();
+
+        let initial_storage_usage = env::storage_usage();
+        let stake = Stake {
+            token_id,
+            owner_id: token.owner_id,
+            staked_at: env::block_timestamp().into(),
+            reward_per_token_paid: pool.reward_per_token,
+        };
+        self.staking_pool = Some(pool);
+        self.internal_add_stake(&stake);
+        self.internal_charge_storage(initial_storage_usage);
+    }
+
+    /// Unlocks `token_id` and pays its pending rewards. The freed storage is refunded.
+    #[payable]
+    pub fn nft_unstake(&mut self, token_id: TokenId) {
+        let stake = self.stakes.get(&token_id).expect("Token is not staked");
+
+        if env::predecessor_account_id() != stake.owner_id {
+            env::panic(b"Unauthorized");
+        }
+
+        let initial_storage_usage = env::storage_usage();
+        let amount = self.internal_settle_stake(&stake);
+        self.internal_remove_stake(&token_id);
+        let mut pool = self.staking_pool.take().unwrap();
+        pool.staked_count = (pool.staked_count.0 - 1).into();
+        self.staking_pool = Some(pool);
+        self.internal_charge_storage(initial_storage_usage);
+        self.internal_pay_rewards(&stake.owner_id, amount);
+    }
+
+    /// Pays the pending rewards of the caller's staked `token_ids`, which stay staked.
+    /// Returns the amount paid.
+    pub fn nft_claim_rewards(&mut self, token_ids: Vec<TokenId>) -> U128 {
+        self.assert_not_paused();
+        let account_id = env::predecessor_account_id();
+        let mut amount = 0;
+        for token_id in token_ids.iter() {
+            let mut stake = self.stakes.get(token_id).expect("Token is not staked");
+            if stake.owner_id != account_id {
+                env::panic(b"Unauthorized");
+            }
+            amount += self.internal_settle_stake(&stake);
+            stake.reward_per_token_paid = self.staking_pool.as_ref().unwrap().reward_per_token;
+            self.stakes.insert(token_id, &stake);
+        }
+        self.internal_pay_rewards(&account_id, amount);
+        U128(amount)
+    }
+
+    /// Sends the caller its rewards in `ft_token_id` whose transfer failed, ex. in a reward
+    /// token the pool no longer pays. Returns the amount sent.
+    pub fn nft_claim_unpaid_rewards(&mut self, ft_token_id: ValidAccountId) -> U128 {
+        let account_id = env::predecessor_account_id();
+        let ft_token_id: AccountId = ft_token_id.into();
+        let amount = self
+            .unpaid_rewards
+            .remove(&(account_id.clone(), ft_token_id.clone()))
+            .expect("No unpaid rewards");
+        self.internal_send_rewards(&account_id, &ft_token_id, amount);
+        U128(amount)
+    }
+
+    /// Records rewards whose transfer failed, to be paid with the next claim.
+    #[private]
+    pub fn on_rewards_paid(&mut self, account_id: AccountId, ft_token_id: AccountId, amount: U128) {
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            return;
+        }
+        log!(
+            "Reward of {} {} to @{} failed",
+            amount.0,
+            ft_token_id,
+            account_id
+        );
+        let key = (account_id, ft_token_id);
+        let unpaid = self.unpaid_rewards.get(&key).unwrap_or(0);
+        self.unpaid_rewards
+            .insert(&key, &math::add_u128(unpaid, amount.0, "Unpaid rewards"));
+    }
+
+    pub fn nft_stake_info(&self, token_id: TokenId) -> Option<StakeView> {
+        self.stakes.get(&token_id).map(|stake| StakeView {
+            pending_rewards: U128(self.internal_pending_rewards(&stake)),
+            stake,
+        })
+    }
+
+    /// Page of the stakes of `account_id`, with their pending rewards.
+    pub fn nft_stakes_for_owner(
+        &self,
+        account_id: ValidAccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<StakeView> {
+        let token_ids = match self.stakes_by_owner.get(account_id.as_ref()) {
+            Some(token_ids) => token_ids,
+            None => return vec![],
+        };
+        let from_index = from_index.map_or(0, |index| index.0 as usize);
+        let limit = limit.map_or(usize::MAX, |limit| limit as usize);
+        token_ids
+            .iter()
+            .skip(from_index)
+            .take(limit)
+            .filter_map(|token_id| self.nft_stake_info(token_id))
+            .collect()
+    }
+
+    /// Rewards `account_id` can claim now over all its stakes, including failed payments in
+    /// the current reward token.
+    pub fn nft_pending_rewards(&self, account_id: ValidAccountId) -> U128 {
+        let staked = self
+            .stakes_by_owner
+            .get(account_id.as_ref())
+            .map_or(0, |token_ids| {
+                token_ids
+                    .iter()
+                    .map(|token_id| {
+                        self.internal_pending_rewards(&self.stakes.get(&token_id).unwrap())
+                    })
+                    .sum()
+            });
+        let unpaid = self.staking_pool.as_ref().map_or(0, |pool| {
+            self.unpaid_rewards
+                .get(&(account_id.into(), pool.ft_token_id.clone()))
+                .unwrap_or(0)
+        });
+        U128(staked + unpaid)
+    }
+
+    /// Rewards of `account_id` in `ft_token_id` whose transfer failed.
+    pub fn nft_unpaid_rewards(
+        &self,
+        account_id: ValidAccountId,
+        ft_token_id: ValidAccountId,
+    ) -> U128 {
+        U128(
+            self.unpaid_rewards
+                .get(&(account_id.into(), ft_token_id.into()))
+                .unwrap_or(0),
+        )
+    }
+}
+
+impl NftContract {
+    pub(crate) fn assert_not_staked(&self, token_id: &TokenId) {
+        assert!(self.stakes.get(token_id).is_none(), "Token is staked");
+    }
+
+    fn internal_pending_rewards(&self, stake: &Stake) -> Balance {
+        let pool = self.staking_pool.as_ref().unwrap();
+        pool.current_reward_per_token() - stake.reward_per_token_paid.0
+    }
+
+    /// Brings the pool up to date and returns the rewards of `stake` since its last claim.
+    fn internal_settle_stake(&mut self, stake: &Stake) -> Balance {
+        let mut pool = self.staking_pool.take().unwrap();
+        pool.checkpoint();
+        let amount = pool.reward_per_token.0 - stake.reward_per_token_paid.0;
+        self.staking_pool = Some(pool);
+        amount
+    }
+
+    /// Sends `amount` plus any earlier unpaid rewards in the current reward token to `account_id`.
+    fn internal_pay_rewards(&mut self, account_id: &AccountId, amount: Balance) {
+        let ft_token_id = self.staking_pool.as_ref().unwrap().ft_token_id.clone();
+        let unpaid = self
+            .unpaid_rewards
+            .remove(&(account_id.clone(), ft_token_id.clone()))
+            .unwrap_or(0);
+        let amount = math::add_u128(amount, unpaid, "Staking reward");
+        if amount > 0 {
+            self.internal_send_rewards(account_id, &ft_token_id, amount);
+        }
+    }
+
+    fn internal_send_rewards(
+        &self,
+        account_id: &AccountId,
+        ft_token_id: &AccountId,
+        amount: Balance,
+    ) {
+        log!("Pay {} of {} to @{}", amount, ft_token_id, account_id);
+        ext_reward_ft::ft_transfer(
+            account_id.clone(),
+            U128(amount),
+            Some("staking rewards".to_string()),
+            ft_token_id,
+            1,
+            GAS_FOR_REWARD_TRANSFER,
+        )
+        .then(ext_rewards_resolver::on_rewards_paid(
+            account_id.clone(),
+            ft_token_id.clone(),
+            U128(amount),
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_REWARDS,
+        ));
+    }
+
+    fn internal_add_stake(&mut self, stake: &Stake) {
+        self.stakes.insert(&stake.token_id, stake);
+        let mut token_ids = self
+            .stakes_by_owner
+            .get(&stake.owner_id)
+            .unwrap_or_else(|| {
+                UnorderedSet::new(
+                    StorageKey::StakesByOwnerInner {
+                        account_id_hash: hash_account_id(&stake.owner_id),
+                    }
+                    .try_to_vec()
+                    .unwrap(),
+                )
+            });
+        token_ids.insert(&stake.token_id);
+        self.stakes_by_owner.insert(&stake.owner_id, &token_ids);
+    }
+
+    fn internal_remove_stake(&mut self, token_id: &TokenId) {
+        let stake = self.stakes.remove(token_id).unwrap();
+        let mut token_ids = self.stakes_by_owner.get(&stake.owner_id).unwrap();
+        token_ids.remove(token_id);
+        if token_ids.is_empty() {
+            self.stakes_by_owner.remove(&stake.owner_id);
+        } else {
+            self.stakes_by_owner.insert(&stake.owner_id, &token_ids);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U128;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn unpaid_rewards_stay_in_their_token() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = NftContract::default();
+        contract.set_staking_rewards(ValidAccountId::try_from("ft.near").unwrap(), U128(5));
+        contract
+            .unpaid_rewards
+            .insert(&(nft(), "ft.near".to_string()), &30);
+        contract.set_staking_rewards(ValidAccountId::try_from("ft2.near").unwrap(), U128(5));
+        assert_eq!(
+            contract.nft_pending_rewards(ValidAccountId::try_from(nft()).unwrap()),
+            U128(0)
+        );
+
+        testing_env!(get_context(nft(), 0));
+        assert_eq!(
+            contract.nft_claim_unpaid_rewards(ValidAccountId::try_from("ft.near").unwrap()),
+            U128(30)
+        );
+        assert_eq!(
+            contract.nft_unpaid_rewards(
+                ValidAccountId::try_from(nft()).unwrap(),
+                ValidAccountId::try_from("ft.near").unwrap()
+            ),
+            U128(0)
+        );
+    }
+
+    #[test]
+    fn staked_token_accrues_rewards() {
+        let (mut contract, mut context) = helper_mint();
+        testing_env!(get_context(owner(), 0));
+        contract.set_staking_rewards(ValidAccountId::try_from("ft.near").unwrap(), U128(5));
+
+        testing_env!(context.clone());
+        contract.nft_stake("0".to_string());
+        context.block_timestamp = 10 * 10u64.pow(9);
+        testing_env!(context.clone());
+        let stake = contract.nft_stake_info("0".to_string()).unwrap();
+        assert_eq!(stake.pending_rewards, U128(50));
+        assert_eq!(
+            contract.nft_pending_rewards(ValidAccountId::try_from(nft()).unwrap()),
+            U128(50)
+        );
+
+        assert_eq!(contract.nft_claim_rewards(vec!["0".to_string()]), U128(50));
+        let stake = contract.nft_stake_info("0".to_string()).unwrap();
+        assert_eq!(stake.pending_rewards, U128(0));
+
+        context.block_timestamp = 12 * 10u64.pow(9);
+        testing_env!(context.clone());
+        contract.nft_unstake("0".to_string());
+        assert!(contract.nft_stake_info("0".to_string()).is_none());
+        assert_eq!(contract.staking_pool().unwrap().staked_count, U64(0));
+        assert!(contract
+            .nft_stakes_for_owner(ValidAccountId::try_from(nft()).unwrap(), None, None)
+            .is_empty());
+    }
+
+    #[test]
+    fn high_reward_rate_does_not_overflow() {
+        let (mut contract, mut context) = helper_mint();
+        testing_env!(get_context(owner(), 0));
+        let rate = 10u128.pow(24);
+        contract.set_staking_rewards(ValidAccountId::try_from("ft.near").unwrap(), U128(rate));
+
+        testing_env!(context.clone());
+        contract.nft_stake("0".to_string());
+        // A year and half a second without a checkpoint.
+        let year = 365 * 24 * 3600;
+        context.block_timestamp = year * 10u64.pow(9) + 5 * 10u64.pow(8);
+        testing_env!(context.clone());
+        let stake = contract.nft_stake_info("0".to_string()).unwrap();
+        assert_eq!(stake.pending_rewards, U128(rate * year as u128 + rate / 2));
+        contract.nft_unstake("0".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Token is staked")]
+    fn staked_token_cannot_move() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(get_context(owner(), 0));
+        contract.set_staking_rewards(ValidAccountId::try_from("ft.near").unwrap(), U128(5));
+
+        testing_env!(context);
+        contract.nft_stake("0".to_string());
+        contract.nft_transfer(
+            ValidAccountId::try_from(bob()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+    }
+}
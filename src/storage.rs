@@ -0,0 +1,588 @@
+use crate::*;
+use near_sdk::json_types::U128;
+use near_sdk::Promise;
+
+/// Bytes a mint stores besides the token metadata, measured with 64 byte account and token IDs
+/// and a pending reveal, so estimates based on it are an upper bound.
+pub const MINT_STORAGE_BASE: u64 = 3000;
+
+/// Storage prepaid by an account with `storage_deposit`, following NEP-145.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalance {
+    pub total: U128,
+    pub available: U128, // not spent on storage yet, can be withdrawn
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// Prepays storage for `account_id`, the caller by default. Calls that add storage and
+    /// don't attach enough deposit draw the difference from the caller's prepaid balance.
+    #[payable]
+    pub fn storage_deposit(&mut self, account_id: Option<ValidAccountId>) -> StorageBalance {
+        self.assert_not_paused();
+        let account_id = account_id.map_or_else(env::predecessor_account_id, |id| id.into());
+        let amount = env::attached_deposit();
+        assert!(amount > 0, "Must attach a deposit");
+
+        let initial_storage_usage = env::storage_usage();
+        let mut balance = self.internal_storage_balance(&account_id);
+        balance.total.0 += amount;
+        balance.available.0 += amount;
+        self.storage_deposits.insert(&account_id, &balance);
+
+        // The first deposit pays for its own record.
+        let record_cost =
+            Balance::from(env::storage_usage() - initial_storage_usage) * env::storage_byte_cost();
+        assert!(
+            balance.available.0 >= record_cost,
+            "Must attach at least {} yoctoNEAR",
+            record_cost
+        );
+        balance.available.0 -= record_cost;
+        self.storage_deposits.insert(&account_id, &balance);
+        balance
+    }
+
+    /// Returns `amount` of the caller's available storage balance, all of it by default.
+    #[payable]
+    pub fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        near_sdk::assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut balance = self
+            .storage_deposits
+            .get(&account_id)
+            .expect("No storage deposit");
+        let amount = amount.map_or(balance.available.0, |amount| amount.0);
+        assert!(
+            amount <= balance.available.0,
+            "Cannot withdraw more than {} yoctoNEAR",
+            balance.available.0
+        );
+
+        balance.total.0 -= amount;
+        balance.available.0 -= amount;
+        self.storage_deposits.insert(&account_id, &balance);
+        Promise::new(account_id).transfer(amount + 1);
+        balance
+    }
+
+    pub fn storage_balance_of(&self, account_id: ValidAccountId) -> Option<StorageBalance> {
+        self.storage_deposits.get(account_id.as_ref())
+    }
+
+    /// Price of one byte of storage in yoctoNEAR.
+    pub fn storage_byte_cost(&self) -> U128 {
+        U128(env::storage_byte_cost())
+    }
+
+    /// Deposit to attach to `nft_mint` for `metadata` now: the mint price plus its storage.
+    /// Anything attached above the actual cost is refunded.
+    pub fn estimated_mint_cost(&self, metadata: TokenMetadata) -> U128 {
+        let storage = MINT_STORAGE_BASE + metadata.try_to_vec().unwrap().len() as u64;
+        U128(self.current_mint_price().0 + Balance::from(storage) * env::storage_byte_cost())
+    }
+
+    /// Removes the per-account records of a caller holding no tokens, returning the bytes freed.
+    /// Their storage is refunded to the caller, who paid for it, except the empty token set,
+    /// which goes back to whoever paid for it, and a pending recovery, which goes back to the guardian.
+    pub fn cleanup_my_data(&mut self) -> U64 {
+        self.assert_not_sunset();
+        let account_id = env::predecessor_account_id();
+
+        let mut freed = 0;
+        if let Some(tokens) = self.tokens_per_owner.get(&account_id) {
+            assert!(tokens.is_empty(), "Account still owns tokens");
+            freed += self.internal_remove_token_set(&account_id, tokens);
+        }
+        // The guardian paid for a pending recovery.
+        let initial_storage_usage = env::storage_usage();
+        self.internal_remove_recovery(&account_id);
+        freed += initial_storage_usage.saturating_sub(env::storage_usage());
+
+        let initial_storage_usage = env::storage_usage();
+        self.watchlists.remove(&account_id);
+        self.receiving_policies.remove(&account_id);
+        self.guardians.remove(&account_id);
+        let own_freed = initial_storage_usage.saturating_sub(env::storage_usage());
+        self.internal_refund_storage(&account_id, own_freed);
+
+        freed += own_freed;
+        log!("Cleanup @{} freed {} bytes", account_id, freed);
+        freed.into()
+    }
+
+    /// Removes the empty token sets left behind by former owners, returning the bytes freed.
+    /// Each call checks the past owners of `limit` tokens, in the order of `nft_tokens`, and
+    /// the next call resumes after them. The freed storage goes back to whoever paid for each set.
+    pub fn cleanup_empty_owners(&mut self, limit: u64) -> U64 {
+        self.assert_admin();
+        let initial_storage_usage = env::storage_usage();
+
+        let len = self.token_ids.len();
+        let start = self.owner_cleanup_cursor.min(len);
+        let end = start.saturating_add(limit).min(len);
+        for index in start..end {
+            let token_id = self.token_ids.as_vector().get(index).unwrap();
+            for record in self.ownership_history.get(&token_id).unwrap_or_default() {
+                self.internal_remove_empty_owner_set(&record.from_id);
+            }
+        }
+        self.owner_cleanup_cursor = if end == len { 0 } else { end };
+
+        let freed = initial_storage_usage.saturating_sub(env::storage_usage());
+        log!("Cleanup of empty owners freed {} bytes", freed);
+        freed.into()
+    }
+
+    /// Index of the next token `cleanup_empty_owners` checks.
+    pub fn owner_cleanup_cursor(&self) -> U64 {
+        self.owner_cleanup_cursor.into()
+    }
+
+    /// Charges the caller for the storage added since `initial_storage_usage` out of the
+    /// attached deposit and refunds the rest. When the deposit falls short, the difference comes
+    /// from the caller's prepaid storage. Storage freed by the call goes back to the caller,
+    /// except what was already refunded to the account that paid for it, such as the token set
+    /// of a sender giving away its last token.
+    pub(crate) fn internal_charge_storage(&mut self, initial_storage_usage: u64) {
+        let account_id = env::predecessor_account_id();
+        self.internal_record_token_set_payer(&account_id);
+        let (storage_used, storage_freed) = self.internal_storage_delta(initial_storage_usage);
+        let required = Balance::from(storage_used) * env::storage_byte_cost();
+        let attached = env::attached_deposit();
+
+        if attached < required {
+            let shortfall = required - attached;
+            match self.storage_deposits.get(&account_id) {
+                Some(mut balance) if balance.available.0 >= shortfall => {
+                    balance.available.0 -= shortfall;
+                    self.storage_deposits.insert(&account_id, &balance);
+                    return;
+                }
+                _ => env::panic(
+                    format!("Must attach {} yoctoNEAR to cover storage", required).as_bytes(),
+                ),
+            }
+        }
+
+        let refund = attached - required;
+        if refund > 0 {
+            Promise::new(account_id.clone()).transfer(refund);
+        }
+        self.internal_credit_storage(&account_id, storage_freed);
+    }
+
+    /// Charges `account_id` for the storage added since `initial_storage_usage` out of its
+    /// prepaid storage, for calls made by another contract on its behalf.
+    pub(crate) fn internal_charge_prepaid_storage(
+        &mut self,
+        account_id: &AccountId,
+        initial_storage_usage: u64,
+    ) {
+        self.internal_record_token_set_payer(account_id);
+        let (storage_used, _) = self.internal_storage_delta(initial_storage_usage);
+        let required = Balance::from(storage_used) * env::storage_byte_cost();
+        let mut balance = self.internal_storage_balance(account_id);
+        assert!(
+            balance.available.0 >= required,
+            "@{} must prepay {} yoctoNEAR of storage with storage_deposit",
+            account_id,
+            required
+        );
+        balance.available.0 -= required;
+        self.storage_deposits.insert(account_id, &balance);
+    }
+
+    /// Bytes added and freed since `initial_storage_usage`, not counting what was already
+    /// refunded with `internal_refund_storage`.
+    pub(crate) fn internal_storage_delta(&mut self, initial_storage_usage: u64) -> (u64, u64) {
+        let storage_usage = env::storage_usage() + self.storage_refunded;
+        self.storage_refunded = 0;
+        (
+            storage_usage.saturating_sub(initial_storage_usage),
+            initial_storage_usage.saturating_sub(storage_usage),
+        )
+    }
+
+    /// Returns the cost of `bytes` of freed storage to `account_id`, who paid for them.
+    /// Storage the contract paid for stays with it.
+    pub(crate) fn internal_refund_storage(&mut self, account_id: &AccountId, bytes: u64) {
+        if bytes == 0 {
+            return;
+        }
+        self.storage_refunded += bytes;
+        if account_id != &env::current_account_id() {
+            self.internal_credit_storage(account_id, bytes);
+        }
+    }
+
+    /// Records `payer_id`, who is charged for the storage of the current call, as the payer of
+    /// the token sets it created. Sets that calls charging nobody create for another account
+    /// than the caller stay paid by the contract, so their storage is never refunded.
+    pub(crate) fn internal_record_token_set_payer(&mut self, payer_id: &AccountId) {
+        for owner_id in std::mem::take(&mut self.new_token_sets) {
+            if !self.tokens_per_owner.contains_key(&owner_id) {
+                continue;
+            }
+            if &owner_id == payer_id {
+                self.token_set_payers.remove(&owner_id);
+            } else {
+                self.token_set_payers.insert(&owner_id, payer_id);
+            }
+        }
+    }
+
+    /// Deletes the empty token set of `account_id` and refunds its storage to whoever paid
+    /// for it, returning the bytes freed.
+    pub(crate) fn internal_remove_token_set(
+        &mut self,
+        account_id: &AccountId,
+        mut tokens: TreeMap<u64, TokenId>,
+    ) -> u64 {
+        let initial_storage_usage = env::storage_usage();
+        tokens.clear();
+        self.tokens_per_owner.remove(account_id);
+        let payer_id = self
+            .token_set_payers
+            .remove(account_id)
+            .unwrap_or_else(|| account_id.clone());
+        let freed = initial_storage_usage.saturating_sub(env::storage_usage());
+        self.internal_refund_storage(&payer_id, freed);
+        freed
+    }
+
+    /// Gives back the cost of `bytes` to `account_id`: first to its prepaid storage, up to what
+    /// was drawn from it, and the rest to its account.
+    fn internal_credit_storage(&mut self, account_id: &AccountId, bytes: u64) {
+        let mut amount = Balance::from(bytes) * env::storage_byte_cost();
+        if let Some(mut balance) = self.storage_deposits.get(account_id) {
+            let credit = amount.min(balance.total.0 - balance.available.0);
+            if credit > 0 {
+                balance.available.0 += credit;
+                self.storage_deposits.insert(account_id, &balance);
+                amount -= credit;
+            }
+        }
+        if amount > 0 {
+            Promise::new(account_id.clone()).transfer(amount);
+        }
+    }
+
+    /// Removes the token set of `account_id` if it is empty, refunding its storage.
+    fn internal_remove_empty_owner_set(&mut self, account_id: &AccountId) {
+        if let Some(tokens) = self.tokens_per_owner.get(account_id) {
+            if tokens.is_empty() {
+                self.internal_remove_token_set(account_id, tokens);
+            }
+        }
+    }
+
+    fn internal_storage_balance(&self, account_id: &AccountId) -> StorageBalance {
+        self.storage_deposits
+            .get(account_id)
+            .unwrap_or(StorageBalance {
+                total: U128(0),
+                available: U128(0),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U128;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    #[should_panic(expected = "to cover storage")]
+    fn transfer_to_new_receiver_without_deposit() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(nft(), 0));
+        contract.nft_transfer(
+            ValidAccountId::try_from(bob()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+    }
+
+    #[test]
+    fn cleanup_account_data() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(context);
+        contract.watch_token("0".to_string());
+        contract.nft_transfer(
+            ValidAccountId::try_from(bob()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+        testing_env!(get_context(nft(), 10u128.pow(23)));
+        let balance = contract.storage_deposit(None);
+        testing_env!(get_context(nft(), 0));
+        assert!(contract.cleanup_my_data().0 > 0);
+        assert!(contract
+            .watchlist(ValidAccountId::try_from(nft()).unwrap())
+            .is_empty());
+        let after = contract
+            .storage_balance_of(ValidAccountId::try_from(nft()).unwrap())
+            .unwrap();
+        assert!(after.available.0 > balance.available.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Account still owns tokens")]
+    fn cleanup_account_data_with_tokens() {
+        let (mut contract, _) = helper_mint();
+        contract.cleanup_my_data();
+    }
+
+    #[test]
+    fn estimated_mint_cost_includes_price() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(owner(), 0));
+        let storage_only = contract.estimated_mint_cost(helper_token_metadata()).0;
+        assert_eq!(storage_only % contract.storage_byte_cost().0, 0);
+
+        contract.set_mint_price(U128(5));
+        assert_eq!(
+            contract.estimated_mint_cost(helper_token_metadata()).0,
+            storage_only + 5
+        );
+    }
+
+    #[test]
+    fn transfer_paid_from_storage_deposit() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(nft(), 10u128.pow(23)));
+        let balance = contract.storage_deposit(None);
+        assert_eq!(balance.total, U128(10u128.pow(23)));
+        assert!(balance.available.0 < balance.total.0);
+
+        testing_env!(get_context(nft(), 0));
+        contract.nft_transfer(
+            ValidAccountId::try_from(bob()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+        let after = contract
+            .storage_balance_of(ValidAccountId::try_from(nft()).unwrap())
+            .unwrap();
+        assert!(after.available.0 < balance.available.0);
+        assert_eq!(after.total, balance.total);
+
+        testing_env!(get_context(nft(), 1));
+        let withdrawn = contract.storage_withdraw(None);
+        assert_eq!(withdrawn.available, U128(0));
+        assert_eq!(withdrawn.total.0, balance.total.0 - after.available.0);
+    }
+
+    #[test]
+    fn freed_storage_credits_storage_deposit() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(nft(), 10u128.pow(23)));
+        let balance = contract.storage_deposit(None);
+        contract.internal_refund_storage(&nft(), 10_000);
+        let after = contract
+            .storage_balance_of(ValidAccountId::try_from(nft()).unwrap())
+            .unwrap();
+        assert_eq!(after.available, balance.total);
+        assert_eq!(after.total, balance.total);
+    }
+
+    #[test]
+    fn token_set_refunded_to_payer() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(nft(), 10u128.pow(23)));
+        contract.storage_deposit(None);
+        testing_env!(get_context(nft(), 0));
+        contract.nft_transfer(
+            ValidAccountId::try_from(bob()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+        assert_eq!(contract.token_set_payers.get(&bob()), Some(nft()));
+        let paid = contract
+            .storage_balance_of(ValidAccountId::try_from(nft()).unwrap())
+            .unwrap();
+
+        testing_env!(get_context(bob(), 10u128.pow(22)));
+        contract.nft_transfer(
+            ValidAccountId::try_from(nft()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+        assert!(contract.token_set_payers.get(&bob()).is_none());
+        let refunded = contract
+            .storage_balance_of(ValidAccountId::try_from(nft()).unwrap())
+            .unwrap();
+        assert!(refunded.available.0 > paid.available.0);
+    }
+
+    #[test]
+    fn unpaid_token_set_not_refunded() {
+        let (mut contract, _) = helper_mint();
+        let token = contract.tokens_by_id.get(&"0".to_string()).unwrap();
+        contract.internal_transfer(token, &bob(), None, None);
+        assert_eq!(contract.token_set_payers.get(&bob()), Some(owner()));
+
+        testing_env!(get_context(bob(), 10u128.pow(22)));
+        contract.storage_deposit(None);
+        let before = contract
+            .storage_balance_of(ValidAccountId::try_from(bob()).unwrap())
+            .unwrap();
+        testing_env!(get_context(bob(), 0));
+        contract.nft_transfer(
+            ValidAccountId::try_from(nft()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+        let after = contract
+            .storage_balance_of(ValidAccountId::try_from(bob()).unwrap())
+            .unwrap();
+        assert!(after.available.0 <= before.available.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "to cover storage")]
+    fn transfer_without_storage_deposit() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(nft(), 0));
+        contract.nft_transfer(
+            ValidAccountId::try_from(bob()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+    }
+
+    #[test]
+    fn cleanup_empty_owner_sets() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(context);
+        contract.nft_transfer(
+            ValidAccountId::try_from(bob()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        contract.nft_transfer(
+            ValidAccountId::try_from(nft()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+        assert!(contract.tokens_per_owner.get(&bob()).is_none());
+        let empty: TreeMap<u64, TokenId> = TreeMap::new(
+            StorageKey::TokenPerOwnerInner {
+                account_id_hash: hash_account_id(&bob()),
+            }
+            .try_to_vec()
+            .unwrap(),
+        );
+        contract.tokens_per_owner.insert(&bob(), &empty);
+
+        testing_env!(get_context(owner(), 0));
+        let freed = contract.cleanup_empty_owners(10);
+        assert!(freed.0 > 0);
+        assert!(contract.tokens_per_owner.get(&bob()).is_none());
+        assert!(contract.tokens_per_owner.get(&nft()).is_some());
+        assert_eq!(contract.owner_cleanup_cursor(), U64(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "0 for the mint price and")]
+    fn mint_without_storage_deposit() {
+        testing_env!(get_context(nft(), 1));
+        let mut contract = NftContract::default();
+        contract.nft_mint(
+            "0".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+    }
+
+    /// Bytes the accounts have paid for out of their storage deposits.
+    fn paid_bytes(contract: &NftContract, accounts: &[AccountId]) -> i128 {
+        accounts
+            .iter()
+            .map(|account_id| {
+                let balance = contract.storage_deposits.get(account_id).unwrap();
+                ((balance.total.0 - balance.available.0) / env::storage_byte_cost()) as i128
+            })
+            .sum()
+    }
+
+    /// Runs `call` as `account_id` with nothing attached, so that every charge comes out of
+    /// the storage deposits, and returns the bytes it added.
+    fn run_call(
+        contract: &mut NftContract,
+        account_id: AccountId,
+        call: impl FnOnce(&mut NftContract),
+    ) -> i128 {
+        testing_env!(get_context(account_id, 0));
+        let initial_storage_usage = env::storage_usage();
+        call(contract);
+        env::storage_usage() as i128 - initial_storage_usage as i128
+    }
+
+    /// `helper_mint`, with `nft()` and `bob()` holding storage deposits.
+    fn helper_prepaid_mint() -> NftContract {
+        let (mut contract, _) = helper_mint();
+        for account_id in [nft(), bob()].iter() {
+            testing_env!(get_context(account_id.clone(), 10u128.pow(24)));
+            contract.storage_deposit(None);
+        }
+        contract
+    }
+
+    #[test]
+    fn storage_covered_across_send_and_claim() {
+        let mut contract = helper_prepaid_mint();
+        let accounts = [nft(), bob()];
+        let paid = paid_bytes(&contract, &accounts);
+        let mut added = 0;
+        for _ in 0..3 {
+            for (sender_id, receiver_id) in [(nft(), bob()), (bob(), nft())].iter() {
+                let receiver = ValidAccountId::try_from(receiver_id.clone()).unwrap();
+                added += run_call(&mut contract, sender_id.clone(), |contract| {
+                    contract.nft_send(receiver, "0".to_string())
+                });
+                assert!(paid_bytes(&contract, &accounts) - paid >= added);
+
+                added += run_call(&mut contract, receiver_id.clone(), |contract| {
+                    contract.nft_claim("0".to_string())
+                });
+                assert!(paid_bytes(&contract, &accounts) - paid >= added);
+            }
+        }
+        assert_eq!(contract.nft_token("0".to_string()).unwrap().owner_id, nft());
+    }
+
+    #[test]
+    fn storage_covered_across_decline_and_transfer() {
+        let mut contract = helper_prepaid_mint();
+        let accounts = [nft(), bob()];
+        let paid = paid_bytes(&contract, &accounts);
+        let mut added = 0;
+        for _ in 0..3 {
+            added += run_call(&mut contract, nft(), |contract| {
+                contract.nft_transfer(
+                    ValidAccountId::try_from(bob()).unwrap(),
+                    "0".to_string(),
+                    None,
+                )
+            });
+            assert!(paid_bytes(&contract, &accounts) - paid >= added);
+
+            added += run_call(&mut contract, bob(), |contract| {
+                contract.decline_token("0".to_string())
+            });
+            assert!(paid_bytes(&contract, &accounts) - paid >= added);
+        }
+        assert_eq!(contract.nft_token("0".to_string()).unwrap().owner_id, nft());
+    }
+}
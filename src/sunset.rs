@@ -0,0 +1,110 @@
+use crate::*;
+use near_sdk::json_types::U128;
+
+/// Token with its ownership history, as returned by `nft_export`.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ExportedToken {
+    pub token: JsonToken,
+    pub history: Vec<OwnershipRecord>,
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// Freezes the contract for good: every method that changes state fails from now on,
+    /// except `nft_burn` and the ones handing back what the contract holds for someone, ex.
+    /// cancelling an offer, withdrawing storage or unstaking. Views, including `nft_export`,
+    /// keep working.
+    pub fn enter_sunset_mode(&mut self) {
+        self.assert_admin();
+
+        log!("Enter sunset mode by @{}", env::predecessor_account_id());
+
+        // Pausing is no longer possible, so holders must be able to burn.
+        self.paused = false;
+        self.sunset = true;
+    }
+
+    pub fn is_sunset(&self) -> bool {
+        self.sunset
+    }
+
+    /// Page of tokens with their full ownership history, to archive the collection.
+    pub fn nft_export(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<ExportedToken> {
+        self.nft_tokens(from_index, limit)
+            .into_iter()
+            .map(|token| ExportedToken {
+                history: self
+                    .ownership_history
+                    .get(&token.token_id)
+                    .unwrap_or_default(),
+                token,
+            })
+            .collect()
+    }
+
+    pub(crate) fn assert_not_sunset(&self) {
+        assert!(!self.sunset, "Contract is in sunset mode");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn sunset_keeps_burns_and_views() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(owner(), 0));
+        contract.enter_sunset_mode();
+        assert!(contract.is_sunset());
+
+        let exported = contract.nft_export(None, None);
+        assert_eq!(exported.len(), 1);
+        assert_eq!(exported[0].token.token_id, "0");
+
+        testing_env!(get_context(nft(), 0));
+        contract.nft_burn("0".to_string());
+        assert!(contract.nft_export(None, None).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Contract is in sunset mode")]
+    fn mint_in_sunset_mode() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(get_context(owner(), 0));
+        contract.enter_sunset_mode();
+        testing_env!(context);
+        contract.nft_mint(
+            "1".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Contract is in sunset mode")]
+    fn propose_owner_in_sunset_mode() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = NftContract::default();
+        contract.enter_sunset_mode();
+        contract.propose_owner(ValidAccountId::try_from(bob()).unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "Contract is in sunset mode")]
+    fn cleanup_in_sunset_mode() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(get_context(owner(), 0));
+        contract.enter_sunset_mode();
+        testing_env!(context);
+        contract.cleanup_my_data();
+    }
+}
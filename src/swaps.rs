@@ -0,0 +1,331 @@
+use crate::*;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::Promise;
+
+/// Exchange of two tokens, each side optionally adding NEAR. The maker commits when
+/// proposing, the taker when accepting, and then either of them can execute it. The maker's
+/// token is locked until the swap is executed or cancelled.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Swap {
+    pub id: U64,
+    pub maker_id: AccountId,
+    pub maker_token_id: TokenId,
+    pub maker_deposit: U128, // in yoctoNEAR, paid to the taker
+    pub taker_id: AccountId,
+    pub taker_token_id: TokenId,
+    pub taker_deposit: U128, // in yoctoNEAR, paid to the maker
+    pub accepted: bool,
+    pub expires_at: U64, // block timestamp in nanoseconds
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// Proposes to swap the caller's `token_id`, plus `amount`, for `taker_token_id` of
+    /// `taker_id`. The deposit covers the amount and the storage of the swap, the rest is
+    /// refunded. Returns the swap id.
+    #[payable]
+    pub fn swap_propose(
+        &mut self,
+        token_id: TokenId,
+        taker_id: ValidAccountId,
+        taker_token_id: TokenId,
+        duration: U64,
+        amount: U128,
+    ) -> U64 {
+        self.assert_not_paused();
+        let maker_id = env::predecessor_account_id();
+        self.assert_token_owner(&token_id, &maker_id)
+            .assert_transferable();
+        self.assert_token_owner(&taker_token_id, taker_id.as_ref())
+            .assert_transferable();
+        assert!(!self.internal_is_locked(&token_id), "Token is locked");
+        assert!(duration.0 > 0, "Duration must be greater than zero");
+
+        let initial_storage_usage = env::storage_usage();
+        let id = self.next_swap_id;
+        self.next_swap_id += 1;
+        self.swaps.insert(
+            &id,
+            &Swap {
+                id: id.into(),
+                maker_id: maker_id.clone(),
+                maker_token_id: token_id.clone(),
+                maker_deposit: amount,
+                taker_id: taker_id.into(),
+                taker_token_id,
+                taker_deposit: U128(0),
+                accepted: false,
+                expires_at: math::add_u64(env::block_timestamp(), duration.0, "Expiry").into(),
+            },
+        );
+        self.swapped_tokens.insert(&token_id, &id);
+
+        let (storage_used, _) = self.internal_storage_delta(initial_storage_usage);
+        let required = math::add_u128(
+            amount.0,
+            Balance::from(storage_used) * env::storage_byte_cost(),
+            "Deposit",
+        );
+        assert!(
+            env::attached_deposit() >= required,
+            "Must attach {} yoctoNEAR to cover the amount and storage",
+            required
+        );
+        let refund = env::attached_deposit() - required;
+        if refund > 0 {
+            Promise::new(maker_id).transfer(refund);
+        }
+        id.into()
+    }
+
+    /// Commits the taker side of swap `id`, with the attached deposit.
+    #[payable]
+    pub fn swap_accept(&mut self, id: U64) {
+        self.assert_not_paused();
+        let mut swap = self.swaps.get(&id.0).expect("Swap not found");
+
+        if env::predecessor_account_id() != swap.taker_id {
+            env::panic(b"Unauthorized");
+        }
+
+        assert!(!swap.accepted, "Swap is already accepted");
+        assert!(
+            env::block_timestamp() < swap.expires_at.0,
+            "Swap has expired"
+        );
+        self.assert_token_owner(&swap.taker_token_id, &swap.taker_id);
+
+        swap.taker_deposit = env::attached_deposit().into();
+        swap.accepted = true;
+        self.swaps.insert(&id.0, &swap);
+    }
+
+    /// Exchanges the tokens and deposits of an accepted swap, if both sides still own
+    /// their token.
+    pub fn swap_execute(&mut self, id: U64) {
+        self.assert_not_paused();
+        let swap = self.swaps.get(&id.0).expect("Swap not found");
+        let caller_id = env::predecessor_account_id();
+
+        if caller_id != swap.maker_id && caller_id != swap.taker_id {
+            env::panic(b"Unauthorized");
+        }
+
+        assert!(swap.accepted, "Swap is not accepted");
+        assert!(
+            env::block_timestamp() < swap.expires_at.0,
+            "Swap has expired"
+        );
+        let maker_token = self.assert_token_owner(&swap.maker_token_id, &swap.maker_id);
+        let taker_token = self.assert_token_owner(&swap.taker_token_id, &swap.taker_id);
+
+        self.internal_remove_swap(&swap);
+        self.internal_transfer(
+            maker_token,
+            &swap.taker_id,
+            Some(env::predecessor_account_id()),
+            None,
+        );
+        self.internal_transfer(
+            taker_token,
+            &swap.maker_id,
+            Some(env::predecessor_account_id()),
+            None,
+        );
+        self.internal_record_activity(&swap.maker_token_id, ActivityKind::Swap);
+        self.internal_record_activity(&swap.taker_token_id, ActivityKind::Swap);
+
+        if swap.maker_deposit.0 > 0 {
+            Promise::new(swap.taker_id.clone()).transfer(swap.maker_deposit.0);
+        }
+        if swap.taker_deposit.0 > 0 {
+            Promise::new(swap.maker_id.clone()).transfer(swap.taker_deposit.0);
+        }
+        log!(
+            "Swap {} of {} and {}",
+            id.0,
+            swap.maker_token_id,
+            swap.taker_token_id
+        );
+    }
+
+    /// Drops swap `id` and refunds the deposits and its storage. The maker can cancel until
+    /// the swap is accepted, after that either side can once it has expired.
+    pub fn swap_cancel(&mut self, id: U64) {
+        let swap = self.swaps.get(&id.0).expect("Swap not found");
+        let caller_id = env::predecessor_account_id();
+        let expired = env::block_timestamp() >= swap.expires_at.0;
+        let allowed = if swap.accepted {
+            expired && (caller_id == swap.maker_id || caller_id == swap.taker_id)
+        } else {
+            caller_id == swap.maker_id || (expired && caller_id == swap.taker_id)
+        };
+        if !allowed {
+            env::panic(b"Unauthorized");
+        }
+
+        self.internal_remove_swap(&swap);
+        if swap.maker_deposit.0 > 0 {
+            Promise::new(swap.maker_id).transfer(swap.maker_deposit.0);
+        }
+        if swap.taker_deposit.0 > 0 {
+            Promise::new(swap.taker_id).transfer(swap.taker_deposit.0);
+        }
+    }
+
+    pub fn swap(&self, id: U64) -> Option<Swap> {
+        self.swaps.get(&id.0)
+    }
+
+    fn assert_token_owner(&self, token_id: &TokenId, account_id: &AccountId) -> Token {
+        let token = self.tokens_by_id.get(token_id).expect("Token not found");
+        assert_eq!(
+            &token.owner_id, account_id,
+            "Token {} is not owned by @{}",
+            token_id, account_id
+        );
+        token
+    }
+}
+
+impl NftContract {
+    /// Removes `swap` and unlocks the maker's token, refunding the storage to the maker.
+    fn internal_remove_swap(&mut self, swap: &Swap) {
+        let initial_storage_usage = env::storage_usage();
+        self.swaps.remove(&swap.id.0);
+        self.swapped_tokens.remove(&swap.maker_token_id);
+        let freed = initial_storage_usage.saturating_sub(env::storage_usage());
+        self.internal_refund_storage(&swap.maker_id, freed);
+    }
+
+    pub(crate) fn assert_not_swapped(&self, token_id: &TokenId) {
+        assert!(
+            self.swapped_tokens.get(token_id).is_none(),
+            "Token is offered in a swap"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn swap_tokens_with_top_up() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        contract.nft_mint(
+            "1".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        testing_env!(get_context(nft(), 10u128.pow(24)));
+        let id = contract.swap_propose(
+            "0".to_string(),
+            ValidAccountId::try_from(bob()).unwrap(),
+            "1".to_string(),
+            U64(100),
+            U128(5),
+        );
+        testing_env!(get_context(bob(), 0));
+        contract.swap_accept(id);
+        contract.swap_execute(id);
+
+        assert_eq!(contract.nft_token("0".to_string()).unwrap().owner_id, bob());
+        assert_eq!(contract.nft_token("1".to_string()).unwrap().owner_id, nft());
+        assert!(contract.swap(id).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Unauthorized")]
+    fn cancel_accepted_swap_before_expiry() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        contract.nft_mint(
+            "1".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        testing_env!(get_context(nft(), 10u128.pow(24)));
+        let id = contract.swap_propose(
+            "0".to_string(),
+            ValidAccountId::try_from(bob()).unwrap(),
+            "1".to_string(),
+            U64(100),
+            U128(0),
+        );
+        testing_env!(get_context(bob(), 0));
+        contract.swap_accept(id);
+        contract.swap_cancel(id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Token is offered in a swap")]
+    fn transfer_token_offered_in_swap() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        contract.nft_mint(
+            "1".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        testing_env!(get_context(nft(), 10u128.pow(24)));
+        contract.swap_propose(
+            "0".to_string(),
+            ValidAccountId::try_from(bob()).unwrap(),
+            "1".to_string(),
+            U64(100),
+            U128(0),
+        );
+        testing_env!(get_context(nft(), 1));
+        contract.nft_transfer(
+            ValidAccountId::try_from(owner()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+    }
+
+    #[test]
+    fn cancelled_swap_unlocks_token() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        contract.nft_mint(
+            "1".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        testing_env!(get_context(nft(), 10u128.pow(24)));
+        let id = contract.swap_propose(
+            "0".to_string(),
+            ValidAccountId::try_from(bob()).unwrap(),
+            "1".to_string(),
+            U64(100),
+            U128(0),
+        );
+        assert!(contract.internal_is_locked(&"0".to_string()));
+        testing_env!(get_context(nft(), 0));
+        contract.swap_cancel(id);
+        assert!(!contract.internal_is_locked(&"0".to_string()));
+    }
+}
@@ -0,0 +1,74 @@
+//! Accounts, contexts and tokens shared by the unit tests.
+
+use crate::*;
+use near_sdk::MockedBlockchain;
+use near_sdk::{testing_env, VMContext};
+
+pub(crate) fn owner() -> AccountId {
+    String::from("alice_near")
+}
+
+pub(crate) fn bob() -> AccountId {
+    String::from("bob.near")
+}
+
+pub(crate) fn nft() -> AccountId {
+    String::from("nft.near")
+}
+
+pub(crate) fn get_context(
+    predecessor_account_id: AccountId,
+    attached_deposit: Balance,
+) -> VMContext {
+    VMContext {
+        current_account_id: owner(),
+        signer_account_id: "bob_near".to_string(),
+        signer_account_pk: vec![0, 1, 2],
+        predecessor_account_id,
+        input: vec![],
+        block_index: 0,
+        block_timestamp: 0,
+        account_balance: 1000 * 10u128.pow(24),
+        account_locked_balance: 0,
+        storage_usage: 10u64.pow(6),
+        attached_deposit,
+        prepaid_gas: 2 * 10u64.pow(14),
+        random_seed: vec![0, 1, 2],
+        is_view: false,
+        output_data_receivers: vec![],
+        epoch_height: 19,
+    }
+}
+
+pub(crate) fn helper_token_metadata() -> TokenMetadata {
+    TokenMetadata {
+        title: Some("Mochi Rising".to_string()),
+        description: Some("Limited edition canvas".to_string()),
+        media: None,
+        media_hash: None,
+        copies: None,
+        issued_at: None,
+        expires_at: None,
+        starts_at: None,
+        updated_at: None,
+        extra: None,
+        reference: None,
+        reference_hash: None,
+        attributes: None,
+    }
+}
+
+pub(crate) fn helper_mint() -> (NftContract, VMContext) {
+    let context = get_context(nft(), 10u128.pow(24));
+    testing_env!(context.clone());
+    let mut contract = NftContract::default();
+    contract.nft_mint(
+        "0".to_string(),
+        helper_token_metadata(),
+        None,
+        None,
+        None,
+        None,
+    );
+    (contract, context)
+}
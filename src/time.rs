@@ -0,0 +1,207 @@
+//! ISO 8601 conversion for timestamps kept as nanoseconds since the Unix epoch.
+
+use near_sdk::Timestamp;
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Parses `YYYY-MM-DD` or `YYYY-MM-DDTHH:MM:SS`, with an optional fraction of up to 9 digits
+/// and an optional `Z` or `±HH:MM` offset. Datetimes without offset are taken as UTC.
+pub fn parse_iso8601(value: &str) -> Result<Timestamp, String> {
+    let invalid = || format!("Invalid ISO 8601 datetime: {}", value);
+    if !value.is_ascii() {
+        return Err(invalid());
+    }
+
+    let (date, time) = match value.find('T') {
+        Some(index) => (&value[..index], Some(&value[index + 1..])),
+        None => (value, None),
+    };
+    if date.len() != 10 || &date[4..5] != "-" || &date[7..8] != "-" {
+        return Err(invalid());
+    }
+    let year = digits(date, 0, 4).ok_or_else(invalid)? as i64;
+    let month = digits(date, 5, 2).ok_or_else(invalid)? as i64;
+    let day = digits(date, 8, 2).ok_or_else(invalid)? as i64;
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return Err(invalid());
+    }
+
+    let (mut seconds, mut nanos) = (days_from_civil(year, month, day) * SECONDS_PER_DAY, 0);
+    if let Some(time) = time {
+        if time.len() < 8 || &time[2..3] != ":" || &time[5..6] != ":" {
+            return Err(invalid());
+        }
+        let hour = digits(time, 0, 2).ok_or_else(invalid)? as i64;
+        let minute = digits(time, 3, 2).ok_or_else(invalid)? as i64;
+        let second = digits(time, 6, 2).ok_or_else(invalid)? as i64;
+        if hour > 23 || minute > 59 || second > 59 {
+            return Err(invalid());
+        }
+        seconds += hour * 3600 + minute * 60 + second;
+
+        let mut rest = &time[8..];
+        if let Some(fraction) = rest.strip_prefix('.') {
+            let len = fraction
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(fraction.len());
+            if len == 0 || len > 9 {
+                return Err(invalid());
+            }
+            nanos = digits(fraction, 0, len).ok_or_else(invalid)? * 10u64.pow(9 - len as u32);
+            rest = &fraction[len..];
+        }
+
+        match rest {
+            "" | "Z" => {}
+            _ if rest.len() == 6 && &rest[3..4] == ":" => {
+                let offset = digits(rest, 1, 2).ok_or_else(invalid)? as i64 * 3600
+                    + digits(rest, 4, 2).ok_or_else(invalid)? as i64 * 60;
+                match &rest[..1] {
+                    "+" => seconds -= offset,
+                    "-" => seconds += offset,
+                    _ => return Err(invalid()),
+                }
+            }
+            _ => return Err(invalid()),
+        }
+    }
+
+    if seconds < 0 {
+        return Err(format!("Datetime is before the Unix epoch: {}", value));
+    }
+    (seconds as u64)
+        .checked_mul(NANOS_PER_SECOND)
+        .and_then(|timestamp| timestamp.checked_add(nanos))
+        .ok_or_else(|| format!("Datetime is out of range: {}", value))
+}
+
+/// Formats as `YYYY-MM-DDTHH:MM:SSZ`, adding the fraction of a second only when it is not zero.
+pub fn format_iso8601(timestamp: Timestamp) -> String {
+    let seconds = (timestamp / NANOS_PER_SECOND) as i64;
+    let nanos = timestamp % NANOS_PER_SECOND;
+    let (year, month, day) = civil_from_days(seconds / SECONDS_PER_DAY);
+    let time = seconds % SECONDS_PER_DAY;
+
+    let mut value = format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    );
+    if nanos > 0 {
+        value.push_str(format!(".{:09}", nanos).trim_end_matches('0'));
+    }
+    value.push('Z');
+    value
+}
+
+/// Serde adapter that keeps `Option<Timestamp>` fields as ISO 8601 strings in JSON.
+pub mod iso8601_option {
+    use super::*;
+    use near_sdk::serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<Timestamp>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(timestamp) => serializer.serialize_some(&format_iso8601(*timestamp)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Timestamp>, D::Error> {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(value) => parse_iso8601(&value).map(Some).map_err(de::Error::custom),
+            None => Ok(None),
+        }
+    }
+}
+
+fn digits(value: &str, from: usize, len: usize) -> Option<u64> {
+    let digits = value.get(from..from + len)?;
+    if !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Inverse of `days_from_civil`.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::TokenMetadata;
+
+    #[test]
+    fn metadata_dates_as_iso8601() {
+        let metadata: TokenMetadata = near_sdk::serde_json::from_str(
+            r#"{"issued_at":"2021-10-20T12:30:00.5+02:00","expires_at":"2024-02-29"}"#,
+        )
+        .unwrap();
+        assert_eq!(metadata.issued_at, Some(1_634_725_800_500_000_000));
+        assert_eq!(metadata.expires_at, Some(1_709_164_800_000_000_000));
+
+        let json = near_sdk::serde_json::to_string(&metadata).unwrap();
+        assert!(json.contains(r#""issued_at":"2021-10-20T10:30:00.5Z""#));
+        assert!(json.contains(r#""expires_at":"2024-02-29T00:00:00Z""#));
+    }
+
+    #[test]
+    fn metadata_dates_reject_malformed() {
+        for value in &[
+            "2021-13-01",
+            "2023-02-29",
+            "2021-10-20T25:00:00Z",
+            "yesterday",
+        ] {
+            let json = format!(r#"{{"issued_at":"{}"}}"#, value);
+            assert!(near_sdk::serde_json::from_str::<TokenMetadata>(&json).is_err());
+        }
+    }
+}
@@ -0,0 +1,332 @@
+use crate::*;
+use near_sdk::{ext_contract, Gas, Promise, PromiseResult, Timestamp};
+use std::collections::HashMap;
+
+/// Number of contracts the destination policy can list.
+pub const MAX_POLICY_DESTINATIONS: usize = 50;
+
+const GAS_FOR_RESOLVE_TRANSFER: Gas = 10_000_000_000_000;
+const GAS_FOR_NFT_TRANSFER_CALL: Gas = 25_000_000_000_000 + GAS_FOR_RESOLVE_TRANSFER;
+
+/// Contracts that `nft_transfer_call` can send tokens to.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum DestinationPolicy {
+    Open,                      // any contract
+    Allowlist(Vec<AccountId>), // only the listed contracts
+    Denylist(Vec<AccountId>),  // any contract but the listed ones
+}
+
+#[ext_contract(ext_receiver)]
+pub trait NonFungibleTokenReceiver {
+    /// Returns `true` when the token should be returned to `previous_owner_id`.
+    fn nft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_id: AccountId,
+        token_id: TokenId,
+        msg: String,
+    ) -> near_sdk::PromiseOrValue<bool>;
+}
+
+#[ext_contract(ext_transfer_resolver)]
+trait NonFungibleTokenResolver {
+    fn nft_resolve_transfer(
+        &mut self,
+        owner_id: AccountId,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        approved_account_ids: Option<HashMap<AccountId, u64>>,
+        approvals_expire_at: Option<HashMap<AccountId, Timestamp>>,
+    ) -> bool;
+}
+
+#[near_bindgen]
+impl NftContract {
+    pub fn set_destination_policy(&mut self, policy: DestinationPolicy) {
+        self.assert_admin();
+        if let DestinationPolicy::Allowlist(contracts) | DestinationPolicy::Denylist(contracts) =
+            &policy
+        {
+            assert!(
+                contracts.len() <= MAX_POLICY_DESTINATIONS,
+                "Cannot list more than {} contracts",
+                MAX_POLICY_DESTINATIONS
+            );
+        }
+        self.destination_policy = policy;
+    }
+
+    pub fn destination_policy(&self) -> DestinationPolicy {
+        self.destination_policy.clone()
+    }
+
+    /// Transfers the token and calls `nft_on_transfer` on `receiver_id`, which can ask for it back.
+    #[payable]
+    pub fn nft_transfer_call(
+        &mut self,
+        receiver_id: ValidAccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        msg: String,
+    ) -> Promise {
+        assert!(
+            env::prepaid_gas() > GAS_FOR_NFT_TRANSFER_CALL,
+            "More gas is required"
+        );
+        self.assert_destination_allowed(receiver_id.as_ref());
+
+        let initial_storage_usage = env::storage_usage();
+        let sender_id = env::predecessor_account_id();
+        let token = self.tokens_by_id.get(&token_id).expect("Token not found");
+        let approved_account_ids = token.approved_account_ids;
+        let approvals_expire_at = token.approvals_expire_at;
+        let previous_owner_id =
+            self.internal_transfer_by_owner(&token_id, receiver_id.as_ref(), approval_id);
+        self.internal_charge_storage(initial_storage_usage);
+
+        ext_receiver::nft_on_transfer(
+            sender_id,
+            previous_owner_id.clone(),
+            token_id.clone(),
+            msg,
+            receiver_id.as_ref(),
+            0,
+            env::prepaid_gas() - GAS_FOR_NFT_TRANSFER_CALL,
+        )
+        .then(ext_transfer_resolver::nft_resolve_transfer(
+            previous_owner_id,
+            receiver_id.into(),
+            token_id,
+            Some(approved_account_ids),
+            Some(approvals_expire_at),
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_TRANSFER,
+        ))
+    }
+
+    /// Returns the token to `owner_id` when the receiver asked for it or failed, along with
+    /// the approvals it had before the transfer. Returns `true` when the token stays with
+    /// `receiver_id`.
+    #[private]
+    pub fn nft_resolve_transfer(
+        &mut self,
+        owner_id: AccountId,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        approved_account_ids: Option<HashMap<AccountId, u64>>,
+        approvals_expire_at: Option<HashMap<AccountId, Timestamp>>,
+    ) -> bool {
+        let return_token = match env::promise_result(0) {
+            PromiseResult::Successful(value) => {
+                near_sdk::serde_json::from_slice::<bool>(&value).unwrap_or(true)
+            }
+            _ => true,
+        };
+        let approvals = approved_account_ids.map(|approved_account_ids| {
+            (
+                approved_account_ids,
+                approvals_expire_at.unwrap_or_default(),
+            )
+        });
+        self.internal_resolve_transfer(&owner_id, &receiver_id, &token_id, approvals, return_token)
+    }
+
+    pub(crate) fn internal_resolve_transfer(
+        &mut self,
+        owner_id: &AccountId,
+        receiver_id: &AccountId,
+        token_id: &TokenId,
+        approvals: Option<(HashMap<AccountId, u64>, HashMap<AccountId, Timestamp>)>,
+        return_token: bool,
+    ) -> bool {
+        if !return_token {
+            return true;
+        }
+
+        let token = match self.tokens_by_id.get(token_id) {
+            Some(token) if &token.owner_id == receiver_id => token,
+            // Burned or already moved on by the receiver.
+            _ => return true,
+        };
+        // The receiver locked it meanwhile.
+        if self.internal_is_locked(token_id) {
+            log!("Cannot return {} from @{}", token_id, receiver_id);
+            return true;
+        }
+
+        log!("Return {} from @{} to @{}", token_id, receiver_id, owner_id);
+
+        // Giving the token back undoes the transfer, so the blacklist and compliance checks
+        // don't apply. Drops the approvals the receiver made in the meantime.
+        self.internal_move_token(token, owner_id, None, None);
+        if let Some((approved_account_ids, approvals_expire_at)) = approvals {
+            let mut token = self.tokens_by_id.get(token_id).unwrap();
+            token.approved_account_ids = approved_account_ids;
+            token.approvals_expire_at = approvals_expire_at;
+            token.remove_expired_approvals();
+            self.tokens_by_id.insert(token_id, &token);
+        }
+        self.internal_record_activity(token_id, ActivityKind::Transfer);
+        false
+    }
+
+    /// Whether `token_id` is staked, leased, bridged, offered in a swap, waiting to be claimed
+    /// or owed to the bidder of an auction.
+    pub(crate) fn internal_is_locked(&self, token_id: &TokenId) -> bool {
+        self.stakes.get(token_id).is_some()
+            || matches!(self.leases.get(token_id), Some(lease) if lease.is_active())
+            || self.bridge_locks.get(token_id).is_some()
+            || self.swapped_tokens.get(token_id).is_some()
+            || self.gifts.get(token_id).is_some()
+            || self.drops.get(token_id).is_some()
+            || matches!(self.auctions.get(token_id), Some(auction) if auction.highest_bid.is_some())
+    }
+
+    fn assert_destination_allowed(&self, receiver_id: &AccountId) {
+        let allowed = match &self.destination_policy {
+            DestinationPolicy::Open => true,
+            DestinationPolicy::Allowlist(contracts) => contracts.contains(receiver_id),
+            DestinationPolicy::Denylist(contracts) => !contracts.contains(receiver_id),
+        };
+        assert!(
+            allowed,
+            "Contract {} is not an allowed transfer_call destination",
+            receiver_id
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::test_utils::get_logs;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    #[should_panic(expected = "Contract market.near is not an allowed transfer_call destination")]
+    fn transfer_call_to_denied_destination() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(get_context(owner(), 0));
+        contract
+            .set_destination_policy(DestinationPolicy::Denylist(vec!["market.near".to_string()]));
+        testing_env!(context);
+        contract.nft_transfer_call(
+            ValidAccountId::try_from("market.near").unwrap(),
+            "0".to_string(),
+            None,
+            "".to_string(),
+        );
+    }
+
+    #[test]
+    fn resolve_transfer_returns_token() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(context);
+        contract.nft_transfer_call(
+            ValidAccountId::try_from("market.near").unwrap(),
+            "0".to_string(),
+            None,
+            "".to_string(),
+        );
+        let market = "market.near".to_string();
+        let token_id = "0".to_string();
+        assert!(!contract.internal_resolve_transfer(&nft(), &market, &token_id, None, true));
+        assert_eq!(contract.nft_token("0".to_string()).unwrap().owner_id, nft());
+        assert!(contract.internal_resolve_transfer(&nft(), &market, &token_id, None, true));
+    }
+
+    #[test]
+    fn resolve_transfer_restores_approvals() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(context.clone());
+        contract.nft_approve(
+            "0".to_string(),
+            ValidAccountId::try_from(bob()).unwrap(),
+            None,
+            None,
+        );
+        let approvals = contract.tokens_by_id.get(&"0".to_string()).unwrap();
+        let approvals = (
+            approvals.approved_account_ids,
+            approvals.approvals_expire_at,
+        );
+        contract.nft_transfer_call(
+            ValidAccountId::try_from("market.near").unwrap(),
+            "0".to_string(),
+            None,
+            "".to_string(),
+        );
+
+        // A malicious receiver approves an accomplice before asking for the return.
+        let market = "market.near".to_string();
+        testing_env!(get_context(market.clone(), 10u128.pow(24)));
+        contract.nft_approve(
+            "0".to_string(),
+            ValidAccountId::try_from(owner()).unwrap(),
+            None,
+            None,
+        );
+        assert!(!contract.internal_resolve_transfer(
+            &nft(),
+            &market,
+            &"0".to_string(),
+            Some(approvals),
+            true
+        ));
+
+        let token = contract.nft_token("0".to_string()).unwrap();
+        assert_eq!(token.owner_id, nft());
+        assert!(token.approved_account_ids.contains_key(&bob()));
+        assert!(!token.approved_account_ids.contains_key(&owner()));
+    }
+
+    #[test]
+    fn resolve_transfer_returns_token_to_blacklisted_owner() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(context);
+        contract.nft_transfer_call(
+            ValidAccountId::try_from("market.near").unwrap(),
+            "0".to_string(),
+            None,
+            "".to_string(),
+        );
+
+        testing_env!(get_context(owner(), 0));
+        contract.blacklist_add(ValidAccountId::try_from(nft()).unwrap());
+        let market = "market.near".to_string();
+        assert!(!contract.internal_resolve_transfer(&nft(), &market, &"0".to_string(), None, true));
+        assert_eq!(contract.nft_token("0".to_string()).unwrap().owner_id, nft());
+        assert!(get_logs().contains(
+            &"EVENT_JSON:{\"standard\":\"nep171\",\"version\":\"1.1.0\",\"event\":\"nft_transfer\",\"data\":[{\"old_owner_id\":\"market.near\",\"new_owner_id\":\"nft.near\",\"token_ids\":[\"0\"]}]}"
+                .to_string()
+        ));
+    }
+
+    #[test]
+    fn resolve_transfer_keeps_token_locked_by_receiver() {
+        let (mut contract, context) = helper_mint();
+        testing_env!(context);
+        contract.nft_transfer_call(
+            ValidAccountId::try_from("market.near").unwrap(),
+            "0".to_string(),
+            None,
+            "".to_string(),
+        );
+
+        // The receiver bridges the token away, then asks for the return.
+        let market = "market.near".to_string();
+        testing_env!(get_context(market.clone(), 10u128.pow(24)));
+        contract.bridge_lock("0".to_string(), "ethereum".to_string(), "0xabc".to_string());
+        assert!(contract.internal_resolve_transfer(&nft(), &market, &"0".to_string(), None, true));
+        assert_eq!(
+            contract.nft_bridge_lock("0".to_string()).unwrap().owner_id,
+            market
+        );
+    }
+}
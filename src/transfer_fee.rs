@@ -0,0 +1,153 @@
+use crate::*;
+#[cfg(feature = "royalties")]
+use near_sdk::json_types::U128;
+
+/// Share of `nft_transfer_payout` sales sent to the treasury.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TransferFee {
+    pub treasury_id: AccountId,
+    pub fee: u32, // in basis points of the sale price
+}
+
+#[cfg(feature = "royalties")]
+#[near_bindgen]
+impl NftContract {
+    /// Takes `fee` out of every sale through `nft_transfer_payout`, or stops it with `None`.
+    pub fn set_transfer_fee(&mut self, transfer_fee: Option<TransferFee>) {
+        self.assert_admin();
+        if let Some(transfer_fee) = &transfer_fee {
+            assert!(
+                transfer_fee.fee <= ROYALTY_DENOMINATOR,
+                "Transfer fee cannot exceed {}",
+                ROYALTY_DENOMINATOR
+            );
+        }
+        self.transfer_fee = transfer_fee;
+    }
+
+    pub fn transfer_fee(&self) -> Option<TransferFee> {
+        self.transfer_fee.clone()
+    }
+
+    /// Lets the marketplace `account_id` settle sales without the transfer fee.
+    pub fn fee_exempt_add(&mut self, account_id: ValidAccountId) {
+        self.assert_admin();
+        assert!(
+            self.fee_exempt.insert(account_id.as_ref()),
+            "Account is already exempt"
+        );
+    }
+
+    pub fn fee_exempt_remove(&mut self, account_id: ValidAccountId) {
+        self.assert_admin();
+        assert!(
+            self.fee_exempt.remove(account_id.as_ref()),
+            "Account is not exempt"
+        );
+    }
+
+    pub fn fee_exempt_accounts(
+        &self,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<AccountId> {
+        let from_index = from_index.map_or(0, |index| index.0 as usize);
+        let limit = limit.map_or(usize::MAX, |limit| limit as usize);
+        self.fee_exempt
+            .iter()
+            .skip(from_index)
+            .take(limit)
+            .collect()
+    }
+
+    /// Treasury cut of a sale for `balance` settled by `marketplace_id`, if any. Without a
+    /// marketplace, the cut a marketplace that is not exempt would pay.
+    pub(crate) fn internal_transfer_fee(
+        &self,
+        balance: Balance,
+        marketplace_id: Option<&AccountId>,
+    ) -> Option<(AccountId, Balance)> {
+        let transfer_fee = self.transfer_fee.as_ref()?;
+        if matches!(marketplace_id, Some(account_id) if self.fee_exempt.contains(account_id)) {
+            return None;
+        }
+        let fee = math::mul_div(
+            balance,
+            Balance::from(transfer_fee.fee),
+            Balance::from(ROYALTY_DENOMINATOR),
+        );
+        Some((transfer_fee.treasury_id.clone(), fee)).filter(|(_, fee)| *fee > 0)
+    }
+}
+
+#[cfg(all(test, feature = "royalties"))]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U128;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn transfer_payout_with_fee() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(owner(), 0));
+        contract.set_transfer_fee(Some(TransferFee {
+            treasury_id: owner(),
+            fee: 250,
+        }));
+
+        testing_env!(get_context(nft(), 10u128.pow(24)));
+        contract.nft_approve_all(ValidAccountId::try_from(bob()).unwrap());
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        let payout = contract.nft_transfer_payout(
+            ValidAccountId::try_from(bob()).unwrap(),
+            "0".to_string(),
+            None,
+            U128(10_000),
+            10,
+        );
+        assert_eq!(payout.payout[&owner()], U128(250));
+        assert_eq!(payout.payout[&nft()], U128(9_750));
+        assert_eq!(contract.nft_token("0".to_string()).unwrap().owner_id, bob());
+    }
+
+    #[test]
+    fn payout_with_fee() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(owner(), 0));
+        contract.set_transfer_fee(Some(TransferFee {
+            treasury_id: owner(),
+            fee: 250,
+        }));
+        let payout = contract.nft_payout("0".to_string(), U128(10_000), 10);
+        assert_eq!(payout.payout[&owner()], U128(250));
+        assert_eq!(payout.payout[&nft()], U128(9_750));
+    }
+
+    #[test]
+    fn transfer_payout_exempt_from_fee() {
+        let (mut contract, _) = helper_mint();
+        testing_env!(get_context(owner(), 0));
+        contract.set_transfer_fee(Some(TransferFee {
+            treasury_id: owner(),
+            fee: 250,
+        }));
+        contract.fee_exempt_add(ValidAccountId::try_from(bob()).unwrap());
+
+        testing_env!(get_context(nft(), 10u128.pow(24)));
+        contract.nft_approve_all(ValidAccountId::try_from(bob()).unwrap());
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        let payout = contract.nft_transfer_payout(
+            ValidAccountId::try_from(bob()).unwrap(),
+            "0".to_string(),
+            None,
+            U128(10_000),
+            10,
+        );
+        assert_eq!(payout.payout.len(), 1);
+        assert_eq!(payout.payout[&nft()], U128(10_000));
+    }
+}
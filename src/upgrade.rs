@@ -0,0 +1,65 @@
+use crate::*;
+use near_sdk::{Gas, Promise};
+
+/// Gas kept for this call itself, the rest is attached to `migrate`.
+const GAS_FOR_UPGRADE: Gas = 20_000_000_000_000;
+
+#[near_bindgen]
+impl NftContract {
+    /// Deploys `code` on this account and calls `migrate` on the new code.
+    /// Both actions run in the same receipt, so a failed migration reverts the deploy.
+    pub fn upgrade(&self, #[serializer(borsh)] code: Vec<u8>) -> Promise {
+        self.assert_admin();
+        let gas = env::prepaid_gas()
+            .checked_sub(env::used_gas() + GAS_FOR_UPGRADE)
+            .expect("More gas is required");
+
+        // The new code reads this to find the layout it migrates from.
+        migrate::write_state_version();
+
+        log!(
+            "Upgrade contract code ({} bytes) by @{}",
+            code.len(),
+            env::predecessor_account_id()
+        );
+
+        Promise::new(env::current_account_id())
+            .deploy_contract(code)
+            .function_call(b"migrate".to_vec(), vec![], 0, gas)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn upgrade_from_dao() {
+        testing_env!(get_context(owner(), 0));
+        let mut contract = NftContract::default();
+        contract.set_dao_account(Some(ValidAccountId::try_from(bob()).unwrap()));
+        testing_env!(get_context(bob(), 0));
+        contract.upgrade(vec![0, 97, 115, 109]);
+    }
+
+    #[test]
+    #[should_panic(expected = "More gas is required")]
+    fn upgrade_without_gas() {
+        let mut context = get_context(owner(), 0);
+        context.prepaid_gas = 10u64.pow(13);
+        testing_env!(context);
+        let contract = NftContract::default();
+        contract.upgrade(vec![0, 97, 115, 109]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unauthorized")]
+    fn upgrade_from_non_owner() {
+        let (contract, _) = helper_mint();
+        contract.upgrade(vec![0, 97, 115, 109]);
+    }
+}
@@ -0,0 +1,162 @@
+use crate::*;
+use std::cmp::Reverse;
+
+/// Number of recent activity entries kept per token.
+pub const MAX_ACTIVITY_PER_TOKEN: usize = 10;
+
+/// Number of tokens a single account can watch.
+pub const MAX_WATCHED_TOKENS: usize = 20;
+
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Debug,
+)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityKind {
+    Mint,
+    Transfer,
+    List,
+    Delist,
+    AuctionCreate,
+    AuctionCancel,
+    Freeze,
+    Reveal,
+    Decline,
+    Recover,
+    Reissue,
+    Burn,
+    Sale,
+    Swap,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Activity {
+    pub token_id: TokenId,
+    pub kind: ActivityKind,
+    pub account_id: AccountId, // account that triggered the activity
+    pub timestamp: U64,        // block timestamp in nanoseconds
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// Adds `token_id` to the caller's watchlist. The deposit covers its storage, refunded by
+    /// `unwatch_token`.
+    #[payable]
+    pub fn watch_token(&mut self, token_id: TokenId) {
+        self.assert_not_paused();
+        assert!(
+            self.tokens_by_id.get(&token_id).is_some(),
+            "Token not found"
+        );
+
+        let account_id = env::predecessor_account_id();
+        let initial_storage_usage = env::storage_usage();
+        let mut watched = self.watchlists.get(&account_id).unwrap_or_default();
+        if !watched.contains(&token_id) {
+            assert!(
+                watched.len() < MAX_WATCHED_TOKENS,
+                "Cannot watch more than {} tokens",
+                MAX_WATCHED_TOKENS
+            );
+            watched.push(token_id);
+            self.watchlists.insert(&account_id, &watched);
+        }
+        self.internal_charge_storage(initial_storage_usage);
+    }
+
+    pub fn unwatch_token(&mut self, token_id: TokenId) {
+        self.assert_not_paused();
+        let account_id = env::predecessor_account_id();
+        let initial_storage_usage = env::storage_usage();
+        let mut watched = self.watchlists.get(&account_id).unwrap_or_default();
+        watched.retain(|id| id != &token_id);
+        if watched.is_empty() {
+            self.watchlists.remove(&account_id);
+        } else {
+            self.watchlists.insert(&account_id, &watched);
+        }
+        let freed = initial_storage_usage.saturating_sub(env::storage_usage());
+        self.internal_refund_storage(&account_id, freed);
+    }
+
+    pub fn watchlist(&self, account_id: ValidAccountId) -> Vec<TokenId> {
+        self.watchlists.get(account_id.as_ref()).unwrap_or_default()
+    }
+
+    /// Recent activity on the tokens watched by `account_id`, newest first.
+    pub fn watched_activity(&self, account_id: ValidAccountId) -> Vec<Activity> {
+        let mut activity: Vec<Activity> = self
+            .watchlist(account_id)
+            .iter()
+            .flat_map(|token_id| self.nft_activity(token_id.clone()).into_iter().rev())
+            .collect();
+        activity.sort_by_key(|activity| Reverse(activity.timestamp));
+        activity
+    }
+
+    pub fn nft_activity(&self, token_id: TokenId) -> Vec<Activity> {
+        self.token_activity.get(&token_id).unwrap_or_default()
+    }
+
+    pub(crate) fn internal_record_activity(&mut self, token_id: &TokenId, kind: ActivityKind) {
+        let mut activity = self.token_activity.get(token_id).unwrap_or_default();
+        if activity.len() == MAX_ACTIVITY_PER_TOKEN {
+            activity.remove(0);
+        }
+        activity.push(Activity {
+            token_id: token_id.clone(),
+            kind,
+            account_id: env::predecessor_account_id(),
+            timestamp: env::block_timestamp().into(),
+        });
+        self.token_activity.insert(token_id, &activity);
+    }
+}
+
+#[cfg(all(test, feature = "marketplace"))]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U128;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn watched_activity() {
+        let (mut contract, mut context) = helper_mint();
+        contract.nft_mint(
+            "1".to_string(),
+            helper_token_metadata(),
+            None,
+            None,
+            None,
+            None,
+        );
+        testing_env!(get_context(bob(), 10u128.pow(22)));
+        contract.watch_token("0".to_string());
+
+        context.block_timestamp = 10;
+        testing_env!(context);
+        contract.nft_list("1".to_string(), U128(10), None);
+        contract.nft_list("0".to_string(), U128(10), None);
+        contract.nft_delist("0".to_string());
+
+        let kinds = contract
+            .watched_activity(ValidAccountId::try_from(bob()).unwrap())
+            .into_iter()
+            .map(|activity| activity.kind)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![ActivityKind::Delist, ActivityKind::List, ActivityKind::Mint]
+        );
+
+        testing_env!(get_context(bob(), 0));
+        contract.unwatch_token("0".to_string());
+        assert!(contract
+            .watched_activity(ValidAccountId::try_from(bob()).unwrap())
+            .is_empty());
+    }
+}
@@ -0,0 +1,256 @@
+use crate::*;
+use near_sdk::{ext_contract, Gas, Promise, PromiseOrValue, PromiseResult};
+
+const GAS_FOR_FOREIGN_TRANSFER: Gas = 20_000_000_000_000;
+const GAS_FOR_RESOLVE_UNWRAP: Gas = 20_000_000_000_000;
+
+/// Token of another NFT contract held in custody for the wrapper token minted here.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct WrappedToken {
+    pub nft_contract_id: AccountId,
+    pub token_id: TokenId,
+}
+
+impl WrappedToken {
+    /// Id of the wrapper token, unique for each foreign token.
+    fn wrapper_id(&self) -> TokenId {
+        format!("{}:{}", self.nft_contract_id, self.token_id)
+    }
+}
+
+#[ext_contract(ext_foreign_nft)]
+trait ForeignNft {
+    fn nft_transfer(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+    );
+}
+
+#[ext_contract(ext_unwrap_resolver)]
+trait UnwrapResolver {
+    fn on_unwrap(&mut self, token_id: TokenId, owner_id: AccountId);
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// Lets tokens of `nft_contract_id` be wrapped by sending them with `nft_transfer_call`.
+    pub fn wrap_contract_add(&mut self, nft_contract_id: ValidAccountId) {
+        self.assert_admin();
+        self.wrap_contracts.insert(nft_contract_id.as_ref());
+    }
+
+    /// Stops wrapping tokens of `nft_contract_id`. Wrapped tokens can still be unwrapped.
+    pub fn wrap_contract_remove(&mut self, nft_contract_id: ValidAccountId) {
+        self.assert_admin();
+        self.wrap_contracts.remove(nft_contract_id.as_ref());
+    }
+
+    pub fn wrap_contracts(&self) -> Vec<AccountId> {
+        self.wrap_contracts.to_vec()
+    }
+
+    /// Foreign token wrapped by `token_id`, if it is a wrapper.
+    pub fn nft_wrapped(&self, token_id: TokenId) -> Option<WrappedToken> {
+        self.wrapped_tokens.get(&token_id)
+    }
+
+    /// Sends back the foreign token wrapped by `token_id` and burns the wrapper. The wrapper is
+    /// held by the contract until the foreign contract confirms, and returned if it fails.
+    /// Requires one yoctoNEAR, forwarded to the foreign `nft_transfer`.
+    #[payable]
+    pub fn unwrap(&mut self, token_id: TokenId) -> Promise {
+        near_sdk::assert_one_yocto();
+        self.assert_not_paused();
+        let wrapped = self
+            .wrapped_tokens
+            .get(&token_id)
+            .expect("Token is not a wrapper");
+        let token = self.tokens_by_id.get(&token_id).unwrap();
+        let owner_id = env::predecessor_account_id();
+
+        if owner_id != token.owner_id {
+            env::panic(b"Unauthorized");
+        }
+
+        self.assert_not_blacklisted(&owner_id);
+        assert!(!self.internal_is_locked(&token_id), "Token is locked");
+
+        // The contract only holds the wrapper in escrow, so the transfer checks, ex. the
+        // compliance allowlist, don't apply to it.
+        let contract_id = env::current_account_id();
+        self.internal_move_token(token, &contract_id, Some(owner_id.clone()), None);
+
+        ext_foreign_nft::nft_transfer(
+            owner_id.clone(),
+            wrapped.token_id,
+            None,
+            Some("unwrap".to_string()),
+            &wrapped.nft_contract_id,
+            1,
+            GAS_FOR_FOREIGN_TRANSFER,
+        )
+        .then(ext_unwrap_resolver::on_unwrap(
+            token_id,
+            owner_id,
+            &contract_id,
+            0,
+            GAS_FOR_RESOLVE_UNWRAP,
+        ))
+    }
+
+    /// Burns the wrapper once the foreign token is back with `owner_id`, otherwise returns it
+    /// even if `owner_id` was blacklisted meanwhile, as the wrapper was theirs.
+    #[private]
+    pub fn on_unwrap(&mut self, token_id: TokenId, owner_id: AccountId) {
+        let token = self.tokens_by_id.get(&token_id).unwrap();
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            self.wrapped_tokens.remove(&token_id);
+            self.internal_burn(&token);
+            self.internal_record_activity(&token_id, ActivityKind::Burn);
+            self.internal_emit(NftEvent::NftBurn(vec![NftBurnLog {
+                owner_id,
+                token_ids: vec![token_id],
+                memo: Some("unwrap".to_string()),
+            }]));
+        } else {
+            log!("Unwrap of {} failed", token_id);
+            self.internal_move_token(token, &owner_id, None, None);
+        }
+    }
+}
+
+#[near_bindgen]
+impl NftContract {
+    /// Wraps a token sent by another NFT contract, minting the wrapper for
+    /// `previous_owner_id`. Its storage comes from the prepaid storage balance of
+    /// `previous_owner_id`, see `storage_deposit`. Implements `NonFungibleTokenReceiver`:
+    /// the token is always kept, any failure makes the sending contract return it.
+    pub fn nft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_id: AccountId,
+        token_id: TokenId,
+        msg: String,
+    ) -> PromiseOrValue<bool> {
+        self.assert_not_paused();
+        let nft_contract_id = env::predecessor_account_id();
+        assert!(
+            self.wrap_contracts.contains(&nft_contract_id),
+            "Tokens of {} cannot be wrapped",
+            nft_contract_id
+        );
+
+        let wrapped = WrappedToken {
+            nft_contract_id,
+            token_id,
+        };
+        let wrapper_id = wrapped.wrapper_id();
+        log!(
+            "Wrap {} for @{} sent by @{}: {}",
+            wrapper_id,
+            previous_owner_id,
+            sender_id,
+            msg
+        );
+
+        let initial_storage_usage = env::storage_usage();
+        let metadata = TokenMetadata {
+            title: Some(format!("Wrapped {}", wrapped.token_id)),
+            description: None,
+            media: None,
+            media_hash: None,
+            copies: None,
+            issued_at: None,
+            expires_at: None,
+            starts_at: None,
+            updated_at: None,
+            extra: Some(near_sdk::serde_json::to_string(&wrapped).unwrap()),
+            reference: None,
+            reference_hash: None,
+            attributes: None,
+        };
+        self.wrapped_tokens.insert(&wrapper_id, &wrapped);
+        let token = self.internal_new_token(
+            wrapper_id,
+            previous_owner_id.clone(),
+            previous_owner_id.clone(),
+        );
+        self.internal_mint_token(token, metadata, 0);
+        self.internal_charge_prepaid_storage(&previous_owner_id, initial_storage_usage);
+        PromiseOrValue::Value(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::serde::export::TryFrom;
+    use near_sdk::MockedBlockchain;
+    use near_sdk::{testing_env, PromiseOrValue};
+
+    #[test]
+    fn wrap_and_unwrap_foreign_token() {
+        let foreign = "foreign.near".to_string();
+        testing_env!(get_context(owner(), 0));
+        let mut contract = NftContract::default();
+        contract.wrap_contract_add(ValidAccountId::try_from(foreign.clone()).unwrap());
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        contract.storage_deposit(None);
+
+        testing_env!(get_context(foreign.clone(), 0));
+        let keep = contract.nft_on_transfer(bob(), bob(), "7".to_string(), String::new());
+        assert!(matches!(keep, PromiseOrValue::Value(false)));
+        let wrapper_id = "foreign.near:7".to_string();
+        assert_eq!(
+            contract.nft_token(wrapper_id.clone()).unwrap().owner_id,
+            bob()
+        );
+        assert_eq!(
+            contract
+                .nft_wrapped(wrapper_id.clone())
+                .unwrap()
+                .nft_contract_id,
+            foreign
+        );
+
+        testing_env!(get_context(bob(), 1));
+        contract.unwrap(wrapper_id.clone());
+        assert_eq!(contract.nft_token(wrapper_id).unwrap().owner_id, owner());
+    }
+
+    #[test]
+    #[should_panic(expected = "Tokens of foreign.near cannot be wrapped")]
+    fn wrap_requires_allowed_contract() {
+        testing_env!(get_context("foreign.near".to_string(), 0));
+        let mut contract = NftContract::default();
+        contract.nft_on_transfer(bob(), bob(), "7".to_string(), String::new());
+    }
+
+    #[test]
+    fn unwrap_in_compliance_mode() {
+        let foreign = "foreign.near".to_string();
+        testing_env!(get_context(owner(), 0));
+        let mut contract = NftContract::new(true);
+        contract.compliance_allow(ValidAccountId::try_from(bob()).unwrap());
+        contract.wrap_contract_add(ValidAccountId::try_from(foreign.clone()).unwrap());
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        contract.storage_deposit(None);
+
+        testing_env!(get_context(foreign, 0));
+        contract.nft_on_transfer(bob(), bob(), "7".to_string(), String::new());
+        testing_env!(get_context(bob(), 1));
+        contract.unwrap("foreign.near:7".to_string());
+        assert_eq!(
+            contract
+                .nft_token("foreign.near:7".to_string())
+                .unwrap()
+                .owner_id,
+            owner()
+        );
+    }
+}
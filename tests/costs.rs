@@ -0,0 +1,159 @@
+//! Storage cost regression checks for the most common calls.
+//!
+//! Each operation runs against the mocked blockchain and the storage it adds is compared
+//! with a budget in bytes. Run `make bench` to print the measured costs. Budgets sit a few
+//! percent above the measured cost, so any growth fails here. When a change makes an
+//! operation more expensive on purpose, raise its budget in the same commit and say what
+//! the extra bytes are for. Gas is not metered by the mocked blockchain, it is checked in
+//! the sandbox by `make gas`.
+
+use near_sdk::json_types::ValidAccountId;
+#[cfg(feature = "marketplace")]
+use near_sdk::json_types::U128;
+use near_sdk::serde::export::TryFrom;
+use near_sdk::MockedBlockchain;
+use near_sdk::{env, testing_env, AccountId, Balance, VMContext};
+use nft_demo::{NftContract, TokenMetadata};
+
+const MINT_BUDGET: u64 = 1550;
+const TRANSFER_TO_NEW_RECEIVER_BUDGET: u64 = 485;
+const TRANSFER_TO_HOLDER_BUDGET: u64 = 85;
+const APPROVE_BUDGET: u64 = 30;
+#[cfg(feature = "marketplace")]
+const LIST_BUDGET: u64 = 275;
+#[cfg(feature = "marketplace")]
+const BUY_BUDGET: u64 = 330;
+
+fn contract_id() -> AccountId {
+    "nft.near".to_string()
+}
+
+fn alice() -> AccountId {
+    "alice.near".to_string()
+}
+
+fn bob() -> AccountId {
+    "bob.near".to_string()
+}
+
+fn get_context(predecessor_account_id: AccountId, attached_deposit: Balance) -> VMContext {
+    VMContext {
+        current_account_id: contract_id(),
+        signer_account_id: predecessor_account_id.clone(),
+        signer_account_pk: vec![0, 1, 2],
+        predecessor_account_id,
+        input: vec![],
+        block_index: 0,
+        block_timestamp: 0,
+        account_balance: 1000 * 10u128.pow(24),
+        account_locked_balance: 0,
+        storage_usage: 10u64.pow(6),
+        attached_deposit,
+        prepaid_gas: 2 * 10u64.pow(14),
+        random_seed: vec![0, 1, 2],
+        is_view: false,
+        output_data_receivers: vec![],
+        epoch_height: 19,
+    }
+}
+
+fn metadata() -> TokenMetadata {
+    TokenMetadata {
+        title: Some("Mochi Rising".to_string()),
+        description: Some("Limited edition canvas".to_string()),
+        media: None,
+        media_hash: None,
+        copies: None,
+        issued_at: None,
+        expires_at: None,
+        starts_at: None,
+        updated_at: None,
+        extra: None,
+        reference: None,
+        reference_hash: None,
+        attributes: None,
+    }
+}
+
+/// Runs `f` as `account_id` and returns the bytes it added to the contract storage.
+fn measure<F: FnOnce()>(account_id: AccountId, f: F) -> u64 {
+    testing_env!(get_context(account_id, 10u128.pow(24)));
+    let initial_storage_usage = env::storage_usage();
+    f();
+    env::storage_usage().saturating_sub(initial_storage_usage)
+}
+
+fn assert_within_budget(operation: &str, used: u64, budget: u64) {
+    println!("{:<28} {:>6} bytes (budget {})", operation, used, budget);
+    assert!(
+        used <= budget,
+        "{} uses {} bytes, over its budget of {}",
+        operation,
+        used,
+        budget
+    );
+}
+
+#[test]
+fn storage_costs_within_budget() {
+    testing_env!(get_context(alice(), 0));
+    let mut contract = NftContract::default();
+
+    let used = measure(alice(), || {
+        contract.nft_mint("0".to_string(), metadata(), None, None, None, None);
+    });
+    assert_within_budget("nft_mint", used, MINT_BUDGET);
+    assert!(
+        contract.estimated_mint_cost(metadata()).0
+            >= Balance::from(used) * env::storage_byte_cost(),
+        "estimated_mint_cost is below the storage of a mint"
+    );
+
+    let used = measure(alice(), || {
+        contract.nft_transfer(
+            ValidAccountId::try_from(bob()).unwrap(),
+            "0".to_string(),
+            None,
+        );
+    });
+    assert_within_budget(
+        "nft_transfer (new receiver)",
+        used,
+        TRANSFER_TO_NEW_RECEIVER_BUDGET,
+    );
+
+    measure(alice(), || {
+        contract.nft_mint("1".to_string(), metadata(), None, None, None, None);
+    });
+    let used = measure(alice(), || {
+        contract.nft_transfer(
+            ValidAccountId::try_from(bob()).unwrap(),
+            "1".to_string(),
+            None,
+        );
+    });
+    assert_within_budget("nft_transfer (holder)", used, TRANSFER_TO_HOLDER_BUDGET);
+
+    let used = measure(bob(), || {
+        contract.nft_approve(
+            "1".to_string(),
+            ValidAccountId::try_from(alice()).unwrap(),
+            None,
+            None,
+        );
+    });
+    assert_within_budget("nft_approve", used, APPROVE_BUDGET);
+
+    #[cfg(feature = "marketplace")]
+    {
+        let used = measure(bob(), || {
+            contract.nft_list("1".to_string(), U128(10u128.pow(23)), None);
+        });
+        assert_within_budget("nft_list", used, LIST_BUDGET);
+
+        let used = measure(alice(), || {
+            contract.nft_buy("1".to_string(), None);
+        });
+        assert_within_budget("nft_buy", used, BUY_BUDGET);
+    }
+}